@@ -1,9 +1,97 @@
+use std::process::Command;
+
 fn main() {
     linker_be_nice();
+    build_info();
+    dfu_public_key();
+    if std::env::var_os("CARGO_FEATURE_DEFMT").is_some() {
+        println!("cargo:rustc-link-arg=-Tdefmt.x");
+    }
     // make sure linkall.x is the last linker script (otherwise might cause problems with flip-link)
     println!("cargo:rustc-link-arg=-Tlinkall.x");
 }
 
+// Embeds the git commit, build profile and enabled features, see src/build_info.rs
+fn build_info() {
+    let git = |args: &[&str]| {
+        Command::new("git")
+            .args(args)
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+    };
+
+    let commit = match git(&["rev-parse", "--short=8", "HEAD"]) {
+        Some(commit) => match git(&["status", "--porcelain", "--untracked-files=no"]) {
+            Some(status) if !status.is_empty() => format!("{commit}-dirty"),
+            _ => commit,
+        },
+        None => "unknown".to_string(),
+    };
+
+    let mut features: Vec<String> = std::env::vars()
+        .filter_map(|(key, _)| key.strip_prefix("CARGO_FEATURE_").map(str::to_string))
+        .map(|feature| feature.to_lowercase().replace('_', "-"))
+        .filter(|feature| feature != "default")
+        .collect();
+    features.sort();
+
+    println!("cargo:rustc-env=BUILD_GIT_COMMIT={commit}");
+    println!(
+        "cargo:rustc-env=BUILD_PROFILE={}",
+        std::env::var("PROFILE").unwrap_or_default()
+    );
+    println!("cargo:rustc-env=BUILD_FEATURES={}", features.join(","));
+
+    for path in [
+        ".git/HEAD",
+        ".git/refs",
+        ".git/index",
+        "src",
+        "build.rs",
+        "Cargo.toml",
+    ] {
+        println!("cargo:rerun-if-changed={path}");
+    }
+}
+
+// Embeds the Ed25519 key that firmware updates must be signed with, given as
+// 64 hex digits in DFU_PUBLIC_KEY, see `magene-tool dfu-key`. Without it the
+// firmware refuses every update over BLE.
+fn dfu_public_key() {
+    println!("cargo:rerun-if-env-changed=DFU_PUBLIC_KEY");
+    let key = match std::env::var("DFU_PUBLIC_KEY") {
+        Ok(hex) => parse_key(hex.trim()).unwrap_or_else(|| {
+            panic!("DFU_PUBLIC_KEY must be 64 hex digits, the output of `magene-tool dfu-key`")
+        }),
+        Err(_) => {
+            println!(
+                "cargo:warning=DFU_PUBLIC_KEY is not set, this firmware refuses all updates over BLE"
+            );
+            [0; 32]
+        }
+    };
+
+    let out_dir = std::env::var("OUT_DIR").unwrap();
+    std::fs::write(
+        std::path::Path::new(&out_dir).join("dfu_public_key.rs"),
+        format!("{key:?}"),
+    )
+    .unwrap();
+}
+
+fn parse_key(hex: &str) -> Option<[u8; 32]> {
+    if hex.len() != 64 || !hex.is_ascii() {
+        return None;
+    }
+    let mut key = [0u8; 32];
+    for (byte, digits) in key.iter_mut().zip(hex.as_bytes().chunks(2)) {
+        *byte = u8::from_str_radix(std::str::from_utf8(digits).ok()?, 16).ok()?;
+    }
+    Some(key)
+}
+
 fn linker_be_nice() {
     let args: Vec<String> = std::env::args().collect();
     if args.len() > 1 {
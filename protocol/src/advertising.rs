@@ -0,0 +1,241 @@
+// Advertising and scan response data: a sequence of AD structures
+// [length][AD type][data: length - 1], see the Core Specification Supplement, part A.
+
+pub const AD_FLAGS: u8 = 0x01;
+pub const AD_INCOMPLETE_UUIDS_16: u8 = 0x02;
+pub const AD_COMPLETE_UUIDS_16: u8 = 0x03;
+pub const AD_INCOMPLETE_UUIDS_32: u8 = 0x04;
+pub const AD_COMPLETE_UUIDS_32: u8 = 0x05;
+pub const AD_INCOMPLETE_UUIDS_128: u8 = 0x06;
+pub const AD_COMPLETE_UUIDS_128: u8 = 0x07;
+pub const AD_SHORTENED_LOCAL_NAME: u8 = 0x08;
+pub const AD_COMPLETE_LOCAL_NAME: u8 = 0x09;
+pub const AD_TX_POWER_LEVEL: u8 = 0x0A;
+pub const AD_SERVICE_DATA_16: u8 = 0x16;
+pub const AD_APPEARANCE: u8 = 0x19;
+pub const AD_SERVICE_DATA_32: u8 = 0x20;
+pub const AD_SERVICE_DATA_128: u8 = 0x21;
+pub const AD_MANUFACTURER_DATA: u8 = 0xFF;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Uuid {
+    Uuid16(u16),
+    Uuid32(u32),
+    Uuid128(u128),
+}
+
+impl Uuid {
+    // `data` must be 2, 4 or 16 bytes, little endian as sent over the air
+    fn from_le_bytes(data: &[u8]) -> Option<Self> {
+        match data.len() {
+            2 => Some(Uuid::Uuid16(u16::from_le_bytes(data.try_into().ok()?))),
+            4 => Some(Uuid::Uuid32(u32::from_le_bytes(data.try_into().ok()?))),
+            16 => Some(Uuid::Uuid128(u128::from_le_bytes(data.try_into().ok()?))),
+            _ => None,
+        }
+    }
+}
+
+// List of service UUIDs of one size
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UuidList<'a> {
+    pub complete: bool,
+    size: usize,
+    data: &'a [u8],
+}
+
+impl<'a> UuidList<'a> {
+    pub fn iter(&self) -> impl Iterator<Item = Uuid> + 'a {
+        self.data
+            .chunks_exact(self.size)
+            .filter_map(Uuid::from_le_bytes)
+    }
+
+    pub fn contains(&self, uuid: Uuid) -> bool {
+        self.iter().any(|u| u == uuid)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdStructure<'a> {
+    Flags(u8),
+    ServiceUuids(UuidList<'a>),
+    // Names are not guaranteed to be valid UTF-8
+    ShortenedLocalName(&'a [u8]),
+    CompleteLocalName(&'a [u8]),
+    TxPowerLevel(i8),
+    ServiceData { uuid: Uuid, data: &'a [u8] },
+    Appearance(u16),
+    ManufacturerData { company: u16, data: &'a [u8] },
+    Unknown { ad_type: u8, data: &'a [u8] },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdError {
+    // The length field points past the end of the data
+    Truncated { offset: usize },
+    // The data is too short or has the wrong size for its AD type
+    InvalidLength { ad_type: u8, length: usize },
+}
+
+impl<'a> AdStructure<'a> {
+    fn decode(ad_type: u8, data: &'a [u8]) -> Result<Self, AdError> {
+        let invalid = AdError::InvalidLength {
+            ad_type,
+            length: data.len(),
+        };
+        let uuids = |size: usize, complete: bool| {
+            if data.len() % size != 0 {
+                return Err(invalid);
+            }
+            Ok(AdStructure::ServiceUuids(UuidList {
+                complete,
+                size,
+                data,
+            }))
+        };
+        let service_data = |size: usize| {
+            if data.len() < size {
+                return Err(invalid);
+            }
+            let (uuid, data) = data.split_at(size);
+            let uuid = Uuid::from_le_bytes(uuid).ok_or(invalid)?;
+            Ok(AdStructure::ServiceData { uuid, data })
+        };
+
+        match ad_type {
+            AD_FLAGS => match data {
+                [flags] => Ok(AdStructure::Flags(*flags)),
+                _ => Err(invalid),
+            },
+            AD_INCOMPLETE_UUIDS_16 => uuids(2, false),
+            AD_COMPLETE_UUIDS_16 => uuids(2, true),
+            AD_INCOMPLETE_UUIDS_32 => uuids(4, false),
+            AD_COMPLETE_UUIDS_32 => uuids(4, true),
+            AD_INCOMPLETE_UUIDS_128 => uuids(16, false),
+            AD_COMPLETE_UUIDS_128 => uuids(16, true),
+            AD_SHORTENED_LOCAL_NAME => Ok(AdStructure::ShortenedLocalName(data)),
+            AD_COMPLETE_LOCAL_NAME => Ok(AdStructure::CompleteLocalName(data)),
+            AD_TX_POWER_LEVEL => match data {
+                [power] => Ok(AdStructure::TxPowerLevel(*power as i8)),
+                _ => Err(invalid),
+            },
+            AD_SERVICE_DATA_16 => service_data(2),
+            AD_SERVICE_DATA_32 => service_data(4),
+            AD_SERVICE_DATA_128 => service_data(16),
+            AD_APPEARANCE => match data {
+                [low, high] => Ok(AdStructure::Appearance(u16::from_le_bytes([*low, *high]))),
+                _ => Err(invalid),
+            },
+            AD_MANUFACTURER_DATA => match data {
+                [low, high, data @ ..] => Ok(AdStructure::ManufacturerData {
+                    company: u16::from_le_bytes([*low, *high]),
+                    data,
+                }),
+                _ => Err(invalid),
+            },
+            _ => Ok(AdStructure::Unknown { ad_type, data }),
+        }
+    }
+}
+
+// Iterates over the AD structures of an advertisement. A structure that can not
+// be decoded is returned as an error; the iterator stops after a truncated one,
+// since the following structures can not be found anymore.
+#[derive(Debug, Clone)]
+pub struct AdStructures<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> AdStructures<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data, offset: 0 }
+    }
+}
+
+impl<'a> Iterator for AdStructures<'a> {
+    type Item = Result<AdStructure<'a>, AdError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let offset = self.offset;
+        let length = *self.data.get(offset)? as usize;
+        // A zero length marks the end of the significant part, the rest is padding
+        if length == 0 {
+            self.offset = self.data.len();
+            return None;
+        }
+
+        let Some(structure) = self.data.get(offset + 1..offset + 1 + length) else {
+            self.offset = self.data.len();
+            return Some(Err(AdError::Truncated { offset }));
+        };
+        self.offset = offset + 1 + length;
+        Some(AdStructure::decode(structure[0], &structure[1..]))
+    }
+}
+
+// Advertising or scan response data of one report
+#[derive(Debug, Clone, Copy)]
+pub struct Advertisement<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Advertisement<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    // Well-formed structures, malformed ones are skipped
+    pub fn structures(&self) -> impl Iterator<Item = AdStructure<'a>> + 'a {
+        AdStructures::new(self.data).filter_map(Result::ok)
+    }
+
+    // The complete local name, or the shortened one if that is all there is
+    pub fn local_name(&self) -> Option<&'a str> {
+        let mut shortened = None;
+        for structure in self.structures() {
+            match structure {
+                AdStructure::CompleteLocalName(name) => {
+                    if let Ok(name) = core::str::from_utf8(name) {
+                        return Some(name);
+                    }
+                }
+                AdStructure::ShortenedLocalName(name) if shortened.is_none() => {
+                    shortened = core::str::from_utf8(name).ok();
+                }
+                _ => {}
+            }
+        }
+        shortened
+    }
+
+    pub fn advertises_service(&self, uuid: Uuid) -> bool {
+        self.structures().any(|structure| match structure {
+            AdStructure::ServiceUuids(uuids) => uuids.contains(uuid),
+            AdStructure::ServiceData { uuid: service, .. } => service == uuid,
+            _ => false,
+        })
+    }
+
+    pub fn tx_power(&self) -> Option<i8> {
+        self.structures().find_map(|structure| match structure {
+            AdStructure::TxPowerLevel(power) => Some(power),
+            _ => None,
+        })
+    }
+
+    pub fn appearance(&self) -> Option<u16> {
+        self.structures().find_map(|structure| match structure {
+            AdStructure::Appearance(appearance) => Some(appearance),
+            _ => None,
+        })
+    }
+
+    pub fn manufacturer_data(&self) -> Option<(u16, &'a [u8])> {
+        self.structures().find_map(|structure| match structure {
+            AdStructure::ManufacturerData { company, data } => Some((company, data)),
+            _ => None,
+        })
+    }
+}
@@ -0,0 +1,102 @@
+use embassy_time::{Duration, Instant};
+
+use crate::clock::{Clock, SystemClock};
+use crate::radar::{RadarFrame, ThreatLevel};
+use crate::tracker::TrackEvent;
+
+pub const ALERT_EVENT_COUNT: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlertEvent {
+    NewVehicle = 0,
+    FastApproach = 1,
+    VehiclePassed = 2,
+    // A vehicle became a high threat, also when it is escalated for its time to contact
+    HighThreat = 3,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AlertConfig {
+    // Shortest time between two alerts of the same kind
+    pub min_interval: Duration,
+    // Closing speed from which an approaching vehicle is announced
+    pub fast_approach_speed: u8,
+}
+
+// Turns radar frames and the tracker's vehicle events into alert events. Fast
+// approaches and high threats are announced when they begin, and every kind of
+// alert at most once per `min_interval`.
+pub struct AlertDetector<C = SystemClock> {
+    config: AlertConfig,
+    fast_approach: bool,
+    high_threat: bool,
+    last_alert: [Option<Instant>; ALERT_EVENT_COUNT],
+    clock: C,
+}
+
+impl AlertDetector {
+    pub fn new(config: AlertConfig) -> Self {
+        Self::with_clock(config, SystemClock)
+    }
+}
+
+impl<C: Clock> AlertDetector<C> {
+    pub fn with_clock(config: AlertConfig, clock: C) -> Self {
+        Self {
+            config,
+            fast_approach: false,
+            high_threat: false,
+            last_alert: [None; ALERT_EVENT_COUNT],
+            clock,
+        }
+    }
+
+    fn detect(&mut self, frame: &RadarFrame) -> Option<AlertEvent> {
+        let fast_approach = frame
+            .fastest()
+            .is_some_and(|target| target.speed >= self.config.fast_approach_speed);
+        let high_threat = frame.max_threat() == ThreatLevel::High;
+        let event = if high_threat && !self.high_threat {
+            Some(AlertEvent::HighThreat)
+        } else if fast_approach && !self.fast_approach {
+            Some(AlertEvent::FastApproach)
+        } else {
+            None
+        };
+        self.fast_approach = fast_approach;
+        self.high_threat = high_threat;
+        event
+    }
+
+    pub fn update(&mut self, frame: &RadarFrame) -> Option<AlertEvent> {
+        let event = self.detect(frame)?;
+        self.rate_limit(event)
+    }
+
+    // A vehicle that was lost far away did not pass, so there is nothing to announce
+    pub fn track_event(&mut self, event: TrackEvent) -> Option<AlertEvent> {
+        let event = match event {
+            TrackEvent::NewVehicle(_) => AlertEvent::NewVehicle,
+            TrackEvent::VehiclePassed(_) => AlertEvent::VehiclePassed,
+            TrackEvent::VehicleLost(_) => return None,
+        };
+        self.rate_limit(event)
+    }
+
+    fn rate_limit(&mut self, event: AlertEvent) -> Option<AlertEvent> {
+        let now = self.clock.now();
+
+        let last_alert = &mut self.last_alert[event as usize];
+        if last_alert.is_some_and(|last| now < last + self.config.min_interval) {
+            return None;
+        }
+        *last_alert = Some(now);
+        Some(event)
+    }
+
+    // The radar was lost, whatever is seen next begins anew
+    pub fn reset(&mut self) {
+        self.fast_approach = false;
+        self.high_threat = false;
+    }
+}
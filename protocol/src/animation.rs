@@ -0,0 +1,379 @@
+use embassy_time::{Duration, Instant};
+use heapless::Vec;
+use rgb::RGB;
+
+use crate::clock::{Clock, SystemClock};
+use crate::radar::{RadarFrame, RadarTarget, ThreatLevel};
+
+pub const MAX_KEYFRAMES: usize = 8;
+pub const LAYER_COUNT: usize = 4;
+
+const BLACK: RGB<u8> = RGB::new(0, 0, 0);
+const RED: RGB<u8> = RGB::new(255, 0, 0);
+const ORANGE: RGB<u8> = RGB::new(255, 165, 0);
+
+// Refresh interval while a keyframe fades into the next one
+pub const FADE_FRAME_TIME: Duration = Duration::from_millis(20);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Keyframe {
+    pub color: RGB<u8>,
+    pub level: u8,
+    pub duration: Duration,
+    // Interpolate towards the next keyframe instead of holding the value
+    pub fade: bool,
+}
+
+impl Keyframe {
+    pub const fn hold(color: RGB<u8>, level: u8, duration: Duration) -> Self {
+        Self {
+            color,
+            level,
+            duration,
+            fade: false,
+        }
+    }
+
+    pub const fn fade(color: RGB<u8>, level: u8, duration: Duration) -> Self {
+        Self {
+            color,
+            level,
+            duration,
+            fade: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Animation {
+    keyframes: Vec<Keyframe, MAX_KEYFRAMES>,
+    repeat: bool,
+}
+
+impl Animation {
+    // Keyframes beyond MAX_KEYFRAMES are dropped
+    pub fn new(keyframes: &[Keyframe], repeat: bool) -> Self {
+        let mut animation = Self {
+            keyframes: Vec::new(),
+            repeat,
+        };
+        for keyframe in keyframes.iter().take(MAX_KEYFRAMES) {
+            let _ = animation.keyframes.push(*keyframe);
+        }
+        animation
+    }
+
+    pub fn solid(color: RGB<u8>, level: u8) -> Self {
+        Self::new(
+            &[Keyframe::hold(color, level, Duration::from_secs(3600))],
+            true,
+        )
+    }
+
+    pub fn blink(color: RGB<u8>, level: u8, on_time: Duration, period: Duration) -> Self {
+        let off_time = if period > on_time {
+            period - on_time
+        } else {
+            Duration::from_millis(0)
+        };
+        Self::new(
+            &[
+                Keyframe::hold(color, level, on_time),
+                Keyframe::hold(BLACK, level, off_time),
+            ],
+            true,
+        )
+    }
+
+    pub fn breathe(color: RGB<u8>, level: u8, period: Duration) -> Self {
+        let half = period / 2;
+        Self::new(
+            &[
+                Keyframe::fade(color, 0, half),
+                Keyframe::fade(color, level, half),
+            ],
+            true,
+        )
+    }
+
+    pub fn sequence(colors: &[RGB<u8>], level: u8, step: Duration) -> Self {
+        let mut animation = Self::new(&[], true);
+        for color in colors.iter().take(MAX_KEYFRAMES) {
+            let _ = animation
+                .keyframes
+                .push(Keyframe::hold(*color, level, step));
+        }
+        animation
+    }
+
+    pub fn total_duration(&self) -> Duration {
+        self.keyframes
+            .iter()
+            .fold(Duration::from_millis(0), |total, keyframe| {
+                total + keyframe.duration
+            })
+    }
+
+    // Position inside the animation, None once a non-repeating animation has finished
+    fn locate(&self, elapsed: Duration) -> Option<(usize, Duration)> {
+        let total = self.total_duration();
+        if self.keyframes.is_empty() || total.as_ticks() == 0 {
+            return None;
+        }
+
+        let mut offset = if self.repeat {
+            Duration::from_ticks(elapsed.as_ticks() % total.as_ticks())
+        } else if elapsed >= total {
+            return None;
+        } else {
+            elapsed
+        };
+
+        for (index, keyframe) in self.keyframes.iter().enumerate() {
+            if offset < keyframe.duration {
+                return Some((index, offset));
+            }
+            offset -= keyframe.duration;
+        }
+        None
+    }
+
+    pub fn sample(&self, elapsed: Duration) -> Option<(RGB<u8>, u8)> {
+        let (index, offset) = self.locate(elapsed)?;
+        let keyframe = self.keyframes[index];
+
+        if !keyframe.fade {
+            return Some((keyframe.color, keyframe.level));
+        }
+
+        let next = match self.keyframes.get(index + 1) {
+            Some(next) => *next,
+            None if self.repeat => self.keyframes[0],
+            None => keyframe,
+        };
+
+        let progress = offset.as_ticks();
+        let span = keyframe.duration.as_ticks();
+        Some((
+            RGB::new(
+                lerp(keyframe.color.r, next.color.r, progress, span),
+                lerp(keyframe.color.g, next.color.g, progress, span),
+                lerp(keyframe.color.b, next.color.b, progress, span),
+            ),
+            lerp(keyframe.level, next.level, progress, span),
+        ))
+    }
+
+    // Time until the sampled value changes next
+    pub fn next_change(&self, elapsed: Duration) -> Option<Duration> {
+        let (index, offset) = self.locate(elapsed)?;
+        let keyframe = self.keyframes[index];
+
+        if keyframe.fade {
+            Some(FADE_FRAME_TIME)
+        } else {
+            Some(keyframe.duration - offset)
+        }
+    }
+}
+
+fn lerp(from: u8, to: u8, progress: u64, span: u64) -> u8 {
+    if span == 0 {
+        return to;
+    }
+    let from = from as i64;
+    let to = to as i64;
+    (from + (to - from) * progress as i64 / span as i64) as u8
+}
+
+// Layers in ascending priority, the highest active layer is shown
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Layer {
+    Idle = 0,
+    Status = 1,
+    Alert = 2,
+    Error = 3,
+}
+
+pub struct AnimationEngine {
+    layers: [Option<(Animation, Instant)>; LAYER_COUNT],
+}
+
+impl Default for AnimationEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AnimationEngine {
+    pub fn new() -> Self {
+        Self {
+            layers: [const { None }; LAYER_COUNT],
+        }
+    }
+
+    // Replaces the animation of a layer. A repeating animation that replaces another
+    // repeating one keeps its phase, so frequent updates do not restart the pattern.
+    pub fn set(&mut self, layer: Layer, animation: Animation, now: Instant) {
+        let slot = &mut self.layers[layer as usize];
+        let start = match slot {
+            Some((current, start)) if current.repeat && animation.repeat => *start,
+            _ => now,
+        };
+        *slot = Some((animation, start));
+    }
+
+    pub fn clear(&mut self, layer: Layer) {
+        self.layers[layer as usize] = None;
+    }
+
+    pub fn is_active(&self, layer: Layer, now: Instant) -> bool {
+        match &self.layers[layer as usize] {
+            Some((animation, start)) => animation
+                .locate(now.saturating_duration_since(*start))
+                .is_some(),
+            None => false,
+        }
+    }
+
+    // Colour and level of the highest layer that is still running
+    pub fn sample(&self, now: Instant) -> (Option<Layer>, RGB<u8>, u8) {
+        for (index, slot) in self.layers.iter().enumerate().rev() {
+            if let Some((animation, start)) = slot {
+                if let Some((color, level)) =
+                    animation.sample(now.saturating_duration_since(*start))
+                {
+                    return (Some(Self::layer(index)), color, level);
+                }
+            }
+        }
+        (None, BLACK, 0)
+    }
+
+    // Next instant the rendered output may change
+    pub fn next_update(&self, now: Instant) -> Instant {
+        for (animation, start) in self.layers.iter().rev().flatten() {
+            if let Some(remaining) = animation.next_change(now.saturating_duration_since(*start)) {
+                return now + remaining;
+            }
+        }
+        Instant::MAX
+    }
+
+    fn layer(index: usize) -> Layer {
+        match index {
+            0 => Layer::Idle,
+            1 => Layer::Status,
+            2 => Layer::Alert,
+            _ => Layer::Error,
+        }
+    }
+}
+
+// Settings of the radar indication on the status LED
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RadarIndicationConfig {
+    pub brightness: u8,
+    // Level of the layers below the alert once the road has been clear for `dim_delay`
+    pub dim_brightness: u8,
+    pub dim_delay: Duration,
+    // Vehicles at or beyond this range blink slowest and in green
+    pub max_range: u8,
+    // Closing speed from which a vehicle blinks fast red whatever its threat level
+    pub fast_approach_speed: u8,
+}
+
+// Radar part of the status LED: the alert layer for approaching vehicles, and the
+// dimming of the layers below it once no vehicle has been seen for a while
+#[derive(Debug, Clone)]
+pub struct RadarIndication<C = SystemClock> {
+    config: RadarIndicationConfig,
+    frame: RadarFrame,
+    last_traffic: Instant,
+    clock: C,
+}
+
+impl RadarIndication {
+    pub fn new(config: RadarIndicationConfig) -> Self {
+        Self::with_clock(config, SystemClock)
+    }
+}
+
+impl<C: Clock> RadarIndication<C> {
+    pub fn with_clock(config: RadarIndicationConfig, clock: C) -> Self {
+        Self {
+            config,
+            frame: RadarFrame::offline(),
+            last_traffic: clock.now(),
+            clock,
+        }
+    }
+
+    // None while no radar data is available
+    pub fn set_frame(&mut self, frame: Option<RadarFrame>) {
+        self.frame = frame.unwrap_or_else(RadarFrame::offline);
+        if !self.frame.is_empty() {
+            self.last_traffic = self.clock.now();
+        }
+    }
+
+    // A fast vehicle, or one that reaches the rider soon enough to be escalated
+    // to a high threat even if it is slower
+    fn fast_approach(&self) -> bool {
+        self.frame
+            .fastest()
+            .is_some_and(|target| target.speed >= self.config.fast_approach_speed)
+            || self.frame.max_threat() == ThreatLevel::High
+    }
+
+    fn threat_color(&self, target: &RadarTarget) -> RGB<u8> {
+        if target.threat == ThreatLevel::High {
+            return RED;
+        }
+
+        // Fade from green at the edge of the range to red right behind the bike
+        let max_range = self.config.max_range.max(1) as u16;
+        let range = (target.range as u16).min(max_range);
+        let red = (255 * (max_range - range) / max_range) as u8;
+        let mut color = RGB::new(red, 255 - red, 0);
+        if target.threat == ThreatLevel::Medium {
+            color.r = ORANGE.r;
+            color.g = color.g.min(ORANGE.g);
+        }
+        color
+    }
+
+    pub fn alert_animation(&self) -> Option<Animation> {
+        if self.fast_approach() {
+            return Some(Animation::blink(
+                RED,
+                self.config.brightness,
+                Duration::from_millis(75),
+                Duration::from_millis(150),
+            ));
+        }
+
+        let target = self.frame.closest()?;
+        // Blink faster the closer the vehicle gets
+        let range = target.range.min(self.config.max_range) as u64;
+        Some(Animation::blink(
+            self.threat_color(target),
+            self.config.brightness,
+            Duration::from_millis(200),
+            Duration::from_millis(250 + range * 5),
+        ))
+    }
+
+    // Everything below the alert layer dims once the road has been clear for a while
+    pub fn level_limit(&self, layer: Option<Layer>) -> u8 {
+        if layer >= Some(Layer::Alert) {
+            return u8::MAX;
+        }
+
+        if self.clock.now() > self.last_traffic + self.config.dim_delay {
+            self.config.dim_brightness
+        } else {
+            u8::MAX
+        }
+    }
+}
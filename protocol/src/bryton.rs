@@ -0,0 +1,66 @@
+use crate::magene::{PAGE_1, PAGE_2};
+use crate::radar::{
+    RadarFrame, RadarTarget, PAGE_SIZE, PAGE_STATUS_OFFLINE, TARGETS_PER_PAGE, TARGET_SLOT_SIZE,
+};
+
+// Radar frame forwarded to the client in the Bryton Gardia format: two pages in
+// the layout described in `radar`, 0x30 and 0x31 back to back.
+pub const FRAME_SIZE: usize = 2 * PAGE_SIZE;
+// Targets a frame has room for
+pub const CAPACITY: usize = FRAME_SIZE / PAGE_SIZE * TARGETS_PER_PAGE;
+
+// The two pages of the L508 go out as the radar sent them, unless the cycle had
+// further pages or the pipeline changed their targets. Otherwise the frame is
+// encoded anew, and a frame with more targets than CAPACITY only carries the
+// most important ones.
+pub fn encode(frame: &RadarFrame) -> [u8; FRAME_SIZE] {
+    if let Some(data) = pass_through(frame) {
+        return data;
+    }
+
+    let mut data = [0u8; FRAME_SIZE];
+    let cycle = match frame.online {
+        true => frame.cycle,
+        false => PAGE_STATUS_OFFLINE,
+    };
+    let targets = frame.prioritized(CAPACITY);
+
+    for (index, page) in data.chunks_exact_mut(PAGE_SIZE).enumerate() {
+        page[0] = PAGE_1 + index as u8;
+        page[1] = cycle;
+        let slots = page[2..].chunks_exact_mut(TARGET_SLOT_SIZE);
+        for (slot, target) in slots.zip(targets.iter().skip(index * TARGETS_PER_PAGE)) {
+            slot.copy_from_slice(&[target.range, target.speed, target.threat as u8]);
+        }
+    }
+    data
+}
+
+// The source pages, if the frame still carries their targets as the format
+// does, so bytes the proxy does not decode reach the client unchanged
+fn pass_through(frame: &RadarFrame) -> Option<[u8; FRAME_SIZE]> {
+    let [page1, page2] = frame.pages.as_slice() else {
+        return None;
+    };
+    if page1[0] != PAGE_1 || page2[0] != PAGE_2 {
+        return None;
+    }
+
+    let source = RadarFrame::from_pages([page1, page2]);
+    let carried = |target: &RadarTarget| (target.range, target.speed, target.threat);
+    let unchanged = source.online == frame.online
+        && source.cycle == frame.cycle
+        && source
+            .targets
+            .iter()
+            .map(carried)
+            .eq(frame.targets.iter().map(carried));
+    if !unchanged {
+        return None;
+    }
+
+    let mut data = [0u8; FRAME_SIZE];
+    data[..PAGE_SIZE].copy_from_slice(page1);
+    data[PAGE_SIZE..].copy_from_slice(page2);
+    Some(data)
+}
@@ -0,0 +1,83 @@
+// btsnoop file format as written by Android and read by Wireshark. All fields
+// are big endian, records carry H4 framed HCI packets.
+pub const FILE_HEADER: [u8; 16] = [
+    b'b', b't', b's', b'n', b'o', b'o', b'p', 0, // identification
+    0, 0, 0, 1, // version
+    0, 0, 0x03, 0xEA, // datalink type 1002, HCI UART (H4)
+];
+pub const RECORD_HEADER_SIZE: usize = 24;
+
+// H4 packet indicators
+pub const H4_COMMAND: u8 = 0x01;
+pub const H4_ACL: u8 = 0x02;
+pub const H4_EVENT: u8 = 0x04;
+
+// Record flags: direction and whether the packet is a command or event
+pub const FLAG_RECEIVED: u32 = 1 << 0;
+pub const FLAG_COMMAND_OR_EVENT: u32 = 1 << 1;
+
+// Timestamps count microseconds since midnight, January 1st of year 0
+pub const UNIX_EPOCH_US: u64 = 0x00DC_DDB3_0F2F_8000;
+
+// Lines of the HCI trace on the serial console: this prefix followed by a
+// record (header and packet) as hex
+pub const TRACE_LINE_PREFIX: &str = "HCI:";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecordHeader {
+    pub original_length: u32,
+    pub included_length: u32,
+    pub flags: u32,
+    pub drops: u32,
+    pub timestamp_us: u64,
+}
+
+impl RecordHeader {
+    // Header of a packet that is stored completely
+    pub fn new(length: u32, flags: u32, timestamp_us: u64) -> Self {
+        Self {
+            original_length: length,
+            included_length: length,
+            flags,
+            drops: 0,
+            timestamp_us,
+        }
+    }
+
+    pub fn encode(&self) -> [u8; RECORD_HEADER_SIZE] {
+        let mut header = [0u8; RECORD_HEADER_SIZE];
+        header[0..4].copy_from_slice(&self.original_length.to_be_bytes());
+        header[4..8].copy_from_slice(&self.included_length.to_be_bytes());
+        header[8..12].copy_from_slice(&self.flags.to_be_bytes());
+        header[12..16].copy_from_slice(&self.drops.to_be_bytes());
+        header[16..24].copy_from_slice(&self.timestamp_us.to_be_bytes());
+        header
+    }
+
+    pub fn decode(header: &[u8; RECORD_HEADER_SIZE]) -> Self {
+        let word = |offset: usize| {
+            u32::from_be_bytes([
+                header[offset],
+                header[offset + 1],
+                header[offset + 2],
+                header[offset + 3],
+            ])
+        };
+        Self {
+            original_length: word(0),
+            included_length: word(4),
+            flags: word(8),
+            drops: word(12),
+            timestamp_us: (word(16) as u64) << 32 | word(20) as u64,
+        }
+    }
+}
+
+// Flags of a packet, given its H4 indicator and whether the host received it
+pub fn flags(indicator: u8, received: bool) -> u32 {
+    let mut flags = if received { FLAG_RECEIVED } else { 0 };
+    if indicator == H4_COMMAND || indicator == H4_EVENT {
+        flags |= FLAG_COMMAND_OR_EVENT;
+    }
+    flags
+}
@@ -0,0 +1,23 @@
+use embassy_time::Instant;
+
+// Source of the current time, so time-dependent logic can be tested with a clock
+// the test controls
+pub trait Clock {
+    fn now(&self) -> Instant;
+}
+
+// The embassy time driver
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for &C {
+    fn now(&self) -> Instant {
+        (**self).now()
+    }
+}
@@ -0,0 +1,269 @@
+use ed25519_compact::{PublicKey, Signature};
+use embedded_storage::nor_flash::{NorFlash, NorFlashError, NorFlashErrorKind};
+use heapless::Vec;
+use sha2::{Digest, Sha256};
+
+use crate::flash_ring::Partition;
+
+// Control point opcodes
+pub const DFU_START: u8 = 0x01;
+pub const DFU_SIGNATURE: u8 = 0x02;
+pub const DFU_FINISH: u8 = 0x03;
+pub const DFU_ABORT: u8 = 0x04;
+// Largest control point write: opcode and signature
+pub const COMMAND_SIZE: usize = 65;
+// Every data write starts with the offset of its image data in the image: u32 LE
+pub const OFFSET_SIZE: usize = 4;
+// First byte of an esp-idf application image
+pub const IMAGE_MAGIC: u8 = 0xE9;
+// Erase size of the ESP32-S3 flash, the image is programmed a sector at a time
+pub const SECTOR_SIZE: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    // [DFU_START][image size: u32 LE][SHA-256 of the image: 32]
+    Start { size: u32, digest: [u8; 32] },
+    // [DFU_SIGNATURE][Ed25519 signature of the SHA-256: 64]
+    Signature([u8; 64]),
+    // [DFU_FINISH]
+    Finish,
+    // [DFU_ABORT]
+    Abort,
+}
+
+impl Command {
+    pub fn decode(command: &[u8]) -> Option<Self> {
+        match command.split_first()? {
+            (&DFU_START, arguments) if arguments.len() == 36 => {
+                let (size, digest) = arguments.split_at(4);
+                Some(Command::Start {
+                    size: u32::from_le_bytes(size.try_into().ok()?),
+                    digest: digest.try_into().ok()?,
+                })
+            }
+            (&DFU_SIGNATURE, arguments) => Some(Command::Signature(arguments.try_into().ok()?)),
+            (&DFU_FINISH, []) => Some(Command::Finish),
+            (&DFU_ABORT, []) => Some(Command::Abort),
+            _ => None,
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8, COMMAND_SIZE> {
+        let mut command = [0u8; COMMAND_SIZE];
+        let len = match self {
+            Command::Start { size, digest } => {
+                command[0] = DFU_START;
+                command[1..5].copy_from_slice(&size.to_le_bytes());
+                command[5..37].copy_from_slice(digest);
+                37
+            }
+            Command::Signature(signature) => {
+                command[0] = DFU_SIGNATURE;
+                command[1..].copy_from_slice(signature);
+                COMMAND_SIZE
+            }
+            Command::Finish => {
+                command[0] = DFU_FINISH;
+                1
+            }
+            Command::Abort => {
+                command[0] = DFU_ABORT;
+                1
+            }
+        };
+        Vec::from_slice(&command[..len]).unwrap_or_default()
+    }
+}
+
+// Splits a data write into the offset and the image data
+pub fn decode_chunk(chunk: &[u8]) -> Option<(u32, &[u8])> {
+    let (offset, data) = chunk.split_at_checked(OFFSET_SIZE)?;
+    Some((u32::from_le_bytes(offset.try_into().ok()?), data))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpdateError {
+    NotStarted,
+    NoPublicKey,
+    ImageTooLarge(u32, u32),
+    UnexpectedOffset(u32, u32),
+    InvalidImage,
+    IncompleteImage(u32, u32),
+    DigestMismatch,
+    MissingSignature,
+    InvalidSignature,
+    Flash(NorFlashErrorKind),
+}
+
+impl core::fmt::Display for UpdateError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            UpdateError::NotStarted => write!(f, "no update in progress"),
+            UpdateError::NoPublicKey => write!(f, "no DFU public key configured"),
+            UpdateError::ImageTooLarge(size, limit) => {
+                write!(f, "image size {} exceeds {} bytes", size, limit)
+            }
+            UpdateError::UnexpectedOffset(offset, expected) => {
+                write!(f, "unexpected offset {}, expected {}", offset, expected)
+            }
+            UpdateError::InvalidImage => write!(f, "not an application image"),
+            UpdateError::IncompleteImage(received, size) => write!(
+                f,
+                "image incomplete, {} of {} bytes received",
+                received, size
+            ),
+            UpdateError::DigestMismatch => write!(f, "image digest does not match"),
+            UpdateError::MissingSignature => write!(f, "image signature missing"),
+            UpdateError::InvalidSignature => write!(f, "image signature invalid"),
+            UpdateError::Flash(kind) => write!(f, "could not write image to flash: {:?}", kind),
+        }
+    }
+}
+
+fn flash_error(error: impl NorFlashError) -> UpdateError {
+    UpdateError::Flash(error.kind())
+}
+
+// Image being received into a partition
+struct Transfer {
+    partition: Partition,
+    size: u32,
+    received: u32,
+    digest: [u8; 32],
+    signature: Option<[u8; 64]>,
+    hasher: Sha256,
+    sector: [u8; SECTOR_SIZE],
+}
+
+impl Transfer {
+    // Erases and programs the sector that holds the last received byte
+    fn write_sector<F: NorFlash>(&mut self, flash: &mut F) -> Result<(), UpdateError> {
+        let start =
+            self.partition.offset + (self.received - 1) / SECTOR_SIZE as u32 * SECTOR_SIZE as u32;
+        flash
+            .erase(start, start + SECTOR_SIZE as u32)
+            .map_err(flash_error)?;
+        flash.write(start, &self.sector).map_err(flash_error)?;
+        self.sector = [0xFF; SECTOR_SIZE];
+        Ok(())
+    }
+}
+
+// Firmware update in the order its control point and data writes arrived. The
+// image is checked while it is written: chunks must follow each other, it must
+// fit the announced size and start like an application image. Finishing
+// checks its SHA-256 and the Ed25519 signature of it.
+pub struct Update {
+    public_key: [u8; 32],
+    transfer: Option<Transfer>,
+}
+
+impl Update {
+    // An all zero key refuses every update
+    pub const fn new(public_key: [u8; 32]) -> Self {
+        Self {
+            public_key,
+            transfer: None,
+        }
+    }
+
+    pub fn is_started(&self) -> bool {
+        self.transfer.is_some()
+    }
+
+    pub fn received(&self) -> u32 {
+        self.transfer
+            .as_ref()
+            .map_or(0, |transfer| transfer.received)
+    }
+
+    // Begins an update into `partition`, replacing any update in progress
+    pub fn start(
+        &mut self,
+        partition: Partition,
+        size: u32,
+        digest: [u8; 32],
+    ) -> Result<(), UpdateError> {
+        self.transfer = None;
+        if self.public_key == [0u8; 32] {
+            return Err(UpdateError::NoPublicKey);
+        }
+        if size == 0 || size > partition.size {
+            return Err(UpdateError::ImageTooLarge(size, partition.size));
+        }
+
+        self.transfer = Some(Transfer {
+            partition,
+            size,
+            received: 0,
+            digest,
+            signature: None,
+            hasher: Sha256::new(),
+            sector: [0xFF; SECTOR_SIZE],
+        });
+        Ok(())
+    }
+
+    pub fn set_signature(&mut self, signature: [u8; 64]) -> Result<(), UpdateError> {
+        let transfer = self.transfer.as_mut().ok_or(UpdateError::NotStarted)?;
+        transfer.signature = Some(signature);
+        Ok(())
+    }
+
+    pub fn append<F: NorFlash>(
+        &mut self,
+        flash: &mut F,
+        offset: u32,
+        data: &[u8],
+    ) -> Result<(), UpdateError> {
+        let transfer = self.transfer.as_mut().ok_or(UpdateError::NotStarted)?;
+        if offset != transfer.received {
+            return Err(UpdateError::UnexpectedOffset(offset, transfer.received));
+        }
+        let end = transfer.received + data.len() as u32;
+        if end > transfer.size {
+            return Err(UpdateError::ImageTooLarge(end, transfer.size));
+        }
+        if offset == 0 && data.first() != Some(&IMAGE_MAGIC) {
+            return Err(UpdateError::InvalidImage);
+        }
+
+        transfer.hasher.update(data);
+        for &byte in data {
+            transfer.sector[transfer.received as usize % SECTOR_SIZE] = byte;
+            transfer.received += 1;
+            if transfer.received as usize % SECTOR_SIZE == 0 {
+                transfer.write_sector(flash)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Writes the rest of the image and checks it. The update is over afterwards,
+    // whether the image was accepted or not.
+    pub fn finish<F: NorFlash>(&mut self, flash: &mut F) -> Result<(), UpdateError> {
+        let mut transfer = self.transfer.take().ok_or(UpdateError::NotStarted)?;
+        if transfer.received != transfer.size {
+            return Err(UpdateError::IncompleteImage(
+                transfer.received,
+                transfer.size,
+            ));
+        }
+        if transfer.received as usize % SECTOR_SIZE != 0 {
+            transfer.write_sector(flash)?;
+        }
+
+        let digest: [u8; 32] = transfer.hasher.finalize().into();
+        if digest != transfer.digest {
+            return Err(UpdateError::DigestMismatch);
+        }
+        let signature = transfer.signature.ok_or(UpdateError::MissingSignature)?;
+        PublicKey::new(self.public_key)
+            .verify(digest, &Signature::new(signature))
+            .map_err(|_| UpdateError::InvalidSignature)
+    }
+
+    pub fn abort(&mut self) {
+        self.transfer = None;
+    }
+}
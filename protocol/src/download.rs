@@ -0,0 +1,80 @@
+// Bulk download protocol. The host opens an L2CAP channel on the download PSM
+// and sends a one byte request. The answer is a stream of frames
+// [type][payload length: u16 LE][payload], packed into SDUs and terminated by an
+// end frame.
+pub const REQUEST_CAPTURE: u8 = 0x01;
+pub const REQUEST_EVENT_LOG: u8 = 0x02;
+pub const REQUEST_RIDES: u8 = 0x03;
+
+// [uptime ms: u32 LE][notification data]
+pub const FRAME_CAPTURE: u8 = 0x01;
+// Event log record as read from the event log characteristic
+pub const FRAME_EVENT: u8 = 0x02;
+// Stored ride, see ride::RideStats
+pub const FRAME_RIDE: u8 = 0x03;
+// [unknown request]
+pub const FRAME_ERROR: u8 = 0x7E;
+// [number of frames sent: u32 LE]
+pub const FRAME_END: u8 = 0x7F;
+
+pub const FRAME_HEADER_SIZE: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Frame<'a> {
+    pub kind: u8,
+    pub payload: &'a [u8],
+}
+
+// Splits a received stream into frames, stopping at the first incomplete one
+pub struct Frames<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Frames<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    // Bytes after the last complete frame
+    pub fn remainder(&self) -> &'a [u8] {
+        self.data
+    }
+}
+
+impl<'a> Iterator for Frames<'a> {
+    type Item = Frame<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (&kind, rest) = self.data.split_first()?;
+        let length = u16::from_le_bytes([*rest.first()?, *rest.get(1)?]) as usize;
+        let payload = rest.get(2..2 + length)?;
+        self.data = &rest[2 + length..];
+        Some(Frame { kind, payload })
+    }
+}
+
+// Payload of a capture frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CaptureRecord<'a> {
+    pub uptime_ms: u32,
+    pub data: &'a [u8],
+}
+
+impl<'a> CaptureRecord<'a> {
+    pub fn decode(payload: &'a [u8]) -> Option<Self> {
+        let (uptime_ms, data) = payload.split_at_checked(4)?;
+        Some(Self {
+            uptime_ms: u32::from_le_bytes([uptime_ms[0], uptime_ms[1], uptime_ms[2], uptime_ms[3]]),
+            data,
+        })
+    }
+
+    // Writes the payload into `out` and returns its length, None if it does not fit
+    pub fn encode(&self, out: &mut [u8]) -> Option<usize> {
+        let length = 4 + self.data.len();
+        let out = out.get_mut(..length)?;
+        out[..4].copy_from_slice(&self.uptime_ms.to_le_bytes());
+        out[4..].copy_from_slice(self.data);
+        Some(length)
+    }
+}
@@ -0,0 +1,54 @@
+use embassy_time::Duration;
+
+use crate::radar::{RadarFrame, RadarTarget, ThreatLevel};
+
+// Time to contact at or below which a target is shown at least at a threat
+// level, whatever the radar rates it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EscalationRules {
+    pub medium: Duration,
+    pub high: Duration,
+}
+
+// Time until the target reaches the rider at its current closing speed, None
+// if it is not closing in
+pub fn time_to_contact(target: &RadarTarget) -> Option<Duration> {
+    // range (m) / (speed (km/h) / 3.6) in ms
+    let speed = target.speed as u64;
+    (speed > 0).then(|| Duration::from_millis(target.range as u64 * 3600 / speed))
+}
+
+impl EscalationRules {
+    // Escalation of the proxy, a fast overtake is flagged before the radar rates it
+    pub const DEFAULT: Self = Self {
+        medium: Duration::from_secs(6),
+        high: Duration::from_secs(3),
+    };
+
+    pub fn threat(&self, time_to_contact: Duration) -> ThreatLevel {
+        if time_to_contact <= self.high {
+            ThreatLevel::High
+        } else if time_to_contact <= self.medium {
+            ThreatLevel::Medium
+        } else {
+            ThreatLevel::None
+        }
+    }
+
+    // Raises the threat level of the targets that are about to reach the
+    // rider, returns how many were raised
+    pub fn apply(&self, frame: &mut RadarFrame) -> usize {
+        let mut escalated = 0;
+        for target in frame.targets.iter_mut() {
+            let Some(time_to_contact) = time_to_contact(target) else {
+                continue;
+            };
+            let threat = self.threat(time_to_contact);
+            if threat > target.threat {
+                target.threat = threat;
+                escalated += 1;
+            }
+        }
+        escalated
+    }
+}
@@ -0,0 +1,119 @@
+use heapless::Vec;
+
+use crate::radar::{RadarTarget, ThreatLevel};
+use crate::tracker::{TrackEvent, Tracked, MAX_TRACKS};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FilterRules {
+    // Targets closing slower than this (km/h) are not shown
+    pub min_speed: u8,
+    // Targets further away than this (m) are not shown
+    pub max_range: u8,
+    // A vehicle that is shown stays until it is this much slower (km/h) or
+    // further away (m) than the limits, so it does not flicker at the edge
+    pub speed_hysteresis: u8,
+    pub range_hysteresis: u8,
+    // Threat level shown for each level the radar reports, indexed by ThreatLevel
+    pub threat_map: [ThreatLevel; 4],
+}
+
+impl FilterRules {
+    // Shows every target as the radar reports it
+    pub const NONE: Self = Self {
+        min_speed: 0,
+        max_range: u8::MAX,
+        speed_hysteresis: 0,
+        range_hysteresis: 0,
+        threat_map: [
+            ThreatLevel::None,
+            ThreatLevel::Low,
+            ThreatLevel::Medium,
+            ThreatLevel::High,
+        ],
+    };
+
+    fn allows(&self, target: &RadarTarget, shown: bool) -> bool {
+        let (min_speed, max_range) = match shown {
+            true => (
+                self.min_speed.saturating_sub(self.speed_hysteresis),
+                self.max_range.saturating_add(self.range_hysteresis),
+            ),
+            false => (self.min_speed, self.max_range),
+        };
+        target.speed >= min_speed && target.range <= max_range
+    }
+}
+
+// Drops the targets of tracked frames that the rules do not allow and remaps
+// their threat levels. The vehicle events follow what is shown: a vehicle is
+// new when it is first shown, and passes or gets lost only if it was shown.
+pub struct TargetFilter {
+    rules: FilterRules,
+    // Vehicles shown, including the ones the tracker keeps while they are missing
+    shown: Vec<u8, MAX_TRACKS>,
+}
+
+impl TargetFilter {
+    pub fn new(rules: FilterRules) -> Self {
+        Self {
+            rules,
+            shown: Vec::new(),
+        }
+    }
+
+    pub fn set_rules(&mut self, rules: FilterRules) {
+        self.rules = rules;
+    }
+
+    pub fn apply(&mut self, tracked: Tracked) -> Tracked {
+        let Tracked { mut frame, events } = tracked;
+        if !frame.online {
+            self.reset();
+            return Tracked { frame, events };
+        }
+
+        let mut filtered = Vec::new();
+        for event in events {
+            match event {
+                TrackEvent::VehiclePassed(id) | TrackEvent::VehicleLost(id) => {
+                    if let Some(index) = self.shown.iter().position(|shown| *shown == id) {
+                        self.shown.swap_remove(index);
+                        let _ = filtered.push(event);
+                    }
+                }
+                // New vehicles are announced once they are shown
+                TrackEvent::NewVehicle(_) => {}
+            }
+        }
+
+        let rules = self.rules;
+        let shown = &mut self.shown;
+        frame.targets.retain_mut(|target| {
+            let index = target
+                .id
+                .and_then(|id| shown.iter().position(|shown| *shown == id));
+            if !rules.allows(target, index.is_some()) {
+                if let Some(index) = index {
+                    shown.swap_remove(index);
+                }
+                return false;
+            }
+
+            target.threat = rules.threat_map[target.threat as usize];
+            if let (Some(id), None) = (target.id, index) {
+                let _ = shown.push(id);
+                let _ = filtered.push(TrackEvent::NewVehicle(id));
+            }
+            true
+        });
+
+        Tracked {
+            frame,
+            events: filtered,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.shown.clear();
+    }
+}
@@ -0,0 +1,119 @@
+use embedded_storage::nor_flash::NorFlash;
+
+// Sequence number of an erased record slot
+const ERASED_SEQUENCE: u32 = u32::MAX;
+pub const SEQUENCE_SIZE: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Partition {
+    pub offset: u32,
+    pub size: u32,
+}
+
+// Append-only ring of fixed size records inside a flash partition. Every record
+// starts with a sequence number, the oldest sector is erased once the ring wraps.
+#[derive(Debug, Clone)]
+pub struct FlashRing<const RECORD_SIZE: usize> {
+    partition: Partition,
+    next_slot: u32,
+    next_sequence: u32,
+    len: u32,
+}
+
+impl<const RECORD_SIZE: usize> FlashRing<RECORD_SIZE> {
+    pub const PAYLOAD_SIZE: usize = RECORD_SIZE - SEQUENCE_SIZE;
+
+    pub fn open<F: NorFlash>(flash: &mut F, partition: Partition) -> Result<Self, F::Error> {
+        let mut ring = Self {
+            partition,
+            next_slot: 0,
+            next_sequence: 0,
+            len: 0,
+        };
+
+        let mut newest: Option<(u32, u32)> = None;
+        for slot in 0..ring.capacity() {
+            let sequence = ring.read_sequence(flash, slot)?;
+            if sequence == ERASED_SEQUENCE {
+                continue;
+            }
+            ring.len += 1;
+            if newest.is_none_or(|(_, newest_sequence)| sequence > newest_sequence) {
+                newest = Some((slot, sequence));
+            }
+        }
+
+        if let Some((slot, sequence)) = newest {
+            ring.next_slot = (slot + 1) % ring.capacity();
+            ring.next_sequence = sequence.wrapping_add(1);
+        }
+        Ok(ring)
+    }
+
+    pub fn capacity(&self) -> u32 {
+        self.partition.size / RECORD_SIZE as u32
+    }
+
+    pub fn len(&self) -> u32 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn slot_offset(&self, slot: u32) -> u32 {
+        self.partition.offset + slot * RECORD_SIZE as u32
+    }
+
+    fn read_sequence<F: NorFlash>(&self, flash: &mut F, slot: u32) -> Result<u32, F::Error> {
+        let mut sequence = [0u8; SEQUENCE_SIZE];
+        flash.read(self.slot_offset(slot), &mut sequence)?;
+        Ok(u32::from_le_bytes(sequence))
+    }
+
+    pub fn append<F: NorFlash>(&mut self, flash: &mut F, payload: &[u8]) -> Result<(), F::Error> {
+        let records_per_sector = (F::ERASE_SIZE / RECORD_SIZE) as u32;
+        if self.next_slot % records_per_sector == 0 {
+            let sector = self.slot_offset(self.next_slot);
+            flash.erase(sector, sector + F::ERASE_SIZE as u32)?;
+            self.len = self.len.min(self.capacity() - records_per_sector);
+        }
+
+        let mut record = [0xFFu8; RECORD_SIZE];
+        record[..SEQUENCE_SIZE].copy_from_slice(&self.next_sequence.to_le_bytes());
+        let length = payload.len().min(Self::PAYLOAD_SIZE);
+        record[SEQUENCE_SIZE..SEQUENCE_SIZE + length].copy_from_slice(&payload[..length]);
+        flash.write(self.slot_offset(self.next_slot), &record)?;
+
+        self.next_slot = (self.next_slot + 1) % self.capacity();
+        self.next_sequence = self.next_sequence.wrapping_add(1);
+        self.len = (self.len + 1).min(self.capacity());
+        Ok(())
+    }
+
+    // Reads the payload of a record, `age` 0 being the newest. Returns false if there is no such record.
+    pub fn read<F: NorFlash>(
+        &self,
+        flash: &mut F,
+        age: u32,
+        payload: &mut [u8],
+    ) -> Result<bool, F::Error> {
+        if age >= self.len {
+            return Ok(false);
+        }
+
+        let slot = (self.next_slot + self.capacity() - 1 - age) % self.capacity();
+        let mut record = [0u8; RECORD_SIZE];
+        flash.read(self.slot_offset(slot), &mut record)?;
+
+        let sequence = u32::from_le_bytes([record[0], record[1], record[2], record[3]]);
+        if sequence != self.next_sequence.wrapping_sub(1 + age) {
+            return Ok(false);
+        }
+
+        let length = payload.len().min(Self::PAYLOAD_SIZE);
+        payload[..length].copy_from_slice(&record[SEQUENCE_SIZE..SEQUENCE_SIZE + length]);
+        Ok(true)
+    }
+}
@@ -0,0 +1,49 @@
+use core::convert::Infallible;
+
+use heapless::Vec;
+use rgb::RGB;
+
+pub trait Indicator {
+    type Error: core::fmt::Debug;
+
+    fn set(&mut self, color: RGB<u8>, level: u8) -> Result<(), Self::Error>;
+
+    fn off(&mut self) -> Result<(), Self::Error> {
+        self.set(RGB::new(0, 0, 0), 0)
+    }
+}
+
+// Records every write, for exercising patterns without hardware
+pub struct MockIndicator<const N: usize> {
+    pub history: Vec<(RGB<u8>, u8), N>,
+}
+
+impl<const N: usize> MockIndicator<N> {
+    pub fn new() -> Self {
+        Self {
+            history: Vec::new(),
+        }
+    }
+
+    pub fn last(&self) -> Option<&(RGB<u8>, u8)> {
+        self.history.last()
+    }
+}
+
+impl<const N: usize> Default for MockIndicator<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> Indicator for MockIndicator<N> {
+    type Error = Infallible;
+
+    fn set(&mut self, color: RGB<u8>, level: u8) -> Result<(), Self::Error> {
+        if self.history.is_full() && !self.history.is_empty() {
+            self.history.remove(0);
+        }
+        let _ = self.history.push((color, level));
+        Ok(())
+    }
+}
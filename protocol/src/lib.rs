@@ -0,0 +1,20 @@
+#![no_std]
+pub mod advertising;
+pub mod alert;
+pub mod animation;
+pub mod bryton;
+pub mod btsnoop;
+pub mod clock;
+pub mod dfu;
+pub mod download;
+pub mod escalation;
+pub mod filter;
+pub mod flash_ring;
+pub mod indicator;
+pub mod magene;
+pub mod page_buffer;
+pub mod pipeline;
+pub mod radar;
+pub mod ride;
+pub mod strip;
+pub mod tracker;
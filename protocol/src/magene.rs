@@ -0,0 +1,64 @@
+use crate::radar::{MAX_PAGES, PAGE_SIZE};
+
+// Radar notification of the Magene L508: a 3 byte header that is not decoded yet,
+// followed by one page in the layout described in `radar`.
+pub const NOTIFICATION_SIZE: usize = 11;
+pub const HEADER_SIZE: usize = NOTIFICATION_SIZE - PAGE_SIZE;
+pub const PAGE_1: u8 = 0x30;
+pub const PAGE_2: u8 = 0x31;
+// Type of the last page of a cycle that is decoded
+pub const PAGE_LAST: u8 = PAGE_1 + MAX_PAGES as u8 - 1;
+
+// One page of a radar cycle, its type is known to be PAGE_1..=PAGE_LAST
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Page([u8; PAGE_SIZE]);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeError {
+    WrongLength(usize),
+    UnknownPageType(u8),
+}
+
+impl core::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            DecodeError::WrongLength(length) => write!(
+                f,
+                "wrong length (got {}, expected {})",
+                length, NOTIFICATION_SIZE
+            ),
+            DecodeError::UnknownPageType(kind) => write!(f, "unknown page type {:#04x}", kind),
+        }
+    }
+}
+
+impl Page {
+    pub fn new(data: [u8; PAGE_SIZE]) -> Result<Self, DecodeError> {
+        match data[0] {
+            PAGE_1..=PAGE_LAST => Ok(Self(data)),
+            kind => Err(DecodeError::UnknownPageType(kind)),
+        }
+    }
+
+    // Position in the cycle, 0 for PAGE_1
+    pub fn index(&self) -> usize {
+        (self.0[0] - PAGE_1) as usize
+    }
+
+    pub fn cycle(&self) -> u8 {
+        self.0[1]
+    }
+
+    pub fn data(&self) -> &[u8; PAGE_SIZE] {
+        &self.0
+    }
+}
+
+pub fn decode_notification(data: &[u8]) -> Result<Page, DecodeError> {
+    if data.len() != NOTIFICATION_SIZE {
+        return Err(DecodeError::WrongLength(data.len()));
+    }
+    let mut page = [0u8; PAGE_SIZE];
+    page.copy_from_slice(&data[HEADER_SIZE..]);
+    Page::new(page)
+}
@@ -0,0 +1,240 @@
+use embassy_time::{Duration, Instant, Timer};
+
+use crate::clock::{Clock, SystemClock};
+use crate::magene::Page;
+use crate::radar::{RadarFrame, MAX_PAGES, PAGE_SIZE};
+
+// Pages every cycle of the L508 has, further pages only come with more targets
+const BASE_PAGES: usize = 2;
+
+// Result of a page or of the timer, to be forwarded to the client
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Update {
+    Frame(RadarFrame),
+    // No page arrived within the data timeout
+    Expired,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct AssemblyCounters {
+    // Frames built from the base pages of one radar cycle
+    pub complete: u32,
+    // Frames that went out while a base page was missing
+    pub partial: u32,
+    // Cycles cut short by a page of another cycle or a repeated page type
+    pub mismatched: u32,
+    // Pages that arrived after a later page of their cycle
+    pub out_of_order: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Received {
+    data: [u8; PAGE_SIZE],
+    timestamp: Instant,
+}
+
+// Pages of the radar cycle being assembled
+#[derive(Debug, Clone)]
+struct Cycle {
+    number: u8,
+    pages: [Option<[u8; PAGE_SIZE]>; MAX_PAGES],
+    started: Instant,
+    forwarded: bool,
+}
+
+impl Cycle {
+    fn new(page: &Page, started: Instant) -> Self {
+        let mut pages = [None; MAX_PAGES];
+        pages[page.index()] = Some(*page.data());
+        Self {
+            number: page.cycle(),
+            pages,
+            started,
+            forwarded: false,
+        }
+    }
+
+    fn accepts(&self, page: &Page) -> bool {
+        page.cycle() == self.number && self.pages[page.index()].is_none()
+    }
+
+    fn has_base_pages(&self) -> bool {
+        self.pages[..BASE_PAGES].iter().all(Option::is_some)
+    }
+
+    fn frame(&self) -> RadarFrame {
+        RadarFrame::from_pages(self.pages.iter().flatten())
+    }
+}
+
+// How long a page waits for the other page of its radar cycle before it is
+// forwarded alone. Zero forwards every page right away.
+pub const PAGE_HOLD: Duration = Duration::from_millis(50);
+// Age after which the latest page of a type no longer stands in for a missing one
+pub const DATA_PAGE_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Assembles radar frames from the pages of the Magene. The pages of a radar
+// cycle (byte 1) make one frame, which goes out once the base pages are in and
+// again with every further page of the cycle. If a base page is missing, the
+// cycle waits up to `hold`, then goes out with the latest page of that type in
+// its place, or without it if that is older than `data_timeout`.
+pub struct PageBuffer<C = SystemClock> {
+    latest: [Option<Received>; MAX_PAGES],
+    cycle: Option<Cycle>,
+    hold: Duration,
+    data_timeout: Duration,
+    counters: AssemblyCounters,
+    clock: C,
+}
+
+impl PageBuffer {
+    pub fn new(hold: Duration, data_timeout: Duration) -> Self {
+        Self::with_clock(hold, data_timeout, SystemClock)
+    }
+}
+
+// Frame of an incomplete cycle, missing base pages are taken from `latest`
+fn fill(
+    cycle: &Cycle,
+    latest: &[Option<Received>; MAX_PAGES],
+    data_timeout: Duration,
+    now: Instant,
+) -> RadarFrame {
+    let pages = cycle
+        .pages
+        .iter()
+        .zip(latest)
+        .enumerate()
+        .filter_map(|(index, (page, latest))| match (page, latest) {
+            (Some(page), _) => Some(page),
+            (None, Some(latest)) if index < BASE_PAGES && now < latest.timestamp + data_timeout => {
+                Some(&latest.data)
+            }
+            _ => None,
+        });
+    let mut frame = RadarFrame::from_pages(pages);
+    if frame.online {
+        frame.cycle = cycle.number;
+    }
+    frame
+}
+
+impl<C: Clock> PageBuffer<C> {
+    pub fn with_clock(hold: Duration, data_timeout: Duration, clock: C) -> Self {
+        Self {
+            latest: [None; MAX_PAGES],
+            cycle: None,
+            hold,
+            data_timeout,
+            counters: AssemblyCounters::default(),
+            clock,
+        }
+    }
+
+    pub fn counters(&self) -> AssemblyCounters {
+        self.counters
+    }
+
+    // Adds a page and returns the frame to forward, if there is one
+    pub fn push(&mut self, page: Page) -> Option<RadarFrame> {
+        let now = self.clock.now();
+        let index = page.index();
+
+        if let Some(cycle) = self.cycle.as_mut().filter(|cycle| cycle.accepts(&page)) {
+            if cycle.pages[index + 1..].iter().any(Option::is_some) {
+                self.counters.out_of_order += 1;
+            }
+            cycle.pages[index] = Some(*page.data());
+            let frame = match (cycle.forwarded, cycle.has_base_pages()) {
+                (true, _) => Some(cycle.frame()),
+                (false, true) => {
+                    cycle.forwarded = true;
+                    self.counters.complete += 1;
+                    Some(cycle.frame())
+                }
+                (false, false) => None,
+            };
+            self.store(&page, now);
+            return frame;
+        }
+
+        // The page starts a new cycle. The previous one is not going to be
+        // completed anymore and goes out as it is, unless it already did.
+        let mut frame = None;
+        if let Some(cycle) = self.cycle.take().filter(|cycle| !cycle.forwarded) {
+            self.counters.mismatched += 1;
+            self.counters.partial += 1;
+            frame = Some(fill(&cycle, &self.latest, self.data_timeout, now));
+        }
+
+        let mut cycle = Cycle::new(&page, now);
+        self.store(&page, now);
+        if self.hold == Duration::from_ticks(0) {
+            cycle.forwarded = true;
+            self.counters.partial += 1;
+            frame = Some(fill(&cycle, &self.latest, self.data_timeout, now));
+        }
+        self.cycle = Some(cycle);
+        frame
+    }
+
+    // Called when the timer from get_timer() fires
+    pub fn poll(&mut self) -> Option<Update> {
+        let now = self.clock.now();
+
+        if let Some(cycle) = self.cycle.as_mut() {
+            if !cycle.forwarded && now >= cycle.started + self.hold {
+                cycle.forwarded = true;
+                self.counters.partial += 1;
+                let frame = fill(cycle, &self.latest, self.data_timeout, now);
+                return Some(Update::Frame(frame));
+            }
+        }
+
+        let had_data = self.latest.iter().any(Option::is_some);
+        for latest in self.latest.iter_mut() {
+            if latest.is_some_and(|page| now >= page.timestamp + self.data_timeout) {
+                *latest = None;
+            }
+        }
+        if had_data && self.latest.iter().all(Option::is_none) {
+            self.cycle = None;
+            return Some(Update::Expired);
+        }
+        None
+    }
+
+    // When a waiting cycle has to go out or a stored page expires, None while
+    // the buffer is empty
+    pub fn next_expiry(&self) -> Option<Instant> {
+        let hold = self
+            .cycle
+            .as_ref()
+            .filter(|cycle| !cycle.forwarded)
+            .map(|cycle| cycle.started + self.hold);
+        let expiries = self
+            .latest
+            .iter()
+            .flatten()
+            .map(|page| page.timestamp + self.data_timeout);
+        expiries.chain(hold).min()
+    }
+
+    // Fires at the next expiry. An empty buffer has nothing to expire, so its
+    // timer never fires instead of firing right away over and over.
+    pub fn get_timer(&self) -> Timer {
+        Timer::at(self.next_expiry().unwrap_or(Instant::MAX))
+    }
+
+    pub fn cleanup(&mut self) {
+        self.latest = [None; MAX_PAGES];
+        self.cycle = None;
+    }
+
+    fn store(&mut self, page: &Page, timestamp: Instant) {
+        self.latest[page.index()] = Some(Received {
+            data: *page.data(),
+            timestamp,
+        });
+    }
+}
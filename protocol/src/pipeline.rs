@@ -0,0 +1,39 @@
+use crate::escalation::EscalationRules;
+use crate::filter::{FilterRules, TargetFilter};
+use crate::radar::RadarFrame;
+use crate::tracker::{Tracked, Tracker, TrackerConfig};
+
+// Everything an assembled radar frame goes through before it is shown: the
+// tracker, the filter rules of the selected profile and the threat escalation.
+// Escalation comes last so no profile maps it down.
+pub struct Pipeline {
+    tracker: Tracker,
+    filter: TargetFilter,
+    escalation: EscalationRules,
+}
+
+impl Pipeline {
+    pub fn new(tracker: TrackerConfig, rules: FilterRules, escalation: EscalationRules) -> Self {
+        Self {
+            tracker: Tracker::new(tracker),
+            filter: TargetFilter::new(rules),
+            escalation,
+        }
+    }
+
+    pub fn set_rules(&mut self, rules: FilterRules) {
+        self.filter.set_rules(rules);
+    }
+
+    pub fn process(&mut self, frame: &RadarFrame) -> Tracked {
+        let mut tracked = self.filter.apply(self.tracker.update(frame));
+        self.escalation.apply(&mut tracked.frame);
+        tracked
+    }
+
+    // Forgets every vehicle, e.g. when the radar is lost
+    pub fn reset(&mut self) {
+        self.tracker.reset();
+        self.filter.reset();
+    }
+}
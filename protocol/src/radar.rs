@@ -0,0 +1,168 @@
+use heapless::Vec;
+
+// Radar page layout (8 bytes, the pages of a cycle are numbered from 0x30 up):
+//   byte 0     page type
+//   byte 1     cycle counter, 0xFF marks the radar as unavailable
+//   bytes 2..8 two target slots of [range (m), closing speed (km/h), threat level]
+// A slot with a range of 0 is empty. The L508 sends two pages per cycle, so
+// at most four targets; further pages carry further targets the same way.
+pub const PAGE_SIZE: usize = 8;
+pub const PAGE_STATUS_OFFLINE: u8 = 0xFF;
+pub const TARGET_SLOT_SIZE: usize = 3;
+pub const TARGETS_PER_PAGE: usize = 2;
+pub const MAX_PAGES: usize = 4;
+pub const MAX_TARGETS: usize = MAX_PAGES * TARGETS_PER_PAGE;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ThreatLevel {
+    None,
+    Low,
+    Medium,
+    High,
+}
+
+impl From<u8> for ThreatLevel {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => ThreatLevel::None,
+            1 => ThreatLevel::Low,
+            2 => ThreatLevel::Medium,
+            _ => ThreatLevel::High,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RadarTarget {
+    // Vehicle the target belongs to, set by the tracker
+    pub id: Option<u8>,
+    pub range: u8,
+    pub speed: u8,
+    pub threat: ThreatLevel,
+}
+
+// A single page, used to look at captures page by page
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RadarPage {
+    pub kind: u8,
+    pub cycle: u8,
+    pub online: bool,
+    pub targets: Vec<RadarTarget, TARGETS_PER_PAGE>,
+}
+
+impl RadarPage {
+    pub fn decode(page: &[u8; PAGE_SIZE]) -> Self {
+        let mut decoded = Self {
+            kind: page[0],
+            cycle: page[1],
+            online: page[1] != PAGE_STATUS_OFFLINE,
+            targets: Vec::new(),
+        };
+        if !decoded.online {
+            return decoded;
+        }
+
+        for slot in page[2..].chunks_exact(TARGET_SLOT_SIZE) {
+            if slot[0] == 0 {
+                continue;
+            }
+            // Capacity matches the slots of a page, so this can not fail
+            let _ = decoded.targets.push(RadarTarget {
+                id: None,
+                range: slot[0],
+                speed: slot[1],
+                threat: ThreatLevel::from(slot[2]),
+            });
+        }
+        decoded
+    }
+}
+
+// All targets of one radar cycle, in the order the radar reported them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RadarFrame {
+    pub online: bool,
+    pub cycle: u8,
+    pub targets: Vec<RadarTarget, MAX_TARGETS>,
+    // Pages the frame was assembled from, as the radar sent them
+    pub pages: Vec<[u8; PAGE_SIZE], MAX_PAGES>,
+}
+
+impl RadarFrame {
+    pub fn offline() -> Self {
+        Self {
+            online: false,
+            cycle: PAGE_STATUS_OFFLINE,
+            targets: Vec::new(),
+            pages: Vec::new(),
+        }
+    }
+
+    // Pages beyond MAX_PAGES are ignored
+    pub fn from_pages<'a>(pages: impl IntoIterator<Item = &'a [u8; PAGE_SIZE]>) -> Self {
+        let mut frame = Self {
+            online: true,
+            cycle: 0,
+            targets: Vec::new(),
+            pages: Vec::new(),
+        };
+
+        for data in pages.into_iter().take(MAX_PAGES) {
+            let page = RadarPage::decode(data);
+            frame.online &= page.online;
+            frame.cycle = page.cycle;
+            // Capacity matches the slots of MAX_PAGES pages, so this can not fail
+            let _ = frame.targets.extend_from_slice(&page.targets);
+            let _ = frame.pages.push(*data);
+        }
+        // A radar that is unavailable on any page of the cycle is unavailable
+        if !frame.online {
+            return Self::offline();
+        }
+
+        frame
+    }
+
+    pub fn decode(data: &[u8]) -> Self {
+        Self::from_pages(
+            data.chunks_exact(PAGE_SIZE)
+                .filter_map(|page| page.try_into().ok()),
+        )
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.targets.is_empty()
+    }
+
+    pub fn closest(&self) -> Option<&RadarTarget> {
+        self.targets.iter().min_by_key(|target| target.range)
+    }
+
+    pub fn fastest(&self) -> Option<&RadarTarget> {
+        self.targets.iter().max_by_key(|target| target.speed)
+    }
+
+    pub fn max_threat(&self) -> ThreatLevel {
+        self.targets
+            .iter()
+            .map(|target| target.threat)
+            .max()
+            .unwrap_or(ThreatLevel::None)
+    }
+
+    // The `capacity` most important targets for an output that can not show
+    // them all: highest threat first, then the closest. They keep the order of
+    // the frame, so an output with room for every target shows the frame as is.
+    pub fn prioritized(&self, capacity: usize) -> Vec<RadarTarget, MAX_TARGETS> {
+        if self.targets.len() <= capacity {
+            return self.targets.clone();
+        }
+        let mut ranked: Vec<(usize, RadarTarget), MAX_TARGETS> =
+            self.targets.iter().copied().enumerate().collect();
+        ranked
+            .sort_unstable_by_key(|(_, target)| (core::cmp::Reverse(target.threat), target.range));
+        ranked.truncate(capacity);
+        ranked.sort_unstable_by_key(|(index, _)| *index);
+        ranked.into_iter().map(|(_, target)| target).collect()
+    }
+}
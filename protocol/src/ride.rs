@@ -0,0 +1,181 @@
+use core::fmt;
+
+use embassy_time::Instant;
+use heapless::Vec;
+
+use crate::radar::ThreatLevel;
+use crate::tracker::{TrackEvent, Tracked, MAX_TRACKS};
+
+// Ride layout: [version][duration ms: u32][traffic ms: u32][vehicles: u16][passed: u16]
+// [high threats: u16][peak speed][closest range], all LE, a closest range of 0
+// means no vehicle was seen
+pub const RIDE_STATS_SIZE: usize = 17;
+const RIDE_STATS_VERSION: u8 = 1;
+
+// Traffic seen while a client was connected
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RideStats {
+    pub duration_ms: u32,
+    // Time with at least one vehicle shown
+    pub traffic_ms: u32,
+    // Vehicles shown, and the ones of them that passed the rider
+    pub vehicles: u16,
+    pub passed: u16,
+    // Times a vehicle became a high threat
+    pub high_threats: u16,
+    // Fastest closing speed (km/h)
+    pub peak_speed: u8,
+    // Closest range (m) of any vehicle, None if there was no traffic
+    pub closest_range: Option<u8>,
+}
+
+impl RideStats {
+    pub fn encode(&self) -> [u8; RIDE_STATS_SIZE] {
+        let mut data = [0u8; RIDE_STATS_SIZE];
+        data[0] = RIDE_STATS_VERSION;
+        data[1..5].copy_from_slice(&self.duration_ms.to_le_bytes());
+        data[5..9].copy_from_slice(&self.traffic_ms.to_le_bytes());
+        data[9..11].copy_from_slice(&self.vehicles.to_le_bytes());
+        data[11..13].copy_from_slice(&self.passed.to_le_bytes());
+        data[13..15].copy_from_slice(&self.high_threats.to_le_bytes());
+        data[15] = self.peak_speed;
+        data[16] = self.closest_range.unwrap_or(0);
+        data
+    }
+
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        let data = data.get(..RIDE_STATS_SIZE)?;
+        if data[0] != RIDE_STATS_VERSION {
+            return None;
+        }
+        let u16_at = |offset: usize| u16::from_le_bytes([data[offset], data[offset + 1]]);
+        let u32_at = |offset: usize| {
+            u32::from_le_bytes([
+                data[offset],
+                data[offset + 1],
+                data[offset + 2],
+                data[offset + 3],
+            ])
+        };
+        Some(Self {
+            duration_ms: u32_at(1),
+            traffic_ms: u32_at(5),
+            vehicles: u16_at(9),
+            passed: u16_at(11),
+            high_threats: u16_at(13),
+            peak_speed: data[15],
+            closest_range: (data[16] > 0).then_some(data[16]),
+        })
+    }
+}
+
+impl fmt::Display for RideStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} min, {} vehicles ({} passed), {} high threats, traffic {} min, peak {} km/h",
+            self.duration_ms / 60_000,
+            self.vehicles,
+            self.passed,
+            self.high_threats,
+            self.traffic_ms / 60_000,
+            self.peak_speed
+        )?;
+        match self.closest_range {
+            Some(range) => write!(f, ", closest {} m", range),
+            None => Ok(()),
+        }
+    }
+}
+
+// Collects the statistics of a ride from the frames and vehicle events the
+// proxy forwards
+pub struct RideRecorder {
+    started: Instant,
+    stats: RideStats,
+    // Since when vehicles are shown, None while the road is clear
+    traffic_since: Option<Instant>,
+    // Vehicles that are at high threat now. Targets without a track can not be
+    // told apart and are not counted.
+    high: Vec<u8, MAX_TRACKS>,
+}
+
+impl RideRecorder {
+    pub fn start(now: Instant) -> Self {
+        Self {
+            started: now,
+            stats: RideStats::default(),
+            traffic_since: None,
+            high: Vec::new(),
+        }
+    }
+
+    pub fn update(&mut self, now: Instant, tracked: &Tracked) {
+        let stats = &mut self.stats;
+        for event in tracked.events.iter() {
+            match event {
+                TrackEvent::NewVehicle(_) => stats.vehicles = stats.vehicles.saturating_add(1),
+                TrackEvent::VehiclePassed(_) => stats.passed = stats.passed.saturating_add(1),
+                TrackEvent::VehicleLost(_) => {}
+            }
+        }
+
+        let frame = &tracked.frame;
+        if !frame.online {
+            self.radar_lost(now);
+            return;
+        }
+
+        let mut high = Vec::new();
+        for target in frame.targets.iter() {
+            stats.peak_speed = stats.peak_speed.max(target.speed);
+            stats.closest_range = Some(
+                stats
+                    .closest_range
+                    .map_or(target.range, |closest| closest.min(target.range)),
+            );
+            if let (Some(id), ThreatLevel::High) = (target.id, target.threat) {
+                if !self.high.contains(&id) {
+                    stats.high_threats = stats.high_threats.saturating_add(1);
+                }
+                let _ = high.push(id);
+            }
+        }
+        self.high = high;
+
+        match frame.is_empty() {
+            true => self.end_traffic(now),
+            false => {
+                self.traffic_since.get_or_insert(now);
+            }
+        }
+    }
+
+    // Nothing is known about the traffic until the radar is back
+    pub fn radar_lost(&mut self, now: Instant) {
+        self.end_traffic(now);
+        self.high.clear();
+    }
+
+    // Statistics of the ride so far
+    pub fn stats(&self, now: Instant) -> RideStats {
+        let mut stats = self.stats;
+        stats.duration_ms = millis(now, self.started);
+        if let Some(since) = self.traffic_since {
+            stats.traffic_ms = stats.traffic_ms.saturating_add(millis(now, since));
+        }
+        stats
+    }
+
+    fn end_traffic(&mut self, now: Instant) {
+        if let Some(since) = self.traffic_since.take() {
+            self.stats.traffic_ms = self.stats.traffic_ms.saturating_add(millis(now, since));
+        }
+    }
+}
+
+fn millis(now: Instant, since: Instant) -> u32 {
+    now.saturating_duration_since(since)
+        .as_millis()
+        .min(u32::MAX as u64) as u32
+}
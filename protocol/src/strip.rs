@@ -0,0 +1,46 @@
+use core::cmp::Reverse;
+
+use crate::radar::{RadarFrame, ThreatLevel};
+
+// What a pixel of the LED strip shows, the firmware picks the colours
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pixel {
+    Off,
+    // Shown on the rider's pixel
+    RadarOffline,
+    RoadClear,
+    Vehicle(ThreatLevel),
+}
+
+// Pixel 0 is the far end of the strip, the last pixel sits closest to the rider.
+// Vehicles travel along the strip towards the rider as they approach.
+pub fn position(range: u8, length: usize, max_range: u8) -> usize {
+    let last = length - 1;
+    let range = range.min(max_range) as usize;
+    last - range * last / max_range as usize
+}
+
+// Draws the `max_targets` most important vehicles of a frame
+pub fn render(frame: &RadarFrame, pixels: &mut [Pixel], max_range: u8, max_targets: usize) {
+    pixels.fill(Pixel::Off);
+    let Some(rider) = pixels.len().checked_sub(1) else {
+        return;
+    };
+
+    if !frame.online {
+        pixels[rider] = Pixel::RadarOffline;
+        return;
+    }
+
+    if frame.is_empty() {
+        pixels[rider] = Pixel::RoadClear;
+        return;
+    }
+
+    // Draw the furthest vehicles first so the closest ones stay visible when they overlap
+    let mut targets = frame.prioritized(max_targets);
+    targets.sort_unstable_by_key(|target| Reverse(target.range));
+    for target in targets.iter() {
+        pixels[position(target.range, pixels.len(), max_range)] = Pixel::Vehicle(target.threat);
+    }
+}
@@ -0,0 +1,262 @@
+use heapless::Vec;
+
+use crate::radar::{RadarFrame, RadarTarget, ThreatLevel, MAX_TARGETS};
+
+// Tracks kept at once: the targets of a frame and the ones missing from it
+pub const MAX_TRACKS: usize = 2 * MAX_TARGETS;
+pub const MAX_EVENTS: usize = MAX_TRACKS + MAX_TARGETS;
+
+// Ranges and speeds of a track are kept in 1/16 m and 1/16 km/h
+const SCALE: i32 = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TrackerConfig {
+    // Largest distance (m) between where a track is expected and a target that
+    // is still taken for the same vehicle
+    pub gate: u8,
+    // Frames a vehicle may be missing before its track ends
+    pub coast_frames: u8,
+    // A track that ends at or below this range (m) passed the rider, one that
+    // ends further away was lost
+    pub passed_range: u8,
+    // Weight of a new measurement in percent, 100 turns smoothing off
+    pub smoothing: u8,
+}
+
+impl TrackerConfig {
+    // Tracking of the proxy. A vehicle may skip two frames, one that disappears
+    // within 15 m is taken to have passed the rider.
+    pub const DEFAULT: Self = Self {
+        gate: 10,
+        coast_frames: 2,
+        passed_range: 15,
+        smoothing: 60,
+    };
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrackEvent {
+    NewVehicle(u8),
+    VehiclePassed(u8),
+    // The vehicle disappeared while still further away, e.g. it turned off
+    VehicleLost(u8),
+}
+
+// A frame with the tracked targets, closest first, and what changed since the last one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tracked {
+    pub frame: RadarFrame,
+    pub events: Vec<TrackEvent, MAX_EVENTS>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Track {
+    id: u8,
+    range: i32,
+    // Change of range per frame, negative while the vehicle approaches
+    rate: i32,
+    speed: i32,
+    threat: ThreatLevel,
+    missed: u8,
+}
+
+impl Track {
+    fn predicted(&self) -> i32 {
+        self.range + self.rate
+    }
+
+    fn target(&self) -> RadarTarget {
+        // A range of 0 marks an empty slot, so a tracked vehicle stays at 1 m
+        RadarTarget {
+            id: Some(self.id),
+            range: ((self.range + SCALE / 2) / SCALE).clamp(1, u8::MAX as i32) as u8,
+            speed: ((self.speed + SCALE / 2) / SCALE).clamp(0, u8::MAX as i32) as u8,
+            threat: self.threat,
+        }
+    }
+}
+
+// Tracks as they were before the latest radar cycle, and the events reported for it
+#[derive(Debug, Clone)]
+struct Previous {
+    cycle: u8,
+    tracks: Vec<Track, MAX_TRACKS>,
+    next_id: u8,
+    events: Vec<TrackEvent, MAX_EVENTS>,
+}
+
+// Follows vehicles from frame to frame. Every target is matched to the track
+// whose expected range is closest, within the gate; range and speed are
+// smoothed with an alpha-beta filter, so a track also follows the approach of
+// its vehicle while it is missing for a few frames.
+//
+// The tracks move on once per radar cycle. The page buffer forwards a cycle
+// again when a late page completes it; that frame replaces the first one of
+// its cycle, and only events not yet reported for the cycle are reported.
+pub struct Tracker {
+    config: TrackerConfig,
+    tracks: Vec<Track, MAX_TRACKS>,
+    next_id: u8,
+    previous: Option<Previous>,
+}
+
+impl Tracker {
+    pub fn new(config: TrackerConfig) -> Self {
+        Self {
+            config,
+            tracks: Vec::new(),
+            next_id: 1,
+            previous: None,
+        }
+    }
+
+    pub fn update(&mut self, frame: &RadarFrame) -> Tracked {
+        let mut events = Vec::new();
+        // Without the radar nothing can be said about the vehicles
+        if !frame.online {
+            self.reset();
+            return Tracked {
+                frame: frame.clone(),
+                events,
+            };
+        }
+
+        let mut reported = Vec::new();
+        match self.previous.take() {
+            Some(previous) if previous.cycle == frame.cycle => {
+                self.tracks = previous.tracks;
+                self.next_id = previous.next_id;
+                reported = previous.events;
+            }
+            _ => {}
+        }
+        let mut previous = Previous {
+            cycle: frame.cycle,
+            tracks: self.tracks.clone(),
+            next_id: self.next_id,
+            events: reported.clone(),
+        };
+
+        let mut ids = self.associate(frame);
+        let config = self.config;
+        let alpha = config.smoothing.clamp(1, 100) as i32;
+        let beta = alpha * alpha / (200 - alpha);
+
+        self.tracks.retain_mut(|track| {
+            let predicted = track.predicted();
+            match ids.iter().position(|id| *id == Some(track.id)) {
+                Some(index) => {
+                    let target = &frame.targets[index];
+                    let residual = target.range as i32 * SCALE - predicted;
+                    track.range = predicted + residual * alpha / 100;
+                    track.rate += residual * beta / 100;
+                    track.speed += (target.speed as i32 * SCALE - track.speed) * alpha / 100;
+                    track.threat = target.threat;
+                    track.missed = 0;
+                    true
+                }
+                None => {
+                    track.range = predicted.max(0);
+                    track.missed += 1;
+                    if track.missed <= config.coast_frames {
+                        return true;
+                    }
+                    let event = match track.range <= config.passed_range as i32 * SCALE {
+                        true => TrackEvent::VehiclePassed(track.id),
+                        false => TrackEvent::VehicleLost(track.id),
+                    };
+                    let _ = events.push(event);
+                    false
+                }
+            }
+        });
+
+        for (id, target) in ids.iter_mut().zip(&frame.targets) {
+            if id.is_some() || self.tracks.is_full() {
+                continue;
+            }
+            let track = Track {
+                id: self.allocate_id(),
+                range: target.range as i32 * SCALE,
+                rate: 0,
+                speed: target.speed as i32 * SCALE,
+                threat: target.threat,
+                missed: 0,
+            };
+            *id = Some(track.id);
+            let _ = self.tracks.push(track);
+            let _ = events.push(TrackEvent::NewVehicle(track.id));
+        }
+
+        // Targets that found no room for a track are passed on as they are
+        let mut targets: Vec<RadarTarget, MAX_TARGETS> = ids
+            .iter()
+            .zip(&frame.targets)
+            .map(|(id, target)| {
+                self.tracks
+                    .iter()
+                    .find(|track| Some(track.id) == *id)
+                    .map_or(*target, Track::target)
+            })
+            .collect();
+        targets.sort_unstable_by_key(|target| (target.range, target.id));
+
+        events.retain(|event| !reported.contains(event));
+        for event in &events {
+            let _ = previous.events.push(*event);
+        }
+        self.previous = Some(previous);
+
+        Tracked {
+            frame: RadarFrame {
+                targets,
+                ..frame.clone()
+            },
+            events,
+        }
+    }
+
+    // Forgets every vehicle, e.g. when the radar is lost
+    pub fn reset(&mut self) {
+        self.tracks.clear();
+        self.previous = None;
+    }
+
+    // Id of the track each target belongs to. The closest pairs of track and
+    // target are matched first, on a tie the older track wins.
+    fn associate(&self, frame: &RadarFrame) -> Vec<Option<u8>, MAX_TARGETS> {
+        let gate = self.config.gate as i32 * SCALE;
+        let mut pairs: Vec<(i32, usize, usize), { MAX_TRACKS * MAX_TARGETS }> = Vec::new();
+        for (track_index, track) in self.tracks.iter().enumerate() {
+            for (target_index, target) in frame.targets.iter().enumerate() {
+                let distance = (target.range as i32 * SCALE - track.predicted()).abs();
+                if distance <= gate {
+                    let _ = pairs.push((distance, track_index, target_index));
+                }
+            }
+        }
+        pairs.sort_unstable();
+
+        let mut ids: Vec<Option<u8>, MAX_TARGETS> = frame.targets.iter().map(|_| None).collect();
+        let mut matched = [false; MAX_TRACKS];
+        for (_, track_index, target_index) in pairs {
+            if matched[track_index] || ids[target_index].is_some() {
+                continue;
+            }
+            matched[track_index] = true;
+            ids[target_index] = Some(self.tracks[track_index].id);
+        }
+        ids
+    }
+
+    // Ids wrap around, but never to one that is still in use
+    fn allocate_id(&mut self) -> u8 {
+        loop {
+            let id = self.next_id;
+            self.next_id = self.next_id.wrapping_add(1);
+            if !self.tracks.iter().any(|track| track.id == id) {
+                return id;
+            }
+        }
+    }
+}
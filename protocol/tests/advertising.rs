@@ -0,0 +1,249 @@
+use magene_protocol::advertising::{AdError, AdStructure, AdStructures, Advertisement, Uuid};
+use proptest::prelude::*;
+
+const RADARLIGHT_SERVICE: u128 = 0x8ce5cc010a4d11e9ab14d663bd873d93;
+
+fn structure(ad_type: u8, data: &[u8]) -> Vec<u8> {
+    let mut structure = vec![data.len() as u8 + 1, ad_type];
+    structure.extend_from_slice(data);
+    structure
+}
+
+fn parse(data: &[u8]) -> Vec<Result<AdStructure<'_>, AdError>> {
+    AdStructures::new(data).collect()
+}
+
+#[test]
+fn empty_data_has_no_structures() {
+    assert!(parse(&[]).is_empty());
+    assert_eq!(Advertisement::new(&[]).local_name(), None);
+}
+
+#[test]
+fn name_in_last_structure_is_found() {
+    let mut data = structure(0x01, &[0x06]);
+    data.extend(structure(0x09, b"34660-5"));
+    assert_eq!(Advertisement::new(&data).local_name(), Some("34660-5"));
+}
+
+#[test]
+fn name_as_only_structure_is_found() {
+    let data = structure(0x09, b"RadarProxy");
+    assert_eq!(Advertisement::new(&data).local_name(), Some("RadarProxy"));
+}
+
+#[test]
+fn complete_name_is_preferred_over_shortened_name() {
+    let mut data = structure(0x08, b"3466");
+    data.extend(structure(0x09, b"34660-5"));
+    assert_eq!(Advertisement::new(&data).local_name(), Some("34660-5"));
+
+    let data = structure(0x08, b"3466");
+    assert_eq!(Advertisement::new(&data).local_name(), Some("3466"));
+}
+
+#[test]
+fn invalid_utf8_name_is_ignored() {
+    let data = structure(0x09, &[0xFF, 0xFE]);
+    assert_eq!(Advertisement::new(&data).local_name(), None);
+}
+
+#[test]
+fn zero_length_ends_the_data() {
+    let mut data = structure(0x01, &[0x06]);
+    data.extend([0, 0, 0]);
+    data.extend(structure(0x09, b"hidden"));
+    assert_eq!(parse(&data), vec![Ok(AdStructure::Flags(0x06))]);
+}
+
+#[test]
+fn truncated_structure_is_reported_and_ends_parsing() {
+    let mut data = structure(0x01, &[0x06]);
+    data.extend([0x05, 0x09, b'a']);
+    assert_eq!(
+        parse(&data),
+        vec![
+            Ok(AdStructure::Flags(0x06)),
+            Err(AdError::Truncated { offset: 3 })
+        ]
+    );
+}
+
+#[test]
+fn malformed_structure_is_skipped() {
+    let mut data = structure(0x01, &[0x06, 0x00]);
+    data.extend(structure(0x09, b"name"));
+    assert_eq!(
+        parse(&data)[0],
+        Err(AdError::InvalidLength {
+            ad_type: 0x01,
+            length: 2
+        })
+    );
+    assert_eq!(Advertisement::new(&data).local_name(), Some("name"));
+}
+
+#[test]
+fn uuid_lists_of_every_size() {
+    let mut data = structure(0x03, &[0x0F, 0x18, 0x0A, 0x18]);
+    data.extend(structure(0x04, &[0x78, 0x56, 0x34, 0x12]));
+    data.extend(structure(0x07, &RADARLIGHT_SERVICE.to_le_bytes()));
+    let advertisement = Advertisement::new(&data);
+
+    assert!(advertisement.advertises_service(Uuid::Uuid16(0x180F)));
+    assert!(advertisement.advertises_service(Uuid::Uuid16(0x180A)));
+    assert!(advertisement.advertises_service(Uuid::Uuid32(0x1234_5678)));
+    assert!(advertisement.advertises_service(Uuid::Uuid128(RADARLIGHT_SERVICE)));
+    assert!(!advertisement.advertises_service(Uuid::Uuid16(0x1816)));
+
+    let Some(Ok(AdStructure::ServiceUuids(list))) = AdStructures::new(&data).next() else {
+        panic!("expected a UUID list");
+    };
+    assert!(list.complete);
+    assert_eq!(
+        list.iter().collect::<Vec<_>>(),
+        vec![Uuid::Uuid16(0x180F), Uuid::Uuid16(0x180A)]
+    );
+}
+
+#[test]
+fn uuid_list_with_partial_uuid_is_invalid() {
+    let data = structure(0x06, &[0u8; 17]);
+    assert_eq!(
+        parse(&data),
+        vec![Err(AdError::InvalidLength {
+            ad_type: 0x06,
+            length: 17
+        })]
+    );
+}
+
+#[test]
+fn service_data() {
+    let data = structure(0x16, &[0x0F, 0x18, 0x5A]);
+    assert_eq!(
+        parse(&data),
+        vec![Ok(AdStructure::ServiceData {
+            uuid: Uuid::Uuid16(0x180F),
+            data: &[0x5A]
+        })]
+    );
+    assert!(Advertisement::new(&data).advertises_service(Uuid::Uuid16(0x180F)));
+
+    let mut service_data = RADARLIGHT_SERVICE.to_le_bytes().to_vec();
+    service_data.push(1);
+    let data = structure(0x21, &service_data);
+    assert!(Advertisement::new(&data).advertises_service(Uuid::Uuid128(RADARLIGHT_SERVICE)));
+
+    let data = structure(0x20, &[0x01, 0x02]);
+    assert_eq!(
+        parse(&data),
+        vec![Err(AdError::InvalidLength {
+            ad_type: 0x20,
+            length: 2
+        })]
+    );
+}
+
+#[test]
+fn tx_power_appearance_and_manufacturer_data() {
+    let mut data = structure(0x0A, &[0xF4]);
+    data.extend(structure(0x19, &[0x84, 0x04]));
+    data.extend(structure(0xFF, &[0x59, 0x00, 0x01, 0x02]));
+    let advertisement = Advertisement::new(&data);
+
+    assert_eq!(advertisement.tx_power(), Some(-12));
+    assert_eq!(advertisement.appearance(), Some(0x0484));
+    assert_eq!(
+        advertisement.manufacturer_data(),
+        Some((0x0059, &[0x01, 0x02][..]))
+    );
+}
+
+#[test]
+fn unknown_types_are_kept() {
+    let data = structure(0x2A, &[1, 2, 3]);
+    assert_eq!(
+        parse(&data),
+        vec![Ok(AdStructure::Unknown {
+            ad_type: 0x2A,
+            data: &[1, 2, 3]
+        })]
+    );
+}
+
+// Types that can not hold a name, used to surround one
+fn other_structure() -> impl Strategy<Value = (u8, Vec<u8>)> {
+    (
+        prop::sample::select(vec![0x01u8, 0x03, 0x07, 0x0A, 0x16, 0x19, 0x2A, 0xFF]),
+        prop::collection::vec(any::<u8>(), 0..8),
+    )
+}
+
+proptest! {
+    #[test]
+    fn arbitrary_data_does_not_panic(data in prop::collection::vec(any::<u8>(), 0..64)) {
+        let advertisement = Advertisement::new(&data);
+        let _ = advertisement.local_name();
+        let _ = advertisement.advertises_service(Uuid::Uuid128(RADARLIGHT_SERVICE));
+        let _ = advertisement.tx_power();
+        let _ = advertisement.appearance();
+        let _ = advertisement.manufacturer_data();
+
+        let structures = parse(&data);
+        // Every structure takes at least two bytes, and a truncated one is the last
+        prop_assert!(structures.len() <= data.len().div_ceil(2));
+        if let Some(position) = structures
+            .iter()
+            .position(|s| matches!(s, Err(AdError::Truncated { .. })))
+        {
+            prop_assert_eq!(position, structures.len() - 1);
+        }
+    }
+
+    #[test]
+    fn every_structure_is_found(
+        structures in prop::collection::vec(
+            (1u8..=0xFF, prop::collection::vec(any::<u8>(), 0..30)),
+            0..8
+        )
+    ) {
+        let data: Vec<u8> = structures
+            .iter()
+            .flat_map(|(ad_type, data)| structure(*ad_type, data))
+            .collect();
+        let parsed = parse(&data);
+        prop_assert_eq!(parsed.len(), structures.len());
+        let truncated = parsed
+            .iter()
+            .any(|s| matches!(s, Err(AdError::Truncated { .. })));
+        prop_assert!(!truncated);
+    }
+
+    #[test]
+    fn name_is_found_anywhere(
+        before in prop::collection::vec(other_structure(), 0..4),
+        name in "[ -~]{0,29}",
+        after in prop::collection::vec(other_structure(), 0..4),
+    ) {
+        let mut data: Vec<u8> = before
+            .iter()
+            .flat_map(|(ad_type, data)| structure(*ad_type, data))
+            .collect();
+        data.extend(structure(0x09, name.as_bytes()));
+        data.extend(after.iter().flat_map(|(ad_type, data)| structure(*ad_type, data)));
+        prop_assert_eq!(Advertisement::new(&data).local_name(), Some(name.as_str()));
+    }
+
+    #[test]
+    fn advertised_uuid_is_found(
+        uuids in prop::collection::vec(any::<u128>(), 1..4),
+        index in any::<prop::sample::Index>(),
+    ) {
+        let list: Vec<u8> = uuids.iter().flat_map(|uuid| uuid.to_le_bytes()).collect();
+        let mut data = structure(0x01, &[0x06]);
+        data.extend(structure(0x06, &list));
+        let uuid = uuids[index.index(uuids.len())];
+        prop_assert!(Advertisement::new(&data).advertises_service(Uuid::Uuid128(uuid)));
+    }
+}
@@ -0,0 +1,113 @@
+use embassy_time::Duration;
+use magene_protocol::alert::{AlertConfig, AlertDetector, AlertEvent};
+use magene_protocol::radar::ThreatLevel;
+use magene_protocol::tracker::TrackEvent;
+
+mod common;
+use common::{frame, target, vehicles, TestClock};
+
+const CONFIG: AlertConfig = AlertConfig {
+    min_interval: Duration::from_secs(3),
+    fast_approach_speed: 40,
+};
+
+fn detector(clock: &TestClock) -> AlertDetector<&TestClock> {
+    AlertDetector::with_clock(CONFIG, clock)
+}
+
+#[test]
+fn fast_approach_starts_at_the_threshold() {
+    let clock = TestClock::new();
+    let mut detector = detector(&clock);
+    assert_eq!(detector.update(&vehicles(&[(60, 39), (90, 20)])), None);
+    assert_eq!(
+        detector.update(&vehicles(&[(55, 40), (85, 20)])),
+        Some(AlertEvent::FastApproach)
+    );
+}
+
+#[test]
+fn high_threat_comes_before_a_fast_approach() {
+    let clock = TestClock::new();
+    let mut detector = detector(&clock);
+    let frame = frame(&[
+        target(30, 20, ThreatLevel::High),
+        target(80, 50, ThreatLevel::Low),
+    ]);
+    assert_eq!(detector.update(&frame), Some(AlertEvent::HighThreat));
+    // The fast approach began with the same frame, it is not announced afterwards
+    assert_eq!(detector.update(&frame), None);
+}
+
+#[test]
+fn ongoing_approach_is_announced_once() {
+    let clock = TestClock::new();
+    let mut detector = detector(&clock);
+    assert_eq!(
+        detector.update(&vehicles(&[(80, 50)])),
+        Some(AlertEvent::FastApproach)
+    );
+    for range in [70, 60, 50, 40] {
+        clock.advance(Duration::from_secs(2));
+        assert_eq!(detector.update(&vehicles(&[(range, 50)])), None);
+    }
+}
+
+#[test]
+fn repeated_alerts_are_suppressed_within_the_interval() {
+    let clock = TestClock::new();
+    let mut detector = detector(&clock);
+    assert_eq!(
+        detector.track_event(TrackEvent::NewVehicle(1)),
+        Some(AlertEvent::NewVehicle)
+    );
+    clock.advance(CONFIG.min_interval - Duration::from_millis(1));
+    assert_eq!(detector.track_event(TrackEvent::NewVehicle(2)), None);
+    // Other kinds of alerts have limits of their own
+    assert_eq!(
+        detector.track_event(TrackEvent::VehiclePassed(1)),
+        Some(AlertEvent::VehiclePassed)
+    );
+    clock.advance(Duration::from_millis(1));
+    assert_eq!(
+        detector.track_event(TrackEvent::NewVehicle(3)),
+        Some(AlertEvent::NewVehicle)
+    );
+}
+
+#[test]
+fn lost_vehicle_is_not_announced() {
+    let clock = TestClock::new();
+    let mut detector = detector(&clock);
+    assert_eq!(detector.track_event(TrackEvent::VehicleLost(1)), None);
+}
+
+#[test]
+fn approach_rearms_once_it_ended() {
+    let clock = TestClock::new();
+    let mut detector = detector(&clock);
+    assert_eq!(
+        detector.update(&vehicles(&[(80, 50)])),
+        Some(AlertEvent::FastApproach)
+    );
+    assert_eq!(detector.update(&vehicles(&[])), None);
+    // Armed again, but within the interval of the last alert
+    assert_eq!(detector.update(&vehicles(&[(90, 45)])), None);
+    assert_eq!(detector.update(&vehicles(&[])), None);
+    clock.advance(CONFIG.min_interval);
+    assert_eq!(
+        detector.update(&vehicles(&[(90, 45)])),
+        Some(AlertEvent::FastApproach)
+    );
+}
+
+#[test]
+fn lost_radar_rearms_the_detector() {
+    let clock = TestClock::new();
+    let mut detector = detector(&clock);
+    let frame = frame(&[target(30, 20, ThreatLevel::High)]);
+    assert_eq!(detector.update(&frame), Some(AlertEvent::HighThreat));
+    detector.reset();
+    clock.advance(CONFIG.min_interval);
+    assert_eq!(detector.update(&frame), Some(AlertEvent::HighThreat));
+}
@@ -0,0 +1,331 @@
+use embassy_time::{Duration, Instant};
+use magene_protocol::animation::{
+    Animation, AnimationEngine, Keyframe, Layer, RadarIndication, RadarIndicationConfig,
+    FADE_FRAME_TIME,
+};
+use magene_protocol::indicator::{Indicator, MockIndicator};
+use magene_protocol::radar::ThreatLevel;
+use rgb::RGB;
+
+mod common;
+use common::{frame, target, TestClock};
+
+const BLACK: RGB<u8> = RGB::new(0, 0, 0);
+const RED: RGB<u8> = RGB::new(255, 0, 0);
+const GREEN: RGB<u8> = RGB::new(0, 255, 0);
+const BLUE: RGB<u8> = RGB::new(0, 0, 255);
+
+const RADAR: RadarIndicationConfig = RadarIndicationConfig {
+    brightness: 31,
+    dim_brightness: 4,
+    dim_delay: Duration::from_secs(30),
+    max_range: 140,
+    fast_approach_speed: 40,
+};
+
+fn ms(millis: u64) -> Duration {
+    Duration::from_millis(millis)
+}
+
+fn at(millis: u64) -> Instant {
+    Instant::from_millis(millis)
+}
+
+// Runs the engine like the LED task does: writes the output when it changes and
+// sleeps until the next update. Returns when each write happened.
+fn play<const N: usize>(
+    engine: &AnimationEngine,
+    start: Instant,
+    until: Instant,
+    indicator: &mut MockIndicator<N>,
+) -> Vec<Instant> {
+    let mut writes = Vec::new();
+    let mut now = start;
+    while now < until {
+        let (_, color, level) = engine.sample(now);
+        if indicator.last() != Some(&(color, level)) {
+            indicator.set(color, level).unwrap();
+            writes.push(now);
+        }
+        now = engine.next_update(now);
+    }
+    writes
+}
+
+#[test]
+fn hold_keyframe_keeps_its_value() {
+    let animation = Animation::new(
+        &[
+            Keyframe::hold(RED, 100, ms(100)),
+            Keyframe::hold(GREEN, 50, ms(100)),
+        ],
+        false,
+    );
+    assert_eq!(animation.sample(ms(0)), Some((RED, 100)));
+    assert_eq!(animation.sample(ms(99)), Some((RED, 100)));
+    assert_eq!(animation.sample(ms(100)), Some((GREEN, 50)));
+    assert_eq!(animation.sample(ms(200)), None);
+}
+
+#[test]
+fn fade_keyframe_interpolates_towards_the_next() {
+    let animation = Animation::new(
+        &[
+            Keyframe::fade(BLACK, 0, ms(100)),
+            Keyframe::hold(RGB::new(200, 100, 0), 200, ms(100)),
+        ],
+        false,
+    );
+    assert_eq!(animation.sample(ms(0)), Some((BLACK, 0)));
+    assert_eq!(animation.sample(ms(50)), Some((RGB::new(100, 50, 0), 100)));
+    assert_eq!(
+        animation.sample(ms(100)),
+        Some((RGB::new(200, 100, 0), 200))
+    );
+}
+
+#[test]
+fn last_fade_of_a_single_run_holds() {
+    let animation = Animation::new(&[Keyframe::fade(RED, 80, ms(100))], false);
+    assert_eq!(animation.sample(ms(50)), Some((RED, 80)));
+}
+
+#[test]
+fn breathe_rises_and_falls() {
+    let animation = Animation::breathe(BLUE, 64, Duration::from_secs(4));
+    assert_eq!(animation.total_duration(), Duration::from_secs(4));
+    let levels: Vec<u8> = [0, 1000, 2000, 3000, 4000, 5000]
+        .into_iter()
+        .map(|millis| animation.sample(ms(millis)).unwrap().1)
+        .collect();
+    assert_eq!(levels, vec![0, 32, 64, 32, 0, 32]);
+    assert_eq!(animation.sample(ms(1000)).unwrap().0, BLUE);
+}
+
+#[test]
+fn sequence_wraps_around() {
+    let animation = Animation::sequence(&[RED, GREEN, BLUE], 10, ms(100));
+    let colors: Vec<RGB<u8>> = [0, 150, 250, 300, 450, 1_000_050]
+        .into_iter()
+        .map(|millis| animation.sample(ms(millis)).unwrap().0)
+        .collect();
+    assert_eq!(colors, vec![RED, GREEN, BLUE, RED, GREEN, GREEN]);
+}
+
+#[test]
+fn keyframes_beyond_the_limit_are_dropped() {
+    let keyframes = [Keyframe::hold(RED, 1, ms(10)); 12];
+    let animation = Animation::new(&keyframes, true);
+    assert_eq!(animation.total_duration(), ms(80));
+}
+
+#[test]
+fn empty_animation_shows_nothing() {
+    let animation = Animation::new(&[], true);
+    assert_eq!(animation.sample(ms(0)), None);
+    assert_eq!(animation.next_change(ms(0)), None);
+}
+
+#[test]
+fn next_change_of_hold_and_fade() {
+    let animation = Animation::new(
+        &[
+            Keyframe::hold(RED, 100, ms(100)),
+            Keyframe::fade(GREEN, 100, ms(100)),
+        ],
+        false,
+    );
+    assert_eq!(animation.next_change(ms(30)), Some(ms(70)));
+    assert_eq!(animation.next_change(ms(130)), Some(FADE_FRAME_TIME));
+    assert_eq!(animation.next_change(ms(200)), None);
+}
+
+#[test]
+fn error_beats_status_beats_idle() {
+    let mut engine = AnimationEngine::new();
+    let now = at(1000);
+    engine.set(Layer::Idle, Animation::solid(GREEN, 10), now);
+    engine.set(Layer::Status, Animation::solid(BLUE, 20), now);
+    engine.set(Layer::Error, Animation::solid(RED, 30), now);
+
+    assert_eq!(engine.sample(now), (Some(Layer::Error), RED, 30));
+    engine.clear(Layer::Error);
+    assert_eq!(engine.sample(now), (Some(Layer::Status), BLUE, 20));
+    engine.clear(Layer::Status);
+    assert_eq!(engine.sample(now), (Some(Layer::Idle), GREEN, 10));
+    engine.clear(Layer::Idle);
+    assert_eq!(engine.sample(now), (None, BLACK, 0));
+}
+
+#[test]
+fn finished_layer_falls_through() {
+    let mut engine = AnimationEngine::new();
+    engine.set(Layer::Idle, Animation::solid(GREEN, 10), at(0));
+    engine.set(
+        Layer::Error,
+        Animation::new(&[Keyframe::hold(RED, 30, ms(100))], false),
+        at(0),
+    );
+    assert!(engine.is_active(Layer::Error, at(50)));
+    assert!(!engine.is_active(Layer::Error, at(100)));
+
+    let mut indicator = MockIndicator::<8>::new();
+    let writes = play(&engine, at(0), at(300), &mut indicator);
+    assert_eq!(indicator.history.as_slice(), &[(RED, 30), (GREEN, 10)]);
+    assert_eq!(writes, vec![at(0), at(100)]);
+}
+
+#[test]
+fn blink_is_written_at_each_edge() {
+    let mut engine = AnimationEngine::new();
+    engine.set(
+        Layer::Status,
+        Animation::blink(RED, 50, ms(100), ms(500)),
+        at(0),
+    );
+
+    let mut indicator = MockIndicator::<8>::new();
+    let writes = play(&engine, at(0), at(1000), &mut indicator);
+    assert_eq!(
+        indicator.history.as_slice(),
+        &[(RED, 50), (BLACK, 50), (RED, 50), (BLACK, 50)]
+    );
+    assert_eq!(writes, vec![at(0), at(100), at(500), at(600)]);
+}
+
+#[test]
+fn higher_layer_drives_the_updates() {
+    let mut engine = AnimationEngine::new();
+    engine.set(Layer::Idle, Animation::breathe(GREEN, 64, ms(400)), at(0));
+    engine.set(
+        Layer::Error,
+        Animation::blink(RED, 50, ms(100), ms(200)),
+        at(0),
+    );
+    assert_eq!(engine.next_update(at(0)), at(100));
+
+    let mut indicator = MockIndicator::<4>::new();
+    play(&engine, at(0), at(400), &mut indicator);
+    assert!(indicator
+        .history
+        .iter()
+        .all(|&(color, _)| color == RED || color == BLACK));
+}
+
+#[test]
+fn repeating_update_keeps_its_phase() {
+    let mut engine = AnimationEngine::new();
+    engine.set(
+        Layer::Alert,
+        Animation::blink(RED, 50, ms(100), ms(200)),
+        at(0),
+    );
+    // The same pattern set again mid-blink does not restart it
+    engine.set(
+        Layer::Alert,
+        Animation::blink(RED, 50, ms(100), ms(200)),
+        at(150),
+    );
+    assert_eq!(engine.sample(at(150)), (Some(Layer::Alert), BLACK, 50));
+
+    // A single run always starts over
+    engine.set(
+        Layer::Alert,
+        Animation::new(&[Keyframe::hold(GREEN, 50, ms(100))], false),
+        at(150),
+    );
+    assert_eq!(engine.sample(at(200)), (Some(Layer::Alert), GREEN, 50));
+}
+
+#[test]
+fn mock_indicator_keeps_the_latest_writes() {
+    let mut indicator = MockIndicator::<2>::new();
+    indicator.set(RED, 1).unwrap();
+    indicator.set(GREEN, 2).unwrap();
+    indicator.off().unwrap();
+    assert_eq!(indicator.history.as_slice(), &[(GREEN, 2), (BLACK, 0)]);
+}
+
+fn indication(clock: &TestClock) -> RadarIndication<&TestClock> {
+    RadarIndication::with_clock(RADAR, clock)
+}
+
+fn blink(color: RGB<u8>, on: u64, period: u64) -> Option<Animation> {
+    Some(Animation::blink(
+        color,
+        RADAR.brightness,
+        ms(on),
+        ms(period),
+    ))
+}
+
+#[test]
+fn closest_vehicle_blinks_faster_and_redder_as_it_approaches() {
+    let clock = TestClock::new();
+    let mut radar = indication(&clock);
+    assert_eq!(radar.alert_animation(), None);
+
+    radar.set_frame(Some(frame(&[
+        target(100, 20, ThreatLevel::Low),
+        target(140, 20, ThreatLevel::Low),
+    ])));
+    assert_eq!(
+        radar.alert_animation(),
+        blink(RGB::new(72, 183, 0), 200, 750)
+    );
+    radar.set_frame(Some(frame(&[target(14, 20, ThreatLevel::Low)])));
+    assert_eq!(
+        radar.alert_animation(),
+        blink(RGB::new(229, 26, 0), 200, 320)
+    );
+    // Beyond the range it stays green at the slowest rate
+    radar.set_frame(Some(frame(&[target(200, 20, ThreatLevel::Low)])));
+    assert_eq!(radar.alert_animation(), blink(GREEN, 200, 950));
+
+    radar.set_frame(None);
+    assert_eq!(radar.alert_animation(), None);
+}
+
+#[test]
+fn threat_levels_change_the_alert() {
+    let clock = TestClock::new();
+    let mut radar = indication(&clock);
+    // A medium threat is at least orange
+    radar.set_frame(Some(frame(&[target(140, 20, ThreatLevel::Medium)])));
+    assert_eq!(
+        radar.alert_animation(),
+        blink(RGB::new(255, 165, 0), 200, 950)
+    );
+    radar.set_frame(Some(frame(&[target(120, 20, ThreatLevel::High)])));
+    assert_eq!(radar.alert_animation(), blink(RED, 75, 150));
+    // as does a fast vehicle at a low threat
+    radar.set_frame(Some(frame(&[
+        target(120, 20, ThreatLevel::Low),
+        target(130, 40, ThreatLevel::None),
+    ])));
+    assert_eq!(radar.alert_animation(), blink(RED, 75, 150));
+}
+
+#[test]
+fn clear_road_dims_the_layers_below_the_alert() {
+    let clock = TestClock::new();
+    let mut radar = indication(&clock);
+    assert_eq!(radar.level_limit(Some(Layer::Status)), u8::MAX);
+
+    clock.advance(RADAR.dim_delay);
+    assert_eq!(radar.level_limit(Some(Layer::Status)), u8::MAX);
+    clock.advance(ms(1));
+    assert_eq!(radar.level_limit(Some(Layer::Status)), RADAR.dim_brightness);
+    assert_eq!(radar.level_limit(None), RADAR.dim_brightness);
+    assert_eq!(radar.level_limit(Some(Layer::Alert)), u8::MAX);
+    assert_eq!(radar.level_limit(Some(Layer::Error)), u8::MAX);
+
+    // Empty frames and a lost radar keep it dimmed, a vehicle brightens it again
+    radar.set_frame(Some(frame(&[])));
+    radar.set_frame(None);
+    assert_eq!(radar.level_limit(Some(Layer::Idle)), RADAR.dim_brightness);
+    radar.set_frame(Some(frame(&[target(80, 20, ThreatLevel::Low)])));
+    assert_eq!(radar.level_limit(Some(Layer::Idle)), u8::MAX);
+    clock.advance(RADAR.dim_delay + ms(1));
+    assert_eq!(radar.level_limit(Some(Layer::Idle)), RADAR.dim_brightness);
+}
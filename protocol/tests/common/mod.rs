@@ -0,0 +1,193 @@
+// Fixtures shared by the tests of the tracking pipeline, the time-dependent
+// logic and the flash users. Every test crate uses only some of them.
+#![allow(dead_code)]
+
+use std::cell::Cell;
+
+use embassy_time::{Duration, Instant};
+use embedded_storage::nor_flash::{
+    check_erase, check_read, check_write, ErrorType, NorFlash, NorFlashErrorKind, ReadNorFlash,
+};
+use magene_protocol::clock::Clock;
+use magene_protocol::radar::{
+    RadarFrame, RadarTarget, ThreatLevel, MAX_TARGETS, PAGE_STATUS_OFFLINE,
+};
+use magene_protocol::tracker::{Tracked, TrackerConfig};
+use proptest::prelude::*;
+
+pub const SECTOR_SIZE: usize = 4096;
+
+// NOR flash in memory with the erase size of the ESP32-S3. Writes can only
+// clear bits, like on the chip, so a missing erase corrupts the data.
+pub struct MemoryFlash {
+    pub data: Vec<u8>,
+    pub erases: usize,
+}
+
+impl MemoryFlash {
+    pub fn new(sectors: usize) -> Self {
+        Self {
+            data: vec![0xFF; sectors * SECTOR_SIZE],
+            erases: 0,
+        }
+    }
+}
+
+impl ErrorType for MemoryFlash {
+    type Error = NorFlashErrorKind;
+}
+
+impl ReadNorFlash for MemoryFlash {
+    const READ_SIZE: usize = 1;
+
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+        check_read(self, offset, bytes.len())?;
+        let offset = offset as usize;
+        bytes.copy_from_slice(&self.data[offset..offset + bytes.len()]);
+        Ok(())
+    }
+
+    fn capacity(&self) -> usize {
+        self.data.len()
+    }
+}
+
+impl NorFlash for MemoryFlash {
+    const WRITE_SIZE: usize = 4;
+    const ERASE_SIZE: usize = SECTOR_SIZE;
+
+    fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+        check_erase(self, from, to)?;
+        self.data[from as usize..to as usize].fill(0xFF);
+        self.erases += 1;
+        Ok(())
+    }
+
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+        check_write(self, offset, bytes.len())?;
+        let offset = offset as usize;
+        for (cell, byte) in self.data[offset..].iter_mut().zip(bytes) {
+            *cell &= byte;
+        }
+        Ok(())
+    }
+}
+
+// Clock that only moves when the test says so
+pub struct TestClock(Cell<Instant>);
+
+impl TestClock {
+    pub fn new() -> Self {
+        Self(Cell::new(Instant::from_secs(1)))
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        self.0.set(self.0.get() + duration);
+    }
+
+    pub fn set(&self, now: Instant) {
+        self.0.set(now);
+    }
+}
+
+impl Clock for TestClock {
+    fn now(&self) -> Instant {
+        self.0.get()
+    }
+}
+
+// Tracking of the proxy without smoothing, so tracked targets are as measured
+pub const CONFIG: TrackerConfig = TrackerConfig {
+    smoothing: 100,
+    ..TrackerConfig::DEFAULT
+};
+
+pub fn target(range: u8, speed: u8, threat: ThreatLevel) -> RadarTarget {
+    RadarTarget {
+        id: None,
+        range,
+        speed,
+        threat,
+    }
+}
+
+thread_local! {
+    static CYCLE: Cell<u8> = const { Cell::new(0) };
+}
+
+fn next_cycle() -> u8 {
+    CYCLE.with(|cycle| {
+        cycle.set((cycle.get() + 1) % PAGE_STATUS_OFFLINE);
+        cycle.get()
+    })
+}
+
+fn frame_of_cycle(cycle: u8, targets: &[RadarTarget]) -> RadarFrame {
+    RadarFrame {
+        online: true,
+        cycle,
+        targets: targets.iter().copied().collect(),
+        pages: Default::default(),
+    }
+}
+
+// Every frame is a radar cycle of its own, like the frames of the page buffer
+pub fn frame(targets: &[RadarTarget]) -> RadarFrame {
+    frame_of_cycle(next_cycle(), targets)
+}
+
+// Frame of vehicles at a low threat, given by range and speed
+pub fn vehicles(targets: &[(u8, u8)]) -> RadarFrame {
+    let targets: Vec<_> = targets
+        .iter()
+        .map(|&(range, speed)| target(range, speed, ThreatLevel::Low))
+        .collect();
+    frame(&targets)
+}
+
+pub fn ids(tracked: &Tracked) -> Vec<Option<u8>> {
+    tracked
+        .frame
+        .targets
+        .iter()
+        .map(|target| target.id)
+        .collect()
+}
+
+pub fn ranges(tracked: &Tracked) -> Vec<u8> {
+    tracked
+        .frame
+        .targets
+        .iter()
+        .map(|target| target.range)
+        .collect()
+}
+
+pub fn any_target() -> impl Strategy<Value = RadarTarget> {
+    (1u8..=255, any::<u8>(), 0u8..4)
+        .prop_map(|(range, speed, threat)| target(range, speed, ThreatLevel::from(threat)))
+}
+
+// Frames of generated sequences are all of cycle 0, see `numbered`
+pub fn any_frame() -> impl Strategy<Value = RadarFrame> {
+    prop::collection::vec(any_target(), 0..=MAX_TARGETS)
+        .prop_map(|targets| frame_of_cycle(0, &targets))
+}
+
+// Mostly frames with targets, now and then the radar is offline
+pub fn radar_frame() -> impl Strategy<Value = RadarFrame> {
+    prop_oneof![
+        1 => Just(RadarFrame::offline()),
+        8 => any_frame(),
+    ]
+}
+
+// Gives every frame of a sequence a radar cycle of its own
+pub fn numbered(frames: Vec<RadarFrame>) -> impl Iterator<Item = RadarFrame> {
+    frames.into_iter().enumerate().map(|(cycle, mut frame)| {
+        if frame.online {
+            frame.cycle = cycle as u8;
+        }
+        frame
+    })
+}
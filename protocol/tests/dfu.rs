@@ -0,0 +1,307 @@
+mod common;
+
+use common::{MemoryFlash, SECTOR_SIZE};
+use ed25519_compact::{KeyPair, Seed};
+use magene_protocol::dfu::{
+    decode_chunk, Command, Update, UpdateError, DFU_FINISH, DFU_START, IMAGE_MAGIC,
+};
+use magene_protocol::flash_ring::Partition;
+use sha2::{Digest, Sha256};
+
+// Two sectors after a sector that belongs to something else
+const PARTITION: Partition = Partition {
+    offset: SECTOR_SIZE as u32,
+    size: 2 * SECTOR_SIZE as u32,
+};
+
+fn key() -> KeyPair {
+    KeyPair::from_seed(Seed::new([7; 32]))
+}
+
+fn update() -> Update {
+    Update::new(*key().pk)
+}
+
+// Application image of `size` bytes
+fn image(size: usize) -> Vec<u8> {
+    let mut image: Vec<u8> = (0..size).map(|i| (i % 251) as u8).collect();
+    image[0] = IMAGE_MAGIC;
+    image
+}
+
+fn digest(image: &[u8]) -> [u8; 32] {
+    Sha256::digest(image).into()
+}
+
+fn signature(image: &[u8]) -> [u8; 64] {
+    *key().sk.sign(digest(image), None)
+}
+
+// Writes the image in chunks of 240 bytes, like the host tool
+fn send(update: &mut Update, flash: &mut MemoryFlash, image: &[u8]) {
+    for (index, chunk) in image.chunks(240).enumerate() {
+        update.append(flash, (index * 240) as u32, chunk).unwrap();
+    }
+}
+
+#[test]
+fn commands_round_trip() {
+    let commands = [
+        Command::Start {
+            size: 0x0001_2345,
+            digest: [3; 32],
+        },
+        Command::Signature([9; 64]),
+        Command::Finish,
+        Command::Abort,
+    ];
+    for command in commands {
+        assert_eq!(Command::decode(&command.encode()), Some(command));
+    }
+}
+
+#[test]
+fn commands_with_wrong_arguments_are_refused() {
+    assert_eq!(Command::decode(&[]), None);
+    assert_eq!(Command::decode(&[DFU_START; 36]), None);
+    assert_eq!(Command::decode(&[DFU_FINISH, 0]), None);
+    assert_eq!(Command::decode(&[0x05]), None);
+    let mut signature = Command::Signature([9; 64]).encode();
+    signature.pop();
+    assert_eq!(Command::decode(&signature), None);
+}
+
+#[test]
+fn chunks_start_with_their_offset() {
+    assert_eq!(
+        decode_chunk(&[0x10, 0x02, 0, 0, 0xAA]),
+        Some((0x210, &[0xAA][..]))
+    );
+    assert_eq!(decode_chunk(&[0x10, 0x02, 0, 0]), Some((0x210, &[][..])));
+    assert_eq!(decode_chunk(&[0x10, 0x02, 0]), None);
+}
+
+#[test]
+fn signed_image_is_written_to_the_partition() {
+    let image = image(SECTOR_SIZE + 1000);
+    let mut flash = MemoryFlash::new(4);
+    let mut update = update();
+
+    update
+        .start(PARTITION, image.len() as u32, digest(&image))
+        .unwrap();
+    update.set_signature(signature(&image)).unwrap();
+    send(&mut update, &mut flash, &image);
+    assert_eq!(update.received(), image.len() as u32);
+    update.finish(&mut flash).unwrap();
+
+    let start = PARTITION.offset as usize;
+    assert_eq!(&flash.data[start..start + image.len()], &image[..]);
+    // The sector before the partition and the rest of the last sector stay erased
+    assert!(flash.data[..start].iter().all(|&byte| byte == 0xFF));
+    assert!(flash.data[start + image.len()..]
+        .iter()
+        .all(|&byte| byte == 0xFF));
+    assert!(!update.is_started());
+}
+
+#[test]
+fn commands_before_start_are_refused() {
+    let mut flash = MemoryFlash::new(4);
+    let mut update = update();
+
+    assert_eq!(update.set_signature([0; 64]), Err(UpdateError::NotStarted));
+    assert_eq!(
+        update.append(&mut flash, 0, &[IMAGE_MAGIC]),
+        Err(UpdateError::NotStarted)
+    );
+    assert_eq!(update.finish(&mut flash), Err(UpdateError::NotStarted));
+    assert_eq!(flash.erases, 0);
+}
+
+#[test]
+fn abort_ends_the_update() {
+    let image = image(100);
+    let mut flash = MemoryFlash::new(4);
+    let mut update = update();
+
+    update
+        .start(PARTITION, image.len() as u32, digest(&image))
+        .unwrap();
+    update.append(&mut flash, 0, &image[..50]).unwrap();
+    update.abort();
+    assert!(!update.is_started());
+    assert_eq!(update.received(), 0);
+    assert_eq!(
+        update.append(&mut flash, 50, &image[50..]),
+        Err(UpdateError::NotStarted)
+    );
+    assert_eq!(update.finish(&mut flash), Err(UpdateError::NotStarted));
+}
+
+#[test]
+fn start_restarts_an_update_in_progress() {
+    let image = image(100);
+    let mut flash = MemoryFlash::new(4);
+    let mut update = update();
+
+    update
+        .start(PARTITION, image.len() as u32, digest(&image))
+        .unwrap();
+    update.append(&mut flash, 0, &image[..50]).unwrap();
+    update
+        .start(PARTITION, image.len() as u32, digest(&image))
+        .unwrap();
+    assert_eq!(update.received(), 0);
+    assert_eq!(
+        update.append(&mut flash, 50, &image[50..]),
+        Err(UpdateError::UnexpectedOffset(50, 0))
+    );
+}
+
+#[test]
+fn updates_are_refused_without_public_key() {
+    let mut update = Update::new([0; 32]);
+    assert_eq!(
+        update.start(PARTITION, 100, [0; 32]),
+        Err(UpdateError::NoPublicKey)
+    );
+    assert!(!update.is_started());
+}
+
+#[test]
+fn image_must_fit_the_partition() {
+    let mut update = update();
+    assert_eq!(
+        update.start(PARTITION, PARTITION.size + 1, [0; 32]),
+        Err(UpdateError::ImageTooLarge(
+            PARTITION.size + 1,
+            PARTITION.size
+        ))
+    );
+    assert_eq!(
+        update.start(PARTITION, 0, [0; 32]),
+        Err(UpdateError::ImageTooLarge(0, PARTITION.size))
+    );
+    assert!(!update.is_started());
+    update.start(PARTITION, PARTITION.size, [0; 32]).unwrap();
+}
+
+#[test]
+fn chunks_must_follow_each_other() {
+    let image = image(300);
+    let mut flash = MemoryFlash::new(4);
+    let mut update = update();
+
+    update
+        .start(PARTITION, image.len() as u32, digest(&image))
+        .unwrap();
+    assert_eq!(
+        update.append(&mut flash, 10, &image[10..20]),
+        Err(UpdateError::UnexpectedOffset(10, 0))
+    );
+    update.append(&mut flash, 0, &image[..100]).unwrap();
+    // A repeated or skipped chunk leaves the received count as it was
+    assert_eq!(
+        update.append(&mut flash, 0, &image[..100]),
+        Err(UpdateError::UnexpectedOffset(0, 100))
+    );
+    assert_eq!(
+        update.append(&mut flash, 200, &image[200..]),
+        Err(UpdateError::UnexpectedOffset(200, 100))
+    );
+    assert_eq!(update.received(), 100);
+    // The transfer resumes from the received count
+    update.append(&mut flash, 100, &image[100..]).unwrap();
+    update.set_signature(signature(&image)).unwrap();
+    update.finish(&mut flash).unwrap();
+}
+
+#[test]
+fn image_can_not_grow_past_its_size() {
+    let image = image(100);
+    let mut flash = MemoryFlash::new(4);
+    let mut update = update();
+
+    update.start(PARTITION, 60, digest(&image)).unwrap();
+    update.append(&mut flash, 0, &image[..50]).unwrap();
+    assert_eq!(
+        update.append(&mut flash, 50, &image[50..]),
+        Err(UpdateError::ImageTooLarge(100, 60))
+    );
+    assert_eq!(update.received(), 50);
+}
+
+#[test]
+fn image_must_start_with_the_magic_byte() {
+    let mut image = image(100);
+    image[0] = 0;
+    let mut flash = MemoryFlash::new(4);
+    let mut update = update();
+
+    update
+        .start(PARTITION, image.len() as u32, digest(&image))
+        .unwrap();
+    assert_eq!(
+        update.append(&mut flash, 0, &image),
+        Err(UpdateError::InvalidImage)
+    );
+    assert_eq!(
+        update.append(&mut flash, 0, &[]),
+        Err(UpdateError::InvalidImage)
+    );
+    assert_eq!(update.received(), 0);
+}
+
+#[test]
+fn incomplete_image_is_refused() {
+    let image = image(100);
+    let mut flash = MemoryFlash::new(4);
+    let mut update = update();
+
+    update
+        .start(PARTITION, image.len() as u32, digest(&image))
+        .unwrap();
+    update.set_signature(signature(&image)).unwrap();
+    update.append(&mut flash, 0, &image[..99]).unwrap();
+    assert_eq!(
+        update.finish(&mut flash),
+        Err(UpdateError::IncompleteImage(99, 100))
+    );
+    assert!(!update.is_started());
+}
+
+#[test]
+fn image_must_match_its_digest_and_signature() {
+    let image = image(100);
+    let mut other = image.clone();
+    other[50] ^= 1;
+    let mut flash = MemoryFlash::new(4);
+    let mut update = update();
+
+    update
+        .start(PARTITION, image.len() as u32, digest(&image))
+        .unwrap();
+    update.set_signature(signature(&image)).unwrap();
+    send(&mut update, &mut flash, &other);
+    assert_eq!(update.finish(&mut flash), Err(UpdateError::DigestMismatch));
+
+    update
+        .start(PARTITION, image.len() as u32, digest(&image))
+        .unwrap();
+    send(&mut update, &mut flash, &image);
+    assert_eq!(
+        update.finish(&mut flash),
+        Err(UpdateError::MissingSignature)
+    );
+
+    update
+        .start(PARTITION, other.len() as u32, digest(&other))
+        .unwrap();
+    update.set_signature(signature(&image)).unwrap();
+    send(&mut update, &mut flash, &other);
+    assert_eq!(
+        update.finish(&mut flash),
+        Err(UpdateError::InvalidSignature)
+    );
+}
@@ -0,0 +1,84 @@
+use magene_protocol::btsnoop::{RecordHeader, RECORD_HEADER_SIZE};
+use magene_protocol::download::{CaptureRecord, Frame, Frames, FRAME_HEADER_SIZE};
+use proptest::prelude::*;
+
+fn encode_frame(kind: u8, payload: &[u8]) -> Vec<u8> {
+    let mut frame = vec![kind];
+    frame.extend_from_slice(&(payload.len() as u16).to_le_bytes());
+    frame.extend_from_slice(payload);
+    frame
+}
+
+proptest! {
+    #[test]
+    fn frame_splitting_does_not_panic(data in prop::collection::vec(any::<u8>(), 0..128)) {
+        let mut frames = Frames::new(&data);
+        let mut consumed = 0;
+        for frame in frames.by_ref() {
+            consumed += FRAME_HEADER_SIZE + frame.payload.len();
+        }
+        prop_assert_eq!(consumed + frames.remainder().len(), data.len());
+    }
+
+    #[test]
+    fn frame_round_trip(
+        frames in prop::collection::vec((any::<u8>(), prop::collection::vec(any::<u8>(), 0..64)), 0..8),
+        partial in prop::collection::vec(any::<u8>(), 0..FRAME_HEADER_SIZE),
+    ) {
+        let mut data: Vec<u8> = frames
+            .iter()
+            .flat_map(|(kind, payload)| encode_frame(*kind, payload))
+            .collect();
+        // An incomplete header at the end stays in the remainder
+        data.extend_from_slice(&partial);
+
+        let mut split = Frames::new(&data);
+        let decoded: Vec<Frame> = split.by_ref().collect();
+        prop_assert_eq!(decoded.len(), frames.len());
+        for (frame, (kind, payload)) in decoded.iter().zip(frames.iter()) {
+            prop_assert_eq!(frame.kind, *kind);
+            prop_assert_eq!(frame.payload, &payload[..]);
+        }
+        prop_assert_eq!(split.remainder(), &partial[..]);
+    }
+
+    #[test]
+    fn capture_record_round_trip(
+        uptime_ms in any::<u32>(),
+        data in prop::collection::vec(any::<u8>(), 0..32),
+        size in 0usize..48,
+    ) {
+        let record = CaptureRecord { uptime_ms, data: &data };
+        let mut out = vec![0u8; size];
+        match record.encode(&mut out) {
+            Some(length) => {
+                prop_assert_eq!(length, 4 + data.len());
+                prop_assert_eq!(CaptureRecord::decode(&out[..length]), Some(record));
+            }
+            None => prop_assert!(size < 4 + data.len()),
+        }
+    }
+
+    #[test]
+    fn capture_record_decoding_does_not_panic(payload in prop::collection::vec(any::<u8>(), 0..16)) {
+        let record = CaptureRecord::decode(&payload);
+        prop_assert_eq!(record.is_some(), payload.len() >= 4);
+    }
+
+    #[test]
+    fn btsnoop_header_round_trip(
+        original_length in any::<u32>(),
+        included_length in any::<u32>(),
+        flags in any::<u32>(),
+        drops in any::<u32>(),
+        timestamp_us in any::<u64>(),
+    ) {
+        let header = RecordHeader { original_length, included_length, flags, drops, timestamp_us };
+        prop_assert_eq!(RecordHeader::decode(&header.encode()), header);
+    }
+
+    #[test]
+    fn btsnoop_header_decoding_is_lossless(bytes in any::<[u8; RECORD_HEADER_SIZE]>()) {
+        prop_assert_eq!(RecordHeader::decode(&bytes).encode(), bytes);
+    }
+}
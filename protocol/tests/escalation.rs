@@ -0,0 +1,104 @@
+use embassy_time::Duration;
+use magene_protocol::escalation::{time_to_contact, EscalationRules};
+use magene_protocol::radar::{RadarFrame, ThreatLevel};
+use proptest::prelude::*;
+
+mod common;
+use common::{any_frame, target};
+
+const RULES: EscalationRules = EscalationRules::DEFAULT;
+
+#[test]
+fn time_to_contact_of_closing_target() {
+    // 36 km/h is 10 m/s
+    assert_eq!(
+        time_to_contact(&target(50, 36, ThreatLevel::Low)),
+        Some(Duration::from_secs(5))
+    );
+    assert_eq!(
+        time_to_contact(&target(1, 255, ThreatLevel::Low)),
+        Some(Duration::from_millis(14))
+    );
+    assert_eq!(time_to_contact(&target(50, 0, ThreatLevel::Low)), None);
+}
+
+#[test]
+fn fast_overtake_is_escalated() {
+    let mut frame = RadarFrame {
+        online: true,
+        cycle: 3,
+        targets: [
+            // 2.5 s, 5.4 s and 9 s away
+            target(50, 72, ThreatLevel::Low),
+            target(60, 40, ThreatLevel::None),
+            target(100, 40, ThreatLevel::Low),
+        ]
+        .into_iter()
+        .collect(),
+        pages: Default::default(),
+    };
+    assert_eq!(RULES.apply(&mut frame), 2);
+    let threats: Vec<_> = frame.targets.iter().map(|t| t.threat).collect();
+    assert_eq!(
+        threats,
+        vec![ThreatLevel::High, ThreatLevel::Medium, ThreatLevel::Low]
+    );
+}
+
+#[test]
+fn threat_is_never_lowered() {
+    let mut frame = RadarFrame {
+        online: true,
+        cycle: 3,
+        targets: [target(120, 10, ThreatLevel::High)].into_iter().collect(),
+        pages: Default::default(),
+    };
+    assert_eq!(RULES.apply(&mut frame), 0);
+    assert_eq!(frame.targets[0].threat, ThreatLevel::High);
+}
+
+#[test]
+fn thresholds_are_inclusive() {
+    // 30 m at 36 km/h is exactly 3 s
+    assert_eq!(RULES.threat(Duration::from_secs(3)), ThreatLevel::High);
+    assert_eq!(
+        RULES.threat(Duration::from_millis(3001)),
+        ThreatLevel::Medium
+    );
+    assert_eq!(RULES.threat(Duration::from_secs(6)), ThreatLevel::Medium);
+    assert_eq!(RULES.threat(Duration::from_millis(6001)), ThreatLevel::None);
+}
+
+proptest! {
+    #[test]
+    fn escalation_only_raises_threats(frame in any_frame()) {
+        let mut escalated = frame.clone();
+        let count = RULES.apply(&mut escalated);
+
+        let mut raised = 0;
+        for (before, after) in frame.targets.iter().zip(&escalated.targets) {
+            prop_assert_eq!((before.id, before.range, before.speed), (after.id, after.range, after.speed));
+            prop_assert!(after.threat >= before.threat);
+            let expected = time_to_contact(before)
+                .map_or(before.threat, |time| before.threat.max(RULES.threat(time)));
+            prop_assert_eq!(after.threat, expected);
+            raised += (after.threat > before.threat) as usize;
+        }
+        prop_assert_eq!(count, raised);
+    }
+
+    #[test]
+    fn closer_and_faster_is_never_less_urgent(
+        range in 1u8..=255,
+        speed in 1u8..=255,
+        closer in 0u8..=254,
+        faster in 0u8..=254,
+    ) {
+        let time = time_to_contact(&target(range, speed, ThreatLevel::None)).unwrap();
+        let closer_range = range.saturating_sub(closer).max(1);
+        let faster_speed = speed.saturating_add(faster);
+        let sooner = time_to_contact(&target(closer_range, faster_speed, ThreatLevel::None)).unwrap();
+        prop_assert!(sooner <= time);
+        prop_assert!(RULES.threat(sooner) >= RULES.threat(time));
+    }
+}
@@ -0,0 +1,229 @@
+use std::collections::HashSet;
+
+use magene_protocol::filter::{FilterRules, TargetFilter};
+use magene_protocol::radar::{RadarTarget, ThreatLevel};
+use magene_protocol::tracker::{TrackEvent, Tracked, Tracker};
+use proptest::prelude::*;
+
+mod common;
+use common::{frame, numbered, radar_frame, ranges, target, CONFIG};
+
+const COMMUTE: FilterRules = FilterRules {
+    min_speed: 10,
+    max_range: 80,
+    speed_hysteresis: 3,
+    range_hysteresis: 10,
+    threat_map: [
+        ThreatLevel::None,
+        ThreatLevel::None,
+        ThreatLevel::Medium,
+        ThreatLevel::High,
+    ],
+};
+
+struct Pipeline {
+    tracker: Tracker,
+    filter: TargetFilter,
+}
+
+impl Pipeline {
+    fn new(rules: FilterRules) -> Self {
+        Self {
+            tracker: Tracker::new(CONFIG),
+            filter: TargetFilter::new(rules),
+        }
+    }
+
+    fn update(&mut self, targets: &[RadarTarget]) -> Tracked {
+        self.filter.apply(self.tracker.update(&frame(targets)))
+    }
+}
+
+// Events in a fixed order, the filter announces new vehicles closest first
+fn sorted(events: &[TrackEvent]) -> Vec<(u8, u8)> {
+    let mut events: Vec<_> = events
+        .iter()
+        .map(|event| match *event {
+            TrackEvent::NewVehicle(id) => (0, id),
+            TrackEvent::VehiclePassed(id) => (1, id),
+            TrackEvent::VehicleLost(id) => (2, id),
+        })
+        .collect();
+    events.sort();
+    events
+}
+
+#[test]
+fn slow_and_distant_targets_are_dropped() {
+    let mut pipeline = Pipeline::new(COMMUTE);
+    let tracked = pipeline.update(&[
+        target(30, 25, ThreatLevel::Medium),
+        target(40, 5, ThreatLevel::Low),
+        target(120, 40, ThreatLevel::Medium),
+    ]);
+    assert_eq!(ranges(&tracked), vec![30]);
+    assert_eq!(tracked.events.as_slice(), &[TrackEvent::NewVehicle(1)]);
+}
+
+#[test]
+fn threat_levels_are_remapped() {
+    let mut pipeline = Pipeline::new(COMMUTE);
+    let tracked = pipeline.update(&[
+        target(30, 25, ThreatLevel::Low),
+        target(50, 25, ThreatLevel::Medium),
+    ]);
+    let threats: Vec<_> = tracked.frame.targets.iter().map(|t| t.threat).collect();
+    assert_eq!(threats, vec![ThreatLevel::None, ThreatLevel::Medium]);
+}
+
+#[test]
+fn shown_vehicle_stays_within_the_hysteresis() {
+    let mut pipeline = Pipeline::new(COMMUTE);
+    assert_eq!(
+        ranges(&pipeline.update(&[target(78, 12, ThreatLevel::Low)])),
+        vec![78]
+    );
+    // Slower and further than the limits, but not by more than the hysteresis
+    assert_eq!(
+        ranges(&pipeline.update(&[target(84, 8, ThreatLevel::Low)])),
+        vec![84]
+    );
+    assert_eq!(
+        ranges(&pipeline.update(&[target(86, 7, ThreatLevel::Low)])),
+        vec![86]
+    );
+    // Beyond it the vehicle is hidden, and it has to meet the limits to come back
+    assert!(pipeline
+        .update(&[target(86, 6, ThreatLevel::Low)])
+        .frame
+        .is_empty());
+    assert!(pipeline
+        .update(&[target(82, 9, ThreatLevel::Low)])
+        .frame
+        .is_empty());
+    let tracked = pipeline.update(&[target(79, 10, ThreatLevel::Low)]);
+    assert_eq!(ranges(&tracked), vec![79]);
+    assert_eq!(tracked.events.as_slice(), &[TrackEvent::NewVehicle(1)]);
+}
+
+#[test]
+fn vehicle_is_new_once_it_is_shown() {
+    let mut pipeline = Pipeline::new(COMMUTE);
+    let tracked = pipeline.update(&[target(100, 30, ThreatLevel::Low)]);
+    assert!(tracked.frame.is_empty());
+    assert!(tracked.events.is_empty());
+    let tracked = pipeline.update(&[target(92, 30, ThreatLevel::Low)]);
+    assert!(tracked.events.is_empty());
+    let tracked = pipeline.update(&[target(84, 30, ThreatLevel::Low)]);
+    assert!(tracked.events.is_empty());
+    let tracked = pipeline.update(&[target(76, 30, ThreatLevel::Low)]);
+    assert_eq!(tracked.events.as_slice(), &[TrackEvent::NewVehicle(1)]);
+}
+
+#[test]
+fn only_shown_vehicles_pass() {
+    let mut pipeline = Pipeline::new(COMMUTE);
+    pipeline.update(&[
+        target(20, 25, ThreatLevel::Medium),
+        target(24, 4, ThreatLevel::Low),
+    ]);
+    pipeline.update(&[
+        target(12, 25, ThreatLevel::Medium),
+        target(23, 4, ThreatLevel::Low),
+    ]);
+    let events: Vec<_> = (0..=CONFIG.coast_frames)
+        .flat_map(|_| pipeline.update(&[]).events)
+        .collect();
+    assert_eq!(events, vec![TrackEvent::VehiclePassed(1)]);
+}
+
+#[test]
+fn missing_vehicle_is_not_announced_again() {
+    let mut pipeline = Pipeline::new(COMMUTE);
+    pipeline.update(&[target(60, 30, ThreatLevel::Low)]);
+    assert!(pipeline.update(&[]).events.is_empty());
+    let tracked = pipeline.update(&[target(52, 30, ThreatLevel::Low)]);
+    assert_eq!(ranges(&tracked), vec![52]);
+    assert!(tracked.events.is_empty());
+}
+
+fn rules() -> impl Strategy<Value = FilterRules> {
+    let threat = (0u8..4).prop_map(ThreatLevel::from);
+    (
+        any::<u8>(),
+        any::<u8>(),
+        0u8..10,
+        0u8..20,
+        [threat.clone(), threat.clone(), threat.clone(), threat],
+    )
+        .prop_map(
+            |(min_speed, max_range, speed_hysteresis, range_hysteresis, threat_map)| FilterRules {
+                min_speed,
+                max_range,
+                speed_hysteresis,
+                range_hysteresis,
+                threat_map,
+            },
+        )
+}
+
+proptest! {
+    #[test]
+    fn no_rules_change_nothing(frames in prop::collection::vec(radar_frame(), 0..32)) {
+        let mut tracker = Tracker::new(CONFIG);
+        let mut filter = TargetFilter::new(FilterRules::NONE);
+        for frame in numbered(frames) {
+            let tracked = tracker.update(&frame);
+            let filtered = filter.apply(tracked.clone());
+            prop_assert_eq!(&filtered.frame, &tracked.frame);
+            prop_assert_eq!(sorted(&filtered.events), sorted(&tracked.events));
+        }
+    }
+
+    #[test]
+    fn filter_invariants(frames in prop::collection::vec(radar_frame(), 0..32), rules in rules()) {
+        let mut tracker = Tracker::new(CONFIG);
+        let mut filter = TargetFilter::new(rules);
+        let mut shown = HashSet::new();
+
+        for frame in numbered(frames) {
+            let tracked = tracker.update(&frame);
+            let filtered = filter.apply(tracked.clone());
+            prop_assert_eq!(filtered.frame.online, tracked.frame.online);
+            if !frame.online {
+                shown.clear();
+            }
+
+            // What is shown is what the radar saw, within the rules and the hysteresis
+            let mut tracked_targets = tracked.frame.targets.iter();
+            for target in &filtered.frame.targets {
+                let original = tracked_targets.find(|t| t.id == target.id && t.range == target.range);
+                prop_assert!(original.is_some());
+                let original = original.unwrap();
+                prop_assert_eq!(target.threat, rules.threat_map[original.threat as usize]);
+                prop_assert!(target.speed >= rules.min_speed.saturating_sub(rules.speed_hysteresis));
+                prop_assert!(target.range <= rules.max_range.saturating_add(rules.range_hysteresis));
+            }
+
+            // Vehicles are announced when they are shown and end only if they were
+            for event in &filtered.events {
+                match *event {
+                    TrackEvent::NewVehicle(id) => prop_assert!(shown.insert(id)),
+                    TrackEvent::VehiclePassed(id) | TrackEvent::VehicleLost(id) => {
+                        prop_assert!(shown.remove(&id))
+                    }
+                }
+            }
+            // Targets the tracker had no room for are shown without an id
+            for id in filtered.frame.targets.iter().filter_map(|target| target.id) {
+                prop_assert!(shown.contains(&id));
+            }
+            // A vehicle that is hidden again is announced again when it comes back
+            for id in tracked.frame.targets.iter().filter_map(|target| target.id) {
+                if !filtered.frame.targets.iter().any(|target| target.id == Some(id)) {
+                    shown.remove(&id);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,148 @@
+mod common;
+
+use common::{MemoryFlash, SECTOR_SIZE};
+use magene_protocol::flash_ring::{FlashRing, Partition, SEQUENCE_SIZE};
+use proptest::prelude::*;
+
+const RECORD_SIZE: usize = 64;
+const PAYLOAD_SIZE: usize = FlashRing::<RECORD_SIZE>::PAYLOAD_SIZE;
+const RECORDS_PER_SECTOR: u32 = (SECTOR_SIZE / RECORD_SIZE) as u32;
+// Two sectors after a sector that belongs to something else
+const PARTITION: Partition = Partition {
+    offset: SECTOR_SIZE as u32,
+    size: 2 * SECTOR_SIZE as u32,
+};
+
+fn payload(value: u32) -> [u8; PAYLOAD_SIZE] {
+    let mut payload = [0u8; PAYLOAD_SIZE];
+    payload[..4].copy_from_slice(&value.to_le_bytes());
+    payload[PAYLOAD_SIZE - 1] = value as u8;
+    payload
+}
+
+fn read(ring: &FlashRing<RECORD_SIZE>, flash: &mut MemoryFlash, age: u32) -> Option<u32> {
+    let mut data = [0u8; PAYLOAD_SIZE];
+    match ring.read(flash, age, &mut data).unwrap() {
+        true => {
+            let value = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+            assert_eq!(data, payload(value));
+            Some(value)
+        }
+        false => None,
+    }
+}
+
+fn ring_with(flash: &mut MemoryFlash, count: u32) -> FlashRing<RECORD_SIZE> {
+    let mut ring = FlashRing::open(flash, PARTITION).unwrap();
+    for value in 0..count {
+        ring.append(flash, &payload(value)).unwrap();
+    }
+    ring
+}
+
+#[test]
+fn empty_partition_opens_empty() {
+    let mut flash = MemoryFlash::new(3);
+    let ring = FlashRing::<RECORD_SIZE>::open(&mut flash, PARTITION).unwrap();
+    assert!(ring.is_empty());
+    assert_eq!(ring.capacity(), 2 * RECORDS_PER_SECTOR);
+    assert_eq!(read(&ring, &mut flash, 0), None);
+}
+
+#[test]
+fn newest_record_is_read_first() {
+    let mut flash = MemoryFlash::new(3);
+    let ring = ring_with(&mut flash, 3);
+    assert_eq!(ring.len(), 3);
+    assert_eq!(read(&ring, &mut flash, 0), Some(2));
+    assert_eq!(read(&ring, &mut flash, 2), Some(0));
+    assert_eq!(read(&ring, &mut flash, 3), None);
+}
+
+#[test]
+fn records_stay_inside_the_partition() {
+    let mut flash = MemoryFlash::new(3);
+    ring_with(&mut flash, 3 * RECORDS_PER_SECTOR);
+    assert!(flash.data[..SECTOR_SIZE].iter().all(|&byte| byte == 0xFF));
+}
+
+#[test]
+fn reopened_ring_continues_after_the_newest_record() {
+    let mut flash = MemoryFlash::new(3);
+    ring_with(&mut flash, 5);
+
+    let mut ring = FlashRing::<RECORD_SIZE>::open(&mut flash, PARTITION).unwrap();
+    assert_eq!(ring.len(), 5);
+    assert_eq!(read(&ring, &mut flash, 0), Some(4));
+    ring.append(&mut flash, &payload(5)).unwrap();
+    assert_eq!(read(&ring, &mut flash, 0), Some(5));
+    assert_eq!(read(&ring, &mut flash, 5), Some(0));
+}
+
+#[test]
+fn wrap_erases_the_oldest_sector() {
+    let mut flash = MemoryFlash::new(3);
+    let capacity = 2 * RECORDS_PER_SECTOR;
+    let ring = ring_with(&mut flash, capacity + 2);
+
+    // The first sector was erased again for the two newest records
+    assert_eq!(flash.erases, 3);
+    assert_eq!(ring.len(), RECORDS_PER_SECTOR + 2);
+    assert_eq!(read(&ring, &mut flash, 0), Some(capacity + 1));
+    assert_eq!(
+        read(&ring, &mut flash, RECORDS_PER_SECTOR + 1),
+        Some(RECORDS_PER_SECTOR)
+    );
+    assert_eq!(read(&ring, &mut flash, RECORDS_PER_SECTOR + 2), None);
+
+    let reopened = FlashRing::<RECORD_SIZE>::open(&mut flash, PARTITION).unwrap();
+    assert_eq!(reopened.len(), ring.len());
+    assert_eq!(read(&reopened, &mut flash, 0), Some(capacity + 1));
+}
+
+#[test]
+fn long_payload_is_cut_and_short_one_padded() {
+    let mut flash = MemoryFlash::new(3);
+    let mut ring = FlashRing::<RECORD_SIZE>::open(&mut flash, PARTITION).unwrap();
+    ring.append(&mut flash, &[0xAB; RECORD_SIZE]).unwrap();
+    ring.append(&mut flash, &[1, 2]).unwrap();
+
+    let mut data = [0u8; PAYLOAD_SIZE];
+    assert!(ring.read(&mut flash, 1, &mut data).unwrap());
+    assert_eq!(data, [0xAB; PAYLOAD_SIZE]);
+    assert!(ring.read(&mut flash, 0, &mut data).unwrap());
+    assert_eq!(data[..3], [1, 2, 0xFF]);
+    let offset = PARTITION.offset as usize + RECORD_SIZE;
+    assert_eq!(
+        flash.data[offset..offset + SEQUENCE_SIZE],
+        1u32.to_le_bytes()
+    );
+}
+
+#[test]
+fn record_from_a_lost_write_is_not_returned() {
+    let mut flash = MemoryFlash::new(3);
+    let ring = ring_with(&mut flash, 2);
+    // Sequence number of the newest record cleared, e.g. by a torn write
+    let offset = PARTITION.offset as usize + RECORD_SIZE;
+    flash.data[offset..offset + SEQUENCE_SIZE].fill(0);
+    assert_eq!(read(&ring, &mut flash, 0), None);
+    assert_eq!(read(&ring, &mut flash, 1), Some(0));
+}
+
+proptest! {
+    #[test]
+    fn newest_records_survive_any_number_of_appends(count in 0u32..400) {
+        let mut flash = MemoryFlash::new(3);
+        let ring = ring_with(&mut flash, count);
+        prop_assert!(ring.len() <= ring.capacity());
+        prop_assert!(ring.len() >= count.min(RECORDS_PER_SECTOR));
+        for age in 0..ring.len() {
+            prop_assert_eq!(read(&ring, &mut flash, age), Some(count - 1 - age));
+        }
+
+        let reopened = FlashRing::<RECORD_SIZE>::open(&mut flash, PARTITION).unwrap();
+        prop_assert_eq!(reopened.len(), ring.len());
+        prop_assert_eq!(read(&reopened, &mut flash, 0), count.checked_sub(1));
+    }
+}
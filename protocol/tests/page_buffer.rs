@@ -0,0 +1,349 @@
+use embassy_time::Duration;
+use magene_protocol::clock::Clock;
+use magene_protocol::magene::{Page, PAGE_1, PAGE_2, PAGE_LAST};
+use magene_protocol::page_buffer::{AssemblyCounters, PageBuffer, Update};
+use magene_protocol::radar::{RadarFrame, MAX_TARGETS, PAGE_SIZE};
+use proptest::prelude::*;
+
+mod common;
+use common::TestClock;
+
+const HOLD_MS: u64 = 20;
+const HOLD: Duration = Duration::from_millis(HOLD_MS);
+const TIMEOUT_MS: u64 = 100;
+const TIMEOUT: Duration = Duration::from_millis(TIMEOUT_MS);
+const PAGE1: [u8; PAGE_SIZE] = [PAGE_1, 7, 20, 30, 2, 0, 0, 0];
+const PAGE2: [u8; PAGE_SIZE] = [PAGE_2, 7, 45, 12, 1, 0, 0, 0];
+const PAGE3: [u8; PAGE_SIZE] = [PAGE_2 + 1, 7, 80, 25, 1, 90, 20, 1];
+
+fn buffer(clock: &TestClock) -> PageBuffer<&TestClock> {
+    PageBuffer::with_clock(HOLD, TIMEOUT, clock)
+}
+
+fn page(data: [u8; PAGE_SIZE]) -> Page {
+    Page::new(data).expect("radar page")
+}
+
+fn frame(pages: &[[u8; PAGE_SIZE]]) -> RadarFrame {
+    RadarFrame::from_pages(pages)
+}
+
+fn with_cycle(mut page: [u8; PAGE_SIZE], cycle: u8) -> [u8; PAGE_SIZE] {
+    page[1] = cycle;
+    page
+}
+
+// Runs the timer like the central does: waits for the next expiry and polls
+fn fire(buffer: &mut PageBuffer<&TestClock>, clock: &TestClock) -> Option<Update> {
+    let expiry = buffer.next_expiry().expect("timer is running");
+    clock.set(clock.now().max(expiry));
+    buffer.poll()
+}
+
+#[test]
+fn empty_buffer_has_no_expiry() {
+    let clock = TestClock::new();
+    let mut buffer = buffer(&clock);
+    assert_eq!(buffer.next_expiry(), None);
+    assert_eq!(buffer.poll(), None);
+}
+
+#[test]
+fn pair_of_one_cycle_is_forwarded_at_once() {
+    let clock = TestClock::new();
+    let mut buffer = buffer(&clock);
+    assert_eq!(buffer.push(page(PAGE1)), None);
+    clock.advance(Duration::from_millis(5));
+    assert_eq!(buffer.push(page(PAGE2)), Some(frame(&[PAGE1, PAGE2])));
+    assert_eq!(
+        buffer.counters(),
+        AssemblyCounters {
+            complete: 1,
+            ..Default::default()
+        }
+    );
+}
+
+#[test]
+fn reversed_pair_is_forwarded_and_counted() {
+    let clock = TestClock::new();
+    let mut buffer = buffer(&clock);
+    assert_eq!(buffer.push(page(PAGE2)), None);
+    assert_eq!(buffer.push(page(PAGE1)), Some(frame(&[PAGE1, PAGE2])));
+    assert_eq!(buffer.counters().complete, 1);
+    assert_eq!(buffer.counters().out_of_order, 1);
+}
+
+#[test]
+fn only_page_1_goes_out_after_the_hold() {
+    let clock = TestClock::new();
+    let mut buffer = buffer(&clock);
+    let start = clock.now();
+    buffer.push(page(PAGE1));
+    assert_eq!(buffer.next_expiry(), Some(start + HOLD));
+
+    clock.advance(HOLD - Duration::from_millis(1));
+    assert_eq!(buffer.poll(), None);
+    clock.advance(Duration::from_millis(1));
+    assert_eq!(buffer.poll(), Some(Update::Frame(frame(&[PAGE1]))));
+    assert_eq!(buffer.counters().partial, 1);
+}
+
+#[test]
+fn only_page_2_goes_out_after_the_hold() {
+    let clock = TestClock::new();
+    let mut buffer = buffer(&clock);
+    buffer.push(page(PAGE2));
+    assert_eq!(
+        fire(&mut buffer, &clock),
+        Some(Update::Frame(frame(&[PAGE2])))
+    );
+}
+
+#[test]
+fn partial_frame_reuses_fresh_page_of_previous_cycle() {
+    let clock = TestClock::new();
+    let mut buffer = buffer(&clock);
+    buffer.push(page(PAGE1));
+    buffer.push(page(PAGE2));
+
+    clock.advance(Duration::from_millis(50));
+    let next = with_cycle(PAGE1, 8);
+    buffer.push(page(next));
+    // The frame belongs to the new cycle, whichever page stands in
+    let mut expected = frame(&[next, PAGE2]);
+    expected.cycle = 8;
+    assert_eq!(fire(&mut buffer, &clock), Some(Update::Frame(expected)));
+}
+
+#[test]
+fn partial_frame_does_not_reuse_expired_page() {
+    let clock = TestClock::new();
+    let mut buffer = buffer(&clock);
+    buffer.push(page(PAGE1));
+    buffer.push(page(PAGE2));
+
+    clock.advance(TIMEOUT - HOLD);
+    let next = with_cycle(PAGE1, 8);
+    buffer.push(page(next));
+    assert_eq!(
+        fire(&mut buffer, &clock),
+        Some(Update::Frame(frame(&[next])))
+    );
+}
+
+#[test]
+fn page_of_another_cycle_is_a_mismatch() {
+    let clock = TestClock::new();
+    let mut buffer = buffer(&clock);
+    buffer.push(page(PAGE1));
+    let other = with_cycle(PAGE2, 8);
+    // The waiting page goes out on its own, the new one waits for its partner
+    assert_eq!(buffer.push(page(other)), Some(frame(&[PAGE1])));
+    let partner = with_cycle(PAGE1, 8);
+    assert_eq!(buffer.push(page(partner)), Some(frame(&[partner, other])));
+    assert_eq!(
+        buffer.counters(),
+        AssemblyCounters {
+            complete: 1,
+            partial: 1,
+            mismatched: 1,
+            out_of_order: 1,
+        }
+    );
+}
+
+#[test]
+fn repeated_page_type_is_a_mismatch() {
+    let clock = TestClock::new();
+    let mut buffer = buffer(&clock);
+    buffer.push(page(PAGE1));
+    let next = with_cycle(PAGE1, 8);
+    assert_eq!(buffer.push(page(next)), Some(frame(&[PAGE1])));
+    assert_eq!(buffer.counters().mismatched, 1);
+}
+
+#[test]
+fn zero_hold_forwards_every_page() {
+    let clock = TestClock::new();
+    let mut buffer = PageBuffer::with_clock(Duration::from_ticks(0), TIMEOUT, &clock);
+    assert_eq!(buffer.push(page(PAGE1)), Some(frame(&[PAGE1])));
+    assert_eq!(buffer.push(page(PAGE2)), Some(frame(&[PAGE1, PAGE2])));
+    assert_eq!(buffer.counters().partial, 1);
+}
+
+#[test]
+fn further_page_updates_the_forwarded_frame() {
+    let clock = TestClock::new();
+    let mut buffer = buffer(&clock);
+    buffer.push(page(PAGE1));
+    assert_eq!(buffer.push(page(PAGE2)), Some(frame(&[PAGE1, PAGE2])));
+    assert_eq!(
+        buffer.push(page(PAGE3)),
+        Some(frame(&[PAGE1, PAGE2, PAGE3]))
+    );
+    assert_eq!(buffer.counters().complete, 1);
+    assert_eq!(buffer.next_expiry(), Some(clock.now() + TIMEOUT));
+}
+
+#[test]
+fn further_page_waits_for_the_base_pages() {
+    let clock = TestClock::new();
+    let mut buffer = buffer(&clock);
+    assert_eq!(buffer.push(page(PAGE3)), None);
+    assert_eq!(buffer.push(page(PAGE1)), None);
+    assert_eq!(
+        buffer.push(page(PAGE2)),
+        Some(frame(&[PAGE1, PAGE2, PAGE3]))
+    );
+    assert_eq!(
+        buffer.counters(),
+        AssemblyCounters {
+            complete: 1,
+            out_of_order: 2,
+            ..Default::default()
+        }
+    );
+}
+
+#[test]
+fn late_base_page_updates_the_partial_frame() {
+    let clock = TestClock::new();
+    let mut buffer = buffer(&clock);
+    buffer.push(page(PAGE1));
+    assert_eq!(
+        fire(&mut buffer, &clock),
+        Some(Update::Frame(frame(&[PAGE1])))
+    );
+    assert_eq!(buffer.push(page(PAGE2)), Some(frame(&[PAGE1, PAGE2])));
+    assert_eq!(buffer.counters().partial, 1);
+    assert_eq!(buffer.counters().complete, 0);
+}
+
+#[test]
+fn silence_expires_once() {
+    let clock = TestClock::new();
+    let mut buffer = buffer(&clock);
+    let start = clock.now();
+    buffer.push(page(PAGE1));
+    buffer.push(page(PAGE2));
+    assert_eq!(buffer.next_expiry(), Some(start + TIMEOUT));
+
+    clock.advance(TIMEOUT - Duration::from_millis(1));
+    assert_eq!(buffer.poll(), None);
+    clock.advance(Duration::from_millis(1));
+    assert_eq!(buffer.poll(), Some(Update::Expired));
+    assert_eq!(buffer.next_expiry(), None);
+    assert_eq!(buffer.poll(), None);
+}
+
+#[test]
+fn expiry_follows_the_oldest_page() {
+    let clock = TestClock::new();
+    let mut buffer = buffer(&clock);
+    let start = clock.now();
+    buffer.push(page(PAGE1));
+    buffer.push(page(PAGE2));
+    clock.advance(Duration::from_millis(30));
+    let next = with_cycle(PAGE2, 8);
+    buffer.push(page(next));
+    assert_eq!(
+        fire(&mut buffer, &clock),
+        Some(Update::Frame(frame(&[PAGE1, next])))
+    );
+
+    // Page 1 is older and expires first, without silencing the radar
+    assert_eq!(buffer.next_expiry(), Some(start + TIMEOUT));
+    assert_eq!(fire(&mut buffer, &clock), None);
+    assert_eq!(
+        buffer.next_expiry(),
+        Some(start + Duration::from_millis(30) + TIMEOUT)
+    );
+    assert_eq!(fire(&mut buffer, &clock), Some(Update::Expired));
+}
+
+#[test]
+fn cleanup_forgets_pages_but_keeps_counters() {
+    let clock = TestClock::new();
+    let mut buffer = buffer(&clock);
+    buffer.push(page(PAGE1));
+    buffer.push(page(PAGE2));
+    buffer.push(page(with_cycle(PAGE1, 8)));
+    buffer.cleanup();
+    assert_eq!(buffer.next_expiry(), None);
+    assert_eq!(buffer.poll(), None);
+    assert_eq!(buffer.counters().complete, 1);
+}
+
+#[derive(Debug, Clone)]
+enum Operation {
+    Push(Page),
+    Advance(u64),
+    Fire,
+    Cleanup,
+}
+
+fn operation() -> impl Strategy<Value = Operation> {
+    // Few cycles, so complete cycles and mismatches both happen
+    let page = (PAGE_1..=PAGE_LAST, 0u8..3, any::<[u8; PAGE_SIZE]>()).prop_map(
+        |(kind, cycle, mut data)| {
+            data[0] = kind;
+            data[1] = cycle;
+            page(data)
+        },
+    );
+    prop_oneof![
+        4 => page.prop_map(Operation::Push),
+        // Stepping by exactly the hold or the timeout checks the boundaries
+        2 => prop_oneof![0..2 * TIMEOUT_MS, Just(HOLD_MS), Just(TIMEOUT_MS)].prop_map(Operation::Advance),
+        1 => Just(Operation::Fire),
+        1 => Just(Operation::Cleanup),
+    ]
+}
+
+fn check_frame(frame: &RadarFrame) -> Result<(), TestCaseError> {
+    prop_assert!(frame.targets.len() <= MAX_TARGETS);
+    prop_assert!(frame.online || frame.targets.is_empty());
+    Ok(())
+}
+
+proptest! {
+    #[test]
+    fn assembly_invariants(operations in prop::collection::vec(operation(), 0..64)) {
+        let clock = TestClock::new();
+        let mut buffer = buffer(&clock);
+        let mut frames = 0;
+
+        for operation in operations {
+            match operation {
+                Operation::Push(page) => {
+                    if let Some(frame) = buffer.push(page) {
+                        check_frame(&frame)?;
+                        frames += 1;
+                    }
+                }
+                Operation::Advance(ms) => clock.advance(Duration::from_millis(ms)),
+                Operation::Fire => {
+                    if buffer.next_expiry().is_none() {
+                        continue;
+                    }
+                    match fire(&mut buffer, &clock) {
+                        Some(Update::Frame(frame)) => {
+                            check_frame(&frame)?;
+                            frames += 1;
+                        }
+                        Some(Update::Expired) => prop_assert_eq!(buffer.next_expiry(), None),
+                        // Nothing to forward means the timer moved into the future
+                        None => prop_assert!(buffer.next_expiry().is_none_or(|expiry| expiry > clock.now())),
+                    }
+                }
+                Operation::Cleanup => buffer.cleanup(),
+            }
+            // Every cycle goes out once as complete or partial, further pages
+            // of a cycle that went out update its frame
+            let counters = buffer.counters();
+            prop_assert!(counters.complete + counters.partial <= frames);
+            prop_assert!(counters.mismatched <= counters.partial);
+        }
+    }
+}
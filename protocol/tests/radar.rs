@@ -0,0 +1,220 @@
+use std::cmp::Reverse;
+
+use magene_protocol::bryton::{self, CAPACITY, FRAME_SIZE};
+use magene_protocol::magene::{
+    self, DecodeError, Page, HEADER_SIZE, NOTIFICATION_SIZE, PAGE_1, PAGE_2, PAGE_LAST,
+};
+use magene_protocol::radar::{
+    RadarFrame, RadarPage, RadarTarget, ThreatLevel, MAX_PAGES, MAX_TARGETS, PAGE_SIZE,
+    PAGE_STATUS_OFFLINE, TARGETS_PER_PAGE,
+};
+use proptest::prelude::*;
+
+fn page(kind: u8) -> impl Strategy<Value = [u8; PAGE_SIZE]> {
+    any::<[u8; PAGE_SIZE]>().prop_map(move |mut page| {
+        page[0] = kind;
+        page
+    })
+}
+
+fn target() -> impl Strategy<Value = RadarTarget> {
+    (1u8..=255, any::<u8>(), 0u8..4).prop_map(|(range, speed, threat)| RadarTarget {
+        id: None,
+        range,
+        speed,
+        threat: ThreatLevel::from(threat),
+    })
+}
+
+fn frame(targets: usize) -> impl Strategy<Value = RadarFrame> {
+    (
+        0u8..PAGE_STATUS_OFFLINE,
+        prop::collection::vec(target(), 0..=targets),
+    )
+        .prop_map(|(cycle, targets)| RadarFrame {
+            online: true,
+            cycle,
+            targets: targets.into_iter().collect(),
+            pages: Default::default(),
+        })
+}
+
+fn rank(target: &RadarTarget) -> (Reverse<ThreatLevel>, u8) {
+    (Reverse(target.threat), target.range)
+}
+
+#[test]
+fn further_page_types_are_decoded() {
+    let mut notification = [0u8; NOTIFICATION_SIZE];
+    notification[HEADER_SIZE..].copy_from_slice(&[PAGE_2 + 1, 4, 80, 40, 3, 0, 0, 0]);
+    let page = magene::decode_notification(&notification).unwrap();
+    assert_eq!(page.index(), 2);
+
+    notification[HEADER_SIZE] = PAGE_LAST + 1;
+    assert_eq!(
+        magene::decode_notification(&notification),
+        Err(DecodeError::UnknownPageType(PAGE_LAST + 1))
+    );
+}
+
+#[test]
+fn further_pages_carry_further_targets() {
+    let pages = [
+        [PAGE_1, 4, 20, 30, 2, 35, 20, 1],
+        [PAGE_2, 4, 50, 25, 1, 60, 10, 1],
+        [PAGE_2 + 1, 4, 80, 40, 3, 0, 0, 0],
+    ];
+    let frame = RadarFrame::from_pages(&pages);
+    assert!(frame.online);
+    assert_eq!(frame.cycle, 4);
+    assert_eq!(
+        frame
+            .targets
+            .iter()
+            .map(|target| target.range)
+            .collect::<Vec<_>>(),
+        vec![20, 35, 50, 60, 80]
+    );
+
+    // The encoder keeps the high threat at 80 m and the three closest of the rest
+    let encoded = RadarFrame::decode(&bryton::encode(&frame));
+    assert_eq!(
+        encoded
+            .targets
+            .iter()
+            .map(|target| target.range)
+            .collect::<Vec<_>>(),
+        vec![20, 35, 50, 80]
+    );
+}
+
+#[test]
+fn radar_pages_are_forwarded_as_they_are() {
+    // A threat level the proxy does not know and a cleared slot that is not zeroed
+    let pages = [
+        [PAGE_1, 4, 20, 30, 7, 0, 9, 9],
+        [PAGE_2, 4, 50, 25, 1, 60, 10, 1],
+    ];
+    let frame = RadarFrame::from_pages(&pages);
+    assert_eq!(frame.targets.len(), 3);
+    assert_eq!(bryton::encode(&frame), *pages.as_flattened());
+}
+
+#[test]
+fn changed_targets_are_encoded_anew() {
+    let pages = [
+        [PAGE_1, 4, 20, 30, 2, 0, 9, 9],
+        [PAGE_2, 4, 50, 25, 1, 60, 10, 1],
+    ];
+    let mut frame = RadarFrame::from_pages(&pages);
+    frame.targets[0].threat = ThreatLevel::High;
+    frame.targets.pop();
+
+    let encoded = bryton::encode(&frame);
+    assert_eq!(
+        encoded,
+        [PAGE_1, 4, 20, 30, 3, 50, 25, 1, PAGE_2, 4, 0, 0, 0, 0, 0, 0]
+    );
+}
+
+#[test]
+fn offline_frame_is_encoded_as_offline_pages() {
+    let encoded = bryton::encode(&RadarFrame::offline());
+    assert_eq!(encoded[0], PAGE_1);
+    assert_eq!(encoded[1], PAGE_STATUS_OFFLINE);
+    assert_eq!(encoded[PAGE_SIZE], PAGE_1 + 1);
+    assert_eq!(encoded[PAGE_SIZE + 1], PAGE_STATUS_OFFLINE);
+    assert_eq!(RadarFrame::decode(&encoded), RadarFrame::offline());
+}
+
+proptest! {
+    #[test]
+    fn notification_decoding_does_not_panic(data in prop::collection::vec(any::<u8>(), 0..32)) {
+        match magene::decode_notification(&data) {
+            Ok(page) => {
+                prop_assert_eq!(data.len(), NOTIFICATION_SIZE);
+                prop_assert_eq!(&page.data()[..], &data[HEADER_SIZE..]);
+                prop_assert!(page.index() < MAX_PAGES);
+            }
+            Err(DecodeError::WrongLength(length)) => {
+                prop_assert_ne!(length, NOTIFICATION_SIZE);
+                prop_assert_eq!(length, data.len());
+            }
+            Err(DecodeError::UnknownPageType(kind)) => {
+                prop_assert!(!(PAGE_1..=PAGE_LAST).contains(&kind));
+            }
+        }
+    }
+
+    #[test]
+    fn notification_round_trip(
+        header in any::<[u8; HEADER_SIZE]>(),
+        page in (PAGE_1..=PAGE_LAST).prop_flat_map(page),
+    ) {
+        let mut notification = header.to_vec();
+        notification.extend_from_slice(&page);
+        let decoded = magene::decode_notification(&notification);
+        prop_assert_eq!(decoded, Page::new(page));
+        let decoded = decoded.unwrap();
+        prop_assert_eq!(decoded.index(), (page[0] - PAGE_1) as usize);
+        prop_assert_eq!(decoded.cycle(), page[1]);
+    }
+
+    #[test]
+    fn page_decoding(page in any::<[u8; PAGE_SIZE]>()) {
+        let decoded = RadarPage::decode(&page);
+        prop_assert_eq!(decoded.kind, page[0]);
+        prop_assert_eq!(decoded.online, page[1] != PAGE_STATUS_OFFLINE);
+        prop_assert!(decoded.targets.len() <= TARGETS_PER_PAGE);
+        prop_assert!(decoded.online || decoded.targets.is_empty());
+        prop_assert!(decoded.targets.iter().all(|target| target.range != 0));
+    }
+
+    #[test]
+    fn frame_decoding(data in prop::collection::vec(any::<u8>(), 0..(MAX_PAGES + 1) * PAGE_SIZE)) {
+        let decoded = RadarFrame::decode(&data);
+        prop_assert!(decoded.targets.len() <= MAX_TARGETS);
+        prop_assert!(decoded.online || decoded.targets.is_empty());
+        prop_assert!(decoded.online || decoded.cycle == PAGE_STATUS_OFFLINE);
+        prop_assert!(decoded.targets.iter().all(|target| target.range != 0));
+        if let Some(closest) = decoded.closest() {
+            prop_assert!(decoded.targets.iter().all(|target| target.range >= closest.range));
+        }
+    }
+
+    #[test]
+    fn frame_encoding_round_trip(frame in frame(CAPACITY)) {
+        let encoded = bryton::encode(&frame);
+        prop_assert_eq!(encoded[0], PAGE_1);
+        prop_assert_eq!(encoded[PAGE_SIZE], PAGE_1 + 1);
+        let decoded = RadarFrame::decode(&encoded);
+        prop_assert_eq!((decoded.online, decoded.cycle), (frame.online, frame.cycle));
+        prop_assert_eq!(decoded.targets, frame.targets);
+    }
+
+    #[test]
+    fn frame_encoding_keeps_the_most_important_targets(frame in frame(MAX_TARGETS)) {
+        let encoded = RadarFrame::decode(&bryton::encode(&frame));
+        prop_assert_eq!(encoded.targets, frame.prioritized(CAPACITY));
+        prop_assert_eq!(FRAME_SIZE / PAGE_SIZE * TARGETS_PER_PAGE, CAPACITY);
+    }
+
+    #[test]
+    fn prioritized_targets(frame in frame(MAX_TARGETS), capacity in 0..=MAX_TARGETS) {
+        let kept = frame.prioritized(capacity);
+        prop_assert_eq!(kept.len(), frame.targets.len().min(capacity));
+
+        // The kept targets are in the order of the frame
+        let mut targets = frame.targets.iter();
+        prop_assert!(kept.iter().all(|kept| targets.any(|target| target == kept)));
+
+        // and none of the dropped ones ranks higher
+        let lowest = kept.iter().map(rank).max();
+        let mut dropped = frame.targets.clone();
+        for kept in &kept {
+            let position = dropped.iter().position(|target| target == kept).unwrap();
+            dropped.remove(position);
+        }
+        prop_assert!(dropped.iter().all(|target| Some(rank(target)) >= lowest));
+    }
+}
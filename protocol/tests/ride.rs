@@ -0,0 +1,219 @@
+use embassy_time::Instant;
+use heapless::Vec as HVec;
+use magene_protocol::radar::{RadarFrame, RadarTarget, ThreatLevel};
+use magene_protocol::ride::{RideRecorder, RideStats, RIDE_STATS_SIZE};
+use magene_protocol::tracker::{TrackEvent, Tracked, Tracker};
+use proptest::prelude::*;
+
+mod common;
+use common::{numbered, radar_frame, CONFIG};
+
+fn at(ms: u64) -> Instant {
+    Instant::from_millis(ms)
+}
+
+fn tracked(targets: &[(u8, u8, u8, ThreatLevel)], events: &[TrackEvent]) -> Tracked {
+    Tracked {
+        frame: RadarFrame {
+            online: true,
+            cycle: 0,
+            targets: targets
+                .iter()
+                .map(|&(id, range, speed, threat)| RadarTarget {
+                    id: Some(id),
+                    range,
+                    speed,
+                    threat,
+                })
+                .collect(),
+            pages: Default::default(),
+        },
+        events: HVec::from_slice(events).unwrap(),
+    }
+}
+
+fn offline() -> Tracked {
+    Tracked {
+        frame: RadarFrame::offline(),
+        events: HVec::new(),
+    }
+}
+
+#[test]
+fn vehicles_and_passes_are_counted() {
+    let mut recorder = RideRecorder::start(at(0));
+    recorder.update(
+        at(100),
+        &tracked(
+            &[(1, 60, 30, ThreatLevel::Low), (2, 90, 45, ThreatLevel::Low)],
+            &[TrackEvent::NewVehicle(1), TrackEvent::NewVehicle(2)],
+        ),
+    );
+    recorder.update(
+        at(200),
+        &tracked(
+            &[(2, 40, 50, ThreatLevel::Medium)],
+            &[TrackEvent::VehiclePassed(1)],
+        ),
+    );
+    recorder.update(at(300), &tracked(&[], &[TrackEvent::VehicleLost(2)]));
+
+    let stats = recorder.stats(at(1000));
+    assert_eq!(stats.duration_ms, 1000);
+    assert_eq!((stats.vehicles, stats.passed), (2, 1));
+    assert_eq!(stats.peak_speed, 50);
+    assert_eq!(stats.closest_range, Some(40));
+}
+
+#[test]
+fn traffic_time_covers_frames_with_vehicles() {
+    let mut recorder = RideRecorder::start(at(0));
+    let busy = tracked(&[(1, 60, 30, ThreatLevel::Low)], &[]);
+    recorder.update(at(1000), &busy);
+    recorder.update(at(2000), &busy);
+    recorder.update(at(4000), &tracked(&[], &[]));
+    recorder.update(at(5000), &busy);
+
+    // Still busy at the end of the ride
+    assert_eq!(recorder.stats(at(6000)).traffic_ms, 4000);
+    assert_eq!(recorder.stats(at(6000)).duration_ms, 6000);
+}
+
+#[test]
+fn losing_the_radar_ends_the_traffic() {
+    let mut recorder = RideRecorder::start(at(0));
+    recorder.update(at(1000), &tracked(&[(1, 60, 30, ThreatLevel::Low)], &[]));
+    recorder.update(at(1500), &offline());
+    assert_eq!(recorder.stats(at(9000)).traffic_ms, 500);
+
+    recorder.update(at(10000), &tracked(&[(1, 60, 30, ThreatLevel::Low)], &[]));
+    recorder.radar_lost(at(10250));
+    assert_eq!(recorder.stats(at(20000)).traffic_ms, 750);
+}
+
+#[test]
+fn high_threats_are_counted_when_a_vehicle_becomes_one() {
+    let mut recorder = RideRecorder::start(at(0));
+    for (ms, threat) in [
+        (100, ThreatLevel::Medium),
+        (200, ThreatLevel::High),
+        (300, ThreatLevel::High),
+        (400, ThreatLevel::Medium),
+        (500, ThreatLevel::High),
+    ] {
+        recorder.update(
+            at(ms),
+            &tracked(&[(1, 30, 40, threat), (2, 80, 20, ThreatLevel::High)], &[]),
+        );
+    }
+    // Vehicle 1 twice, vehicle 2 once
+    assert_eq!(recorder.stats(at(600)).high_threats, 3);
+}
+
+#[test]
+fn ride_without_traffic() {
+    let recorder = RideRecorder::start(at(500));
+    let stats = recorder.stats(at(60_500));
+    assert_eq!(
+        stats,
+        RideStats {
+            duration_ms: 60_000,
+            ..RideStats::default()
+        }
+    );
+    assert_eq!(RideStats::decode(&stats.encode()), Some(stats));
+}
+
+#[test]
+fn invalid_records_are_rejected() {
+    let data = RideStats::default().encode();
+    assert_eq!(RideStats::decode(&data[..RIDE_STATS_SIZE - 1]), None);
+    let mut wrong_version = data;
+    wrong_version[0] = 0xFF;
+    assert_eq!(RideStats::decode(&wrong_version), None);
+    // Erased flash
+    assert_eq!(RideStats::decode(&[0xFF; RIDE_STATS_SIZE]), None);
+}
+
+fn stats() -> impl Strategy<Value = RideStats> {
+    (
+        any::<u32>(),
+        any::<u32>(),
+        any::<u16>(),
+        any::<u16>(),
+        any::<u16>(),
+        any::<u8>(),
+        prop::option::of(1u8..=255),
+    )
+        .prop_map(
+            |(
+                duration_ms,
+                traffic_ms,
+                vehicles,
+                passed,
+                high_threats,
+                peak_speed,
+                closest_range,
+            )| {
+                RideStats {
+                    duration_ms,
+                    traffic_ms,
+                    vehicles,
+                    passed,
+                    high_threats,
+                    peak_speed,
+                    closest_range,
+                }
+            },
+        )
+}
+
+// Frames some time apart
+fn frames() -> impl Strategy<Value = Vec<(u64, RadarFrame)>> {
+    prop::collection::vec((0u64..2000, radar_frame()), 0..64)
+}
+
+proptest! {
+    #[test]
+    fn encoding_round_trip(stats in stats()) {
+        prop_assert_eq!(RideStats::decode(&stats.encode()), Some(stats));
+    }
+
+    #[test]
+    fn ride_invariants(frames in frames(), end in 0u64..2000) {
+        let mut tracker = Tracker::new(CONFIG);
+        let mut recorder = RideRecorder::start(at(0));
+        let mut now = 0;
+        let mut peak_speed = 0;
+        let mut closest_range: Option<u8> = None;
+        let mut high_threats = 0;
+        let mut high: Vec<u8> = Vec::new();
+        let (delays, frames): (Vec<_>, Vec<_>) = frames.into_iter().unzip();
+        for (delay, frame) in delays.into_iter().zip(numbered(frames)) {
+            now += delay;
+            let tracked = tracker.update(&frame);
+            for target in tracked.frame.targets.iter() {
+                peak_speed = peak_speed.max(target.speed);
+                closest_range = Some(closest_range.map_or(target.range, |range| range.min(target.range)));
+            }
+            let now_high: Vec<u8> = tracked
+                .frame
+                .targets
+                .iter()
+                .filter(|target| target.threat == ThreatLevel::High)
+                .filter_map(|target| target.id)
+                .collect();
+            high_threats += now_high.iter().filter(|id| !high.contains(id)).count() as u16;
+            high = now_high;
+            recorder.update(at(now), &tracked);
+        }
+
+        let stats = recorder.stats(at(now + end));
+        prop_assert_eq!(stats.duration_ms as u64, now + end);
+        prop_assert!(stats.traffic_ms <= stats.duration_ms);
+        prop_assert!(stats.passed <= stats.vehicles);
+        prop_assert_eq!(stats.high_threats, high_threats);
+        prop_assert_eq!(stats.peak_speed, peak_speed);
+        prop_assert_eq!(stats.closest_range, closest_range);
+    }
+}
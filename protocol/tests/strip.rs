@@ -0,0 +1,125 @@
+use embassy_time::Instant;
+use magene_protocol::clock::Clock;
+use magene_protocol::escalation::EscalationRules;
+use magene_protocol::filter::FilterRules;
+use magene_protocol::magene::{self, HEADER_SIZE, NOTIFICATION_SIZE, PAGE_1, PAGE_2};
+use magene_protocol::page_buffer::{PageBuffer, DATA_PAGE_TIMEOUT, PAGE_HOLD};
+use magene_protocol::pipeline::Pipeline;
+use magene_protocol::radar::{RadarFrame, ThreatLevel, MAX_TARGETS, PAGE_SIZE};
+use magene_protocol::strip::{self, Pixel};
+use magene_protocol::tracker::TrackerConfig;
+use proptest::prelude::*;
+
+mod common;
+use common::vehicles;
+
+// The strip of the firmware, see LED_STRIP_*
+const LENGTH: usize = 16;
+const MAX_RANGE: u8 = 140;
+const SHOWN: usize = 6;
+
+// Pages of one cycle are forwarded at once, so time does not matter here
+struct FixedClock;
+
+impl Clock for FixedClock {
+    fn now(&self) -> Instant {
+        Instant::from_secs(1)
+    }
+}
+
+fn notification(page: [u8; PAGE_SIZE]) -> [u8; NOTIFICATION_SIZE] {
+    let mut data = [0u8; NOTIFICATION_SIZE];
+    data[HEADER_SIZE..].copy_from_slice(&page);
+    data
+}
+
+fn render(frame: &RadarFrame) -> [Pixel; LENGTH] {
+    let mut pixels = [Pixel::Off; LENGTH];
+    strip::render(frame, &mut pixels, MAX_RANGE, SHOWN);
+    pixels
+}
+
+// The path of the central with nothing but the radar connected: notifications
+// are assembled, run through the pipeline and drawn
+#[test]
+fn strip_shows_vehicles_without_a_client() {
+    let mut buffer = PageBuffer::with_clock(PAGE_HOLD, DATA_PAGE_TIMEOUT, FixedClock);
+    let mut pipeline = Pipeline::new(
+        TrackerConfig::DEFAULT,
+        FilterRules::NONE,
+        EscalationRules::DEFAULT,
+    );
+
+    let pages = [
+        [PAGE_1, 7, 80, 25, 1, 0, 0, 0],
+        [PAGE_2, 7, 20, 30, 1, 0, 0, 0],
+    ];
+    let mut shown = None;
+    for page in pages {
+        let page = magene::decode_notification(&notification(page)).expect("radar page");
+        if let Some(frame) = buffer.push(page) {
+            shown = Some(pipeline.process(&frame).frame);
+        }
+    }
+    let pixels = render(&shown.expect("frame forwarded"));
+
+    let mut expected = [Pixel::Off; LENGTH];
+    expected[7] = Pixel::Vehicle(ThreatLevel::Low);
+    // 20 m at 30 km/h is 2.4 s away, escalated to High
+    expected[13] = Pixel::Vehicle(ThreatLevel::High);
+    assert_eq!(pixels, expected);
+}
+
+#[test]
+fn offline_radar_is_shown_on_the_rider() {
+    let pixels = render(&RadarFrame::offline());
+    assert_eq!(pixels[LENGTH - 1], Pixel::RadarOffline);
+    assert!(pixels[..LENGTH - 1]
+        .iter()
+        .all(|&pixel| pixel == Pixel::Off));
+}
+
+#[test]
+fn clear_road_is_shown_on_the_rider() {
+    let pixels = render(&vehicles(&[]));
+    assert_eq!(pixels[LENGTH - 1], Pixel::RoadClear);
+    assert!(pixels[..LENGTH - 1]
+        .iter()
+        .all(|&pixel| pixel == Pixel::Off));
+}
+
+#[test]
+fn far_end_and_rider_positions() {
+    assert_eq!(strip::position(MAX_RANGE, LENGTH, MAX_RANGE), 0);
+    assert_eq!(strip::position(u8::MAX, LENGTH, MAX_RANGE), 0);
+    assert_eq!(strip::position(0, LENGTH, MAX_RANGE), LENGTH - 1);
+}
+
+#[test]
+fn closer_vehicle_wins_a_shared_pixel() {
+    let mut frame = vehicles(&[(50, 20), (51, 20)]);
+    frame.targets[0].threat = ThreatLevel::High;
+    let pixels = render(&frame);
+    assert_eq!(
+        pixels[strip::position(50, LENGTH, MAX_RANGE)],
+        Pixel::Vehicle(ThreatLevel::High)
+    );
+}
+
+#[test]
+fn empty_strip_does_not_panic() {
+    strip::render(&vehicles(&[(30, 20)]), &mut [], MAX_RANGE, SHOWN);
+}
+
+proptest! {
+    #[test]
+    fn position_stays_on_the_strip(range: u8, length in 1usize..64, max_range in 1u8..) {
+        prop_assert!(strip::position(range, length, max_range) < length);
+    }
+
+    #[test]
+    fn render_never_panics(targets in prop::collection::vec((any::<u8>(), any::<u8>()), 0..=MAX_TARGETS)) {
+        let pixels = render(&vehicles(&targets));
+        prop_assert!(pixels.iter().filter(|&&pixel| pixel != Pixel::Off).count() <= SHOWN);
+    }
+}
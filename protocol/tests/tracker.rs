@@ -0,0 +1,205 @@
+use std::collections::HashSet;
+
+use magene_protocol::radar::RadarFrame;
+use magene_protocol::tracker::{TrackEvent, Tracker, TrackerConfig};
+use proptest::prelude::*;
+
+mod common;
+use common::{ids, numbered, radar_frame, ranges, vehicles, CONFIG};
+
+#[test]
+fn approaching_vehicle_keeps_its_id() {
+    let mut tracker = Tracker::new(CONFIG);
+    let tracked = tracker.update(&vehicles(&[(80, 30)]));
+    assert_eq!(ids(&tracked), vec![Some(1)]);
+    assert_eq!(tracked.events.as_slice(), &[TrackEvent::NewVehicle(1)]);
+
+    for range in [74, 68, 61, 55] {
+        let tracked = tracker.update(&vehicles(&[(range, 30)]));
+        assert_eq!(ids(&tracked), vec![Some(1)]);
+        assert!(tracked.events.is_empty());
+    }
+}
+
+#[test]
+fn vehicles_are_ordered_by_range_and_keep_their_ids() {
+    let mut tracker = Tracker::new(CONFIG);
+    tracker.update(&vehicles(&[(100, 20), (40, 30)]));
+    // The radar reports them in another order, the output stays closest first
+    let tracked = tracker.update(&vehicles(&[(36, 30), (97, 20)]));
+    assert_eq!(ranges(&tracked), vec![36, 97]);
+    assert_eq!(ids(&tracked), vec![Some(2), Some(1)]);
+}
+
+#[test]
+fn target_goes_to_the_closest_track() {
+    let mut tracker = Tracker::new(CONFIG);
+    tracker.update(&vehicles(&[(50, 20), (58, 20)]));
+    // 55 is within the gate of both, but closer to the vehicle that was at 58
+    let tracked = tracker.update(&vehicles(&[(55, 20)]));
+    assert_eq!(ids(&tracked), vec![Some(2)]);
+}
+
+#[test]
+fn missing_vehicle_coasts_and_keeps_its_id() {
+    let mut tracker = Tracker::new(CONFIG);
+    tracker.update(&vehicles(&[(80, 30)]));
+    tracker.update(&vehicles(&[(76, 30)]));
+    for _ in 0..CONFIG.coast_frames {
+        let tracked = tracker.update(&vehicles(&[]));
+        assert!(tracked.frame.targets.is_empty());
+        assert!(tracked.events.is_empty());
+    }
+    // It went on approaching while it was missing
+    let tracked = tracker.update(&vehicles(&[(65, 30)]));
+    assert_eq!(ids(&tracked), vec![Some(1)]);
+    assert!(tracked.events.is_empty());
+}
+
+#[test]
+fn close_vehicle_that_disappears_passed() {
+    let mut tracker = Tracker::new(CONFIG);
+    tracker.update(&vehicles(&[(20, 30)]));
+    tracker.update(&vehicles(&[(12, 30)]));
+    for _ in 0..CONFIG.coast_frames {
+        assert!(tracker.update(&vehicles(&[])).events.is_empty());
+    }
+    let tracked = tracker.update(&vehicles(&[]));
+    assert_eq!(tracked.events.as_slice(), &[TrackEvent::VehiclePassed(1)]);
+}
+
+#[test]
+fn far_vehicle_that_disappears_is_lost() {
+    let mut tracker = Tracker::new(CONFIG);
+    tracker.update(&vehicles(&[(120, 10)]));
+    tracker.update(&vehicles(&[(118, 10)]));
+    let events: Vec<_> = (0..=CONFIG.coast_frames)
+        .flat_map(|_| tracker.update(&vehicles(&[])).events)
+        .collect();
+    assert_eq!(events, vec![TrackEvent::VehicleLost(1)]);
+}
+
+#[test]
+fn jump_beyond_the_gate_is_a_new_vehicle() {
+    let mut tracker = Tracker::new(CONFIG);
+    tracker.update(&vehicles(&[(80, 30)]));
+    let tracked = tracker.update(&vehicles(&[(40, 30)]));
+    assert_eq!(ids(&tracked), vec![Some(2)]);
+    assert_eq!(tracked.events.as_slice(), &[TrackEvent::NewVehicle(2)]);
+}
+
+#[test]
+fn offline_radar_forgets_vehicles_without_events() {
+    let mut tracker = Tracker::new(CONFIG);
+    tracker.update(&vehicles(&[(30, 30)]));
+    let tracked = tracker.update(&RadarFrame::offline());
+    assert_eq!(tracked.frame, RadarFrame::offline());
+    assert!(tracked.events.is_empty());
+
+    let tracked = tracker.update(&vehicles(&[(30, 30)]));
+    assert_eq!(ids(&tracked), vec![Some(2)]);
+}
+
+#[test]
+fn smoothing_damps_jitter() {
+    let mut tracker = Tracker::new(TrackerConfig {
+        smoothing: 50,
+        ..CONFIG
+    });
+    tracker.update(&vehicles(&[(60, 30)]));
+    let tracked = tracker.update(&vehicles(&[(66, 40)]));
+    assert_eq!(ranges(&tracked), vec![63]);
+    assert_eq!(tracked.frame.targets[0].speed, 35);
+}
+
+#[test]
+fn repeated_cycle_counts_as_one_frame() {
+    let mut tracker = Tracker::new(CONFIG);
+    tracker.update(&vehicles(&[(20, 30)]));
+    tracker.update(&vehicles(&[(12, 30)]));
+    // The page with the vehicle is late, its cycle goes out without it first
+    let partial = vehicles(&[]);
+    for _ in 0..=CONFIG.coast_frames {
+        assert!(tracker.update(&partial).events.is_empty());
+    }
+    let completed = RadarFrame {
+        targets: vehicles(&[(6, 30)]).targets,
+        ..partial
+    };
+    let tracked = tracker.update(&completed);
+    assert_eq!(ids(&tracked), vec![Some(1)]);
+    assert!(tracked.events.is_empty());
+}
+
+#[test]
+fn repeated_cycle_reports_only_new_events() {
+    let mut tracker = Tracker::new(CONFIG);
+    let partial = vehicles(&[(80, 30)]);
+    let tracked = tracker.update(&partial);
+    assert_eq!(tracked.events.as_slice(), &[TrackEvent::NewVehicle(1)]);
+
+    let completed = RadarFrame {
+        targets: vehicles(&[(80, 30), (40, 20)]).targets,
+        ..partial
+    };
+    let tracked = tracker.update(&completed);
+    assert_eq!(ids(&tracked), vec![Some(2), Some(1)]);
+    assert_eq!(tracked.events.as_slice(), &[TrackEvent::NewVehicle(2)]);
+    assert!(tracker.update(&completed).events.is_empty());
+
+    // The next cycle moves the tracks on from the completed one
+    let tracked = tracker.update(&vehicles(&[(74, 30), (36, 20)]));
+    assert_eq!(ids(&tracked), vec![Some(2), Some(1)]);
+    assert!(tracked.events.is_empty());
+}
+
+proptest! {
+    #[test]
+    fn tracking_invariants(
+        frames in prop::collection::vec(radar_frame(), 0..32),
+        smoothing in 0u8..=100,
+    ) {
+        let mut tracker = Tracker::new(TrackerConfig { smoothing, ..CONFIG });
+        let mut alive = HashSet::new();
+
+        for frame in numbered(frames) {
+            let tracked = tracker.update(&frame);
+            prop_assert_eq!(tracked.frame.online, frame.online);
+            prop_assert_eq!(tracked.frame.targets.len(), frame.targets.len());
+            prop_assert!(tracked.frame.targets.iter().all(|target| target.range != 0));
+            prop_assert!(tracked.frame.targets.windows(2).all(|pair| pair[0].range <= pair[1].range));
+
+            let ids: Vec<u8> = tracked.frame.targets.iter().filter_map(|target| target.id).collect();
+            prop_assert_eq!(ids.iter().collect::<HashSet<_>>().len(), ids.len());
+            if !frame.online {
+                alive.clear();
+            }
+            for event in &tracked.events {
+                match *event {
+                    TrackEvent::NewVehicle(id) => {
+                        prop_assert!(alive.insert(id));
+                        prop_assert!(ids.contains(&id));
+                    }
+                    TrackEvent::VehiclePassed(id) | TrackEvent::VehicleLost(id) => {
+                        prop_assert!(alive.remove(&id));
+                        prop_assert!(!ids.contains(&id));
+                    }
+                }
+            }
+            prop_assert!(ids.iter().all(|id| alive.contains(id)));
+        }
+    }
+
+    #[test]
+    fn unsmoothed_targets_are_reported_as_measured(frames in prop::collection::vec(radar_frame(), 0..16)) {
+        let mut tracker = Tracker::new(CONFIG);
+        for frame in numbered(frames) {
+            let tracked = tracker.update(&frame);
+            let mut measured: Vec<_> = frame.targets.iter().map(|t| (t.range, t.speed, t.threat)).collect();
+            let mut reported: Vec<_> = tracked.frame.targets.iter().map(|t| (t.range, t.speed, t.threat)).collect();
+            measured.sort();
+            reported.sort();
+            prop_assert_eq!(reported, measured);
+        }
+    }
+}
@@ -0,0 +1,74 @@
+use embassy_futures::select::{select, Either};
+use embassy_time::{Duration, Timer};
+use esp_hal::ledc::channel::{Channel, ChannelIFace as _};
+use esp_hal::ledc::LowSpeed;
+pub use magene_protocol::alert::{AlertDetector, AlertEvent};
+
+use crate::config::{
+    ALERT_DETECTOR, ALERT_DUTY, ALERT_FAST_APPROACH_PATTERN, ALERT_HIGH_THREAT_PATTERN,
+    ALERT_NEW_VEHICLE_PATTERN, ALERT_VEHICLE_PASSED_PATTERN,
+};
+use crate::fmt::*;
+use crate::messages::{RADAR_DATA_WATCH, TRACK_EVENT_CHANNEL};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Beep {
+    pub on: Duration,
+    pub off: Duration,
+}
+
+impl Beep {
+    pub const fn new(on_ms: u64, off_ms: u64) -> Self {
+        Self {
+            on: Duration::from_millis(on_ms),
+            off: Duration::from_millis(off_ms),
+        }
+    }
+}
+
+// Beeps that announce an alert
+fn pattern(event: AlertEvent) -> &'static [Beep] {
+    match event {
+        AlertEvent::NewVehicle => ALERT_NEW_VEHICLE_PATTERN,
+        AlertEvent::FastApproach => ALERT_FAST_APPROACH_PATTERN,
+        AlertEvent::VehiclePassed => ALERT_VEHICLE_PASSED_PATTERN,
+        AlertEvent::HighThreat => ALERT_HIGH_THREAT_PATTERN,
+    }
+}
+
+async fn play(channel: &Channel<'_, LowSpeed>, pattern: &[Beep]) {
+    for beep in pattern {
+        if let Err(e) = channel.set_duty(ALERT_DUTY) {
+            warn!("[Alert] Could not start beep: {:?}", Debug2Format(&e));
+            return;
+        }
+        Timer::after(beep.on).await;
+        if let Err(e) = channel.set_duty(0) {
+            warn!("[Alert] Could not stop beep: {:?}", Debug2Format(&e));
+            return;
+        }
+        Timer::after(beep.off).await;
+    }
+}
+
+pub async fn alert_task(channel: &Channel<'_, LowSpeed>) {
+    let mut detector = AlertDetector::new(ALERT_DETECTOR);
+    let mut radar_receiver = RADAR_DATA_WATCH
+        .receiver()
+        .expect("[Alert] Radar Watch receiver returned None - watch not initialized");
+
+    loop {
+        let event = match select(radar_receiver.changed(), TRACK_EVENT_CHANNEL.receive()).await {
+            Either::First(Some(frame)) => detector.update(&frame),
+            Either::First(None) => {
+                detector.reset();
+                None
+            }
+            Either::Second(event) => detector.track_event(event),
+        };
+        if let Some(event) = event {
+            info!("[Alert] {:?}", Debug2Format(&event));
+            play(channel, pattern(event)).await;
+        }
+    }
+}
@@ -6,12 +6,27 @@
     holding buffers for the duration of a data transfer."
 )]
 
-use embassy_futures::select::{select4, Either4};
+use embassy_futures::join::{join4, join5};
+use embassy_futures::select::{select, select4, Either, Either4};
 
 use embassy_time::Timer;
 use magene_proxy::bluetooth::{ble_manager_task, ScanEventHandler};
-use magene_proxy::config::{Server, CONNECTIONS_MAX, L2CAP_CHANNELS_MAX};
-use magene_proxy::led::led_task;
+use magene_proxy::build_info;
+use magene_proxy::config::{Server, CONNECTIONS_MAX, L2CAP_CHANNELS_MAX, LOG_LEVEL};
+use magene_proxy::console::console_task;
+use magene_proxy::dfu::{self, dfu_task};
+use magene_proxy::event_log::{self, event_log_task};
+#[cfg(feature = "hci-trace")]
+use magene_proxy::hci_trace::{hci_trace_task, TracingTransport};
+use magene_proxy::led::{led_task, Ws2812Indicator};
+use magene_proxy::logger;
+use magene_proxy::messages::{SystemRequest, SYSTEM_REQUEST_SIGNAL};
+use magene_proxy::rides::{self, ride_task};
+use magene_proxy::settings;
+#[cfg(feature = "alerts")]
+use magene_proxy::{alert::alert_task, config::ALERT_FREQUENCY};
+#[cfg(feature = "led-strip")]
+use magene_proxy::{config::LED_STRIP_LENGTH, led_strip::led_strip_task};
 
 use bt_hci::{controller::ExternalController, uuid::appearance};
 
@@ -22,16 +37,28 @@ use esp_hal::rtc_cntl::{sleep::GpioWakeupSource, Rtc};
 use esp_hal::system::software_reset;
 use esp_hal::timer::systimer::SystemTimer;
 use esp_hal::timer::timg::TimerGroup;
+use esp_hal::usb_serial_jtag::UsbSerialJtag;
+#[cfg(feature = "alerts")]
+use esp_hal::{
+    gpio::DriveMode,
+    ledc::{
+        channel::{self, ChannelIFace as _},
+        timer::{self, TimerIFace as _},
+        LSGlobalClkSource, Ledc, LowSpeed,
+    },
+};
 use esp_hal::{rmt::Rmt, time::Rate};
 
 use esp_hal_smartled::{smart_led_buffer, SmartLedsAdapter};
 
 use esp_wifi::ble::controller::BleConnector;
 
-use log::*;
+use magene_proxy::fmt::*;
 
 use embassy_executor::Spawner;
 
+#[cfg(feature = "defmt-rtt")]
+use defmt_rtt as _;
 use esp_backtrace as _;
 use trouble_host::Address;
 use trouble_host::{
@@ -48,9 +75,17 @@ esp_bootloader_esp_idf::esp_app_desc!();
 
 #[esp_hal_embassy::main]
 async fn main(_spawner: Spawner) {
-    esp_println::logger::init_logger_from_env();
+    logger::init(LOG_LEVEL);
     let config = esp_hal::Config::default().with_cpu_clock(CpuClock::_80MHz);
     let peripherals = esp_hal::init(config);
+    event_log::init();
+    settings::init();
+    rides::init();
+    dfu::init();
+
+    let (usb_rx, _usb_tx) = UsbSerialJtag::new(peripherals.USB_DEVICE)
+        .into_async()
+        .split();
 
     let input_config = InputConfig::default().with_pull(Pull::Up);
     let mut user_button = Input::new(peripherals.GPIO41, input_config);
@@ -58,11 +93,42 @@ async fn main(_spawner: Spawner) {
         .wakeup_enable(true, WakeEvent::LowLevel)
         .expect("[Main] Failed to initialize user button wakeup");
 
-    let mut led = {
-        let frequency = Rate::from_mhz(80);
-        let rmt = Rmt::new(peripherals.RMT, frequency).expect("[Main] Failed to initialize RMT0");
-        SmartLedsAdapter::new(rmt.channel0, peripherals.GPIO35, smart_led_buffer!(1))
-    };
+    let frequency = Rate::from_mhz(80);
+    let rmt = Rmt::new(peripherals.RMT, frequency).expect("[Main] Failed to initialize RMT");
+    let mut led_adapter =
+        SmartLedsAdapter::new(rmt.channel0, peripherals.GPIO35, smart_led_buffer!(1));
+    let mut led = Ws2812Indicator::new(&mut led_adapter);
+    #[cfg(feature = "led-strip")]
+    let mut led_strip = SmartLedsAdapter::new(
+        rmt.channel1,
+        peripherals.GPIO2,
+        smart_led_buffer!(LED_STRIP_LENGTH),
+    );
+
+    #[cfg(feature = "alerts")]
+    let mut ledc = Ledc::new(peripherals.LEDC);
+    #[cfg(feature = "alerts")]
+    ledc.set_global_slow_clock(LSGlobalClkSource::APBClk);
+    #[cfg(feature = "alerts")]
+    let mut alert_timer = ledc.timer::<LowSpeed>(timer::Number::Timer0);
+    #[cfg(feature = "alerts")]
+    alert_timer
+        .configure(timer::config::Config {
+            duty: timer::config::Duty::Duty8Bit,
+            clock_source: timer::LSClockSource::APBClk,
+            frequency: ALERT_FREQUENCY,
+        })
+        .expect("[Main] Failed to configure alert timer");
+    #[cfg(feature = "alerts")]
+    let mut alert_channel = ledc.channel(channel::Number::Channel0, peripherals.GPIO5);
+    #[cfg(feature = "alerts")]
+    alert_channel
+        .configure(channel::config::Config {
+            timer: &alert_timer,
+            duty_pct: 0,
+            drive_mode: DriveMode::PushPull,
+        })
+        .expect("[Main] Failed to configure alert channel");
 
     esp_alloc::heap_allocator!(size: 64 * 1024);
     let timer0 = SystemTimer::new(peripherals.SYSTIMER);
@@ -81,6 +147,8 @@ async fn main(_spawner: Spawner) {
         .expect("[Main] Failed to initialize WIFI/BLE controller");
 
     let transport = BleConnector::new(&wifi_init, peripherals.BT);
+    #[cfg(feature = "hci-trace")]
+    let transport = TracingTransport::new(transport);
     let controller = ExternalController::<_, 20>::new(transport);
     let address = Address::random([0xff, 0x8f, 0x1b, 0x05, 0xe4, 0xff]);
 
@@ -94,10 +162,13 @@ async fn main(_spawner: Spawner) {
     })) {
         Ok(result) => result,
         Err(e) => {
-            error!("[Main] Failed to setup GATT server: {:?}", e);
+            error!("[Main] Failed to setup GATT server: {:?}", Debug2Format(&e));
             return;
         }
     };
+    if let Err(e) = build_info::set_characteristics(&server) {
+        warn!("[Main] Failed to set build info: {:?}", Debug2Format(&e));
+    }
 
     let Host {
         mut runner,
@@ -106,30 +177,74 @@ async fn main(_spawner: Spawner) {
         ..
     } = stack.build();
 
-    info!("[Main] Setup complete.");
+    #[cfg(feature = "led-strip")]
+    let led_strip_future = led_strip_task(&mut led_strip);
+    #[cfg(not(feature = "led-strip"))]
+    let led_strip_future = core::future::pending::<()>();
+
+    #[cfg(feature = "alerts")]
+    let alert_future = alert_task(&alert_channel);
+    #[cfg(not(feature = "alerts"))]
+    let alert_future = core::future::pending::<()>();
 
+    #[cfg(feature = "hci-trace")]
+    let hci_trace_future = hci_trace_task();
+    #[cfg(not(feature = "hci-trace"))]
+    let hci_trace_future = core::future::pending::<()>();
+
+    info!("[Main] Setup complete. {}", build_info::BUILD_INFO);
+
+    let mut sleep = false;
     match select4(
         runner.run_with_handler(&ScanEventHandler),
-        led_task(&mut led),
+        join4(
+            join5(
+                led_task(&mut led),
+                led_strip_future,
+                alert_future,
+                event_log_task(),
+                console_task(usb_rx),
+            ),
+            dfu_task(),
+            ride_task(),
+            hci_trace_future,
+        ),
         ble_manager_task(central, &stack, &server, &mut peripheral),
-        user_button.wait_for_falling_edge(),
+        select(
+            user_button.wait_for_falling_edge(),
+            SYSTEM_REQUEST_SIGNAL.wait(),
+        ),
     )
     .await
     {
         Either4::First(result) => match result {
             Ok(()) => info!("[Main] Runner Task ended."),
-            Err(e) => error!("[Main] Runner task encounterd an error: {:?}", e),
+            Err(e) => error!(
+                "[Main] Runner task encounterd an error: {:?}",
+                Debug2Format(&e)
+            ),
         },
         Either4::Second(_) => {
-            info!("[Main] Led Task ended.")
+            info!("[Main] Auxiliary Tasks ended.")
         }
         Either4::Third(_) => {
             info!("[Main] BLE Manager Task ended.")
         }
-        Either4::Fourth(_) => {
+        Either4::Fourth(Either::First(_)) => {
             info!("[Main] User button pressed")
         }
+        Either4::Fourth(Either::Second(request)) => {
+            info!("[Main] {:?} requested by command", request);
+            sleep = request == SystemRequest::Sleep;
+            // Give the console and the config service time to send the response
+            Timer::after_millis(100).await;
+        }
     };
+
+    if sleep {
+        info!("[Main] Sleeping until the user button is pressed");
+        rtc.sleep_light(&[&wakeup_source]);
+    }
     info!("[Main] Resetting main application - byebye");
     software_reset();
 }
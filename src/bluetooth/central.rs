@@ -1,97 +1,139 @@
 use core::{u8, usize};
 
+use super::explorer::explore;
 use super::scan::scan;
-use super::utils::PageBuffer;
 
+use magene_protocol::escalation::EscalationRules;
+use magene_protocol::magene;
+use magene_protocol::page_buffer::{
+    AssemblyCounters, PageBuffer, Update, DATA_PAGE_TIMEOUT, PAGE_HOLD,
+};
+use magene_protocol::pipeline::Pipeline;
+use magene_protocol::tracker::TrackerConfig;
+
+use crate::capture;
 use crate::config::{
     BATTERY_LEVEL_CHARACTERISTIC, BATTERY_SERVICE, RADARLIGHT_CHARACTERISTIC, RADARLIGHT_SERVICE,
-    RADAR_ACTIVATION_BYTES, RADAR_DATA_PAGE_TIMEOUT,
+    RADAR_ACTIVATION_BYTES,
 };
-use crate::config::{DISCOVERY_DELAY, MAX_SERVICES};
+use crate::config::{DISCOVERY_DELAY, MAX_SERVICES, RSSI_POLL_INTERVAL};
+use crate::diagnostics::{Diagnostics, DIAGNOSTICS, RSSI_UNAVAILABLE};
 use crate::errors::CentralError;
+use crate::event_log::{self, EventKind};
+use crate::fmt::*;
 
 use crate::messages::{
-    ClientState, SourceState, BATTERY_DATA_WATCH, CLIENT_STATE_WATCH, RADAR_DATA_WATCH,
-    SOURCE_STATE_WATCH,
+    ClientState, SourceState, BATTERY_DATA_WATCH, CLIENT_STATE_WATCH, EXPLORE_WATCH,
+    RADAR_DATA_WATCH, SETTINGS_CHANGED_SIGNAL, SOURCE_STATE_WATCH, TRACK_EVENT_CHANNEL,
 };
+use crate::radar::RadarFrame;
+use crate::rides;
+use crate::settings;
 
-use embassy_futures::select::{select, Either};
+use core::sync::atomic::Ordering;
+use embassy_futures::select::{select, select3, Either, Either3};
 use embassy_time::Timer;
 use embedded_io::ErrorType;
 
-use log::*;
 use trouble_host::gatt::{GattClient, NotificationListener};
 use trouble_host::prelude::{Characteristic, Connection, ConnectionEvent, Uuid};
 use trouble_host::{Controller, PacketPool};
 
 use bt_hci::cmd::le::LeSetScanParams;
+use bt_hci::cmd::status::ReadRssi;
 use bt_hci::controller::ControllerCmdSync;
-use embassy_futures::select::{select3, Either3};
+use embassy_futures::select::{select4, Either4};
 use trouble_host::prelude::{Central, ConnectConfig, ScanConfig};
 use trouble_host::{Address, Stack};
 
+// Tracks, filters and escalates a frame and publishes the events of the
+// vehicles that are shown. The ride statistics count what is forwarded.
+fn process_frame(pipeline: &mut Pipeline, frame: &RadarFrame) -> RadarFrame {
+    let tracked = pipeline.process(frame);
+    rides::record(&tracked);
+    for event in tracked.events {
+        debug!("[Central] {:?}", Debug2Format(&event));
+        // Nobody listens without the alerts feature, so a full channel is fine
+        let _ = TRACK_EVENT_CHANNEL.try_send(event);
+    }
+    tracked.frame
+}
+
+// Runs for as long as the radar is connected, whether a client is connected or
+// not, so the LED, the strip and the alerts also work without a head unit
 async fn radarlight_notification_task<'a, const MTU: usize>(
     listener: &mut NotificationListener<'a, MTU>,
 ) {
-    let mut receiver = CLIENT_STATE_WATCH
-        .receiver()
-        .expect("[Central] Watch receiver returned None - watch not initialized");
     let sender = RADAR_DATA_WATCH.sender();
-    let mut page_buffer = PageBuffer::new(RADAR_DATA_PAGE_TIMEOUT);
+    let mut page_buffer = PageBuffer::new(PAGE_HOLD, DATA_PAGE_TIMEOUT);
+    // The profile is read below, earlier changes are already part of it
+    SETTINGS_CHANGED_SIGNAL.reset();
+    let profile = settings::get().filter_profile();
+    info!("[Central] Filter profile {}", profile.name);
+    let mut pipeline = Pipeline::new(
+        TrackerConfig::DEFAULT,
+        profile.rules,
+        EscalationRules::DEFAULT,
+    );
+    // The counters of the page buffer start over with every connection, the
+    // diagnostics keep counting since boot
+    let mut reported = AssemblyCounters::default();
 
     loop {
-        receiver
-            .changed_and(|&value| value == ClientState::Connected)
-            .await;
-
-        select(
-            receiver.changed_and(|&value| value == ClientState::Disconnected),
-            async {
-                loop {
-                    match select(listener.next(), page_buffer.get_timer()).await {
-                        Either::First(notification) => {
-                            let data = notification.as_ref();
-                            if data.len() == 11 {
-                                let mut page: [u8; 8] = [0u8; 8];
-                                page.copy_from_slice(&data[3..]);
-
-                                match page.get(0) {
-                                    Some(0x30) => {
-                                        page_buffer.set_page1(page);
-                                        let value = page_buffer.get();
-                                        sender.send(value);
-                                    }
-                                    Some(0x31) => {
-                                        page_buffer.set_page2(page);
-                                        let value = page_buffer.get();
-                                        sender.send(value);
-                                    }
-                                    _ => {
-                                        warn!(
-                                            "[Central] Radar notification: unknown page type {:?}",
-                                            page.as_slice()
-                                        );
-                                    }
-                                }
-                            } else {
-                                warn!(
-                                "[Central] Radar notification: wrong length (got {}, expected 11)",
-                                data.len()
-                            );
-                            }
-                        }
-                        Either::Second(_) => {
-                            info!("[Central] Radar data timeout");
-                            let value = page_buffer.get();
-                            sender.send(value)
+        match select3(
+            listener.next(),
+            page_buffer.get_timer(),
+            SETTINGS_CHANGED_SIGNAL.wait(),
+        )
+        .await
+        {
+            Either3::First(notification) => {
+                Diagnostics::increment(&DIAGNOSTICS.notifications_received);
+                let data = notification.as_ref();
+                capture::record(data);
+                match magene::decode_notification(data) {
+                    Ok(page) => {
+                        if let Some(frame) = page_buffer.push(page) {
+                            sender.send(Some(process_frame(&mut pipeline, &frame)));
                         }
                     }
+                    Err(e) => {
+                        Diagnostics::increment(&DIAGNOSTICS.decode_errors);
+                        warn!("[Central] Radar notification: {}", Display2Format(&e));
+                    }
+                }
+            }
+            Either3::Second(_) => match page_buffer.poll() {
+                Some(Update::Frame(frame)) => {
+                    sender.send(Some(process_frame(&mut pipeline, &frame)))
+                }
+                Some(Update::Expired) => {
+                    info!("[Central] Radar data timeout");
+                    Diagnostics::increment(&DIAGNOSTICS.page_timeouts);
+                    pipeline.reset();
+                    rides::radar_lost();
+                    sender.send(None)
                 }
+                None => {}
             },
-        )
-        .await;
+            // Vehicles already shown stay until they no longer meet the new rules
+            Either3::Third(_) => {
+                let profile = settings::get().filter_profile();
+                info!("[Central] Filter profile {}", profile.name);
+                pipeline.set_rules(profile.rules);
+            }
+        }
 
-        page_buffer.cleanup();
+        let counters = page_buffer.counters();
+        Diagnostics::add(
+            &DIAGNOSTICS.page_mismatches,
+            counters.mismatched.wrapping_sub(reported.mismatched),
+        );
+        Diagnostics::add(
+            &DIAGNOSTICS.pages_out_of_order,
+            counters.out_of_order.wrapping_sub(reported.out_of_order),
+        );
+        reported = counters;
     }
 }
 
@@ -121,7 +163,10 @@ async fn battery_notification_task<'a, 'b, C, P, const MTU: usize, const MAX_SER
         {
             Ok(bytes) => bytes,
             Err(e) => {
-                error!("[Central] Couldnt read inital battery level: {:?}", e);
+                error!(
+                    "[Central] Couldnt read inital battery level: {:?}",
+                    Debug2Format(&e)
+                );
                 sender.send(None);
                 continue;
             }
@@ -163,21 +208,35 @@ async fn battery_notification_task<'a, 'b, C, P, const MTU: usize, const MAX_SER
     }
 }
 
-async fn event_task<'a, P>(connection: &Connection<'a, P>)
+async fn event_task<'a, C, P>(connection: &Connection<'a, P>, stack: &'a Stack<'a, C, P>)
 where
+    C: Controller + ControllerCmdSync<ReadRssi>,
     P: PacketPool,
 {
     let reason = loop {
-        match connection.next().await {
-            ConnectionEvent::Disconnected { reason } => {
+        match select(connection.next(), Timer::after(RSSI_POLL_INTERVAL)).await {
+            Either::First(ConnectionEvent::Disconnected { reason }) => {
                 break reason;
             }
-            _ => {}
+            Either::First(_) => {}
+            Either::Second(_) => match connection.rssi(stack).await {
+                Ok(rssi) => DIAGNOSTICS.source_rssi.store(rssi, Ordering::Relaxed),
+                Err(e) => warn!(
+                    "[Central] Could not read source RSSI: {:?}",
+                    Debug2Format(&e)
+                ),
+            },
         }
     };
+    DIAGNOSTICS
+        .source_disconnect_reason
+        .store(reason.into_inner(), Ordering::Relaxed);
+    DIAGNOSTICS
+        .source_rssi
+        .store(RSSI_UNAVAILABLE, Ordering::Relaxed);
     info!(
         "[Central] Disconnected from source device, reason: {:?}",
-        reason
+        Debug2Format(&reason)
     )
 }
 
@@ -256,15 +315,19 @@ pub async fn ble_central_task<'a, 'server, C, P>(
     central: Central<'a, C, P>,
     stack: &'a Stack<'a, C, P>,
 ) where
-    C: Controller + ControllerCmdSync<LeSetScanParams>,
+    C: Controller + ControllerCmdSync<LeSetScanParams> + ControllerCmdSync<ReadRssi>,
     P: PacketPool,
 {
     let mut internal_central: Central<'a, C, P>;
     internal_central = central;
 
+    let mut explore_receiver = EXPLORE_WATCH
+        .receiver()
+        .expect("[Central] Watch receiver returned None - watch not initialized");
+
     let mut internal_target: Address;
     loop {
-        match scan(internal_central).await {
+        match scan(internal_central, &mut explore_receiver).await {
             Ok((target, central)) => {
                 internal_target = target;
                 internal_central = central
@@ -272,7 +335,8 @@ pub async fn ble_central_task<'a, 'server, C, P>(
 
             Err((error, central)) => {
                 internal_central = central;
-                error!("{:?}", error);
+                error!("{}", Display2Format(&error));
+                event_log::record(EventKind::CentralError, format_args!("{}", error));
                 continue;
             }
         };
@@ -296,38 +360,85 @@ pub async fn ble_central_task<'a, 'server, C, P>(
         let connection = match internal_central.connect(&connection_config).await {
             Ok(conn) => conn,
             Err(e) => {
-                error!("[Central] Error instantiating source connection {:?}", e);
+                error!(
+                    "[Central] Error instantiating source connection {:?}",
+                    Debug2Format(&e)
+                );
+                event_log::record(EventKind::CentralError, format_args!("connect: {:?}", e));
                 continue;
             }
         };
 
+        Diagnostics::increment(&DIAGNOSTICS.source_connects);
+
         let client = match GattClient::<C, P, MAX_SERVICES>::new(&stack, &connection).await {
             Ok(client) => client,
             Err(e) => {
-                error!("[Central] Error instantiating source client {:?}", e);
+                error!(
+                    "[Central] Error instantiating source client {:?}",
+                    Debug2Format(&e)
+                );
+                event_log::record(EventKind::CentralError, format_args!("client: {:?}", e));
                 continue;
             }
         };
 
         Timer::after(DISCOVERY_DELAY).await;
-        match select3(
+        if EXPLORE_WATCH.try_get().flatten() == Some(internal_target.addr) {
+            info!("[Central] Exploring the GATT database of the source device");
+            match select3(
+                client.task(),
+                explore(&client),
+                explore_receiver.changed_and(|address| address.is_none()),
+            )
+            .await
+            {
+                Either3::First(_) => warn!("[Central] Client runner ended while exploring"),
+                Either3::Second(Ok(())) => info!("[Central] GATT explorer finished"),
+                Either3::Second(Err(e)) => error!(
+                    "[Central] GATT explorer encountered an error: {:?}",
+                    Debug2Format(&e)
+                ),
+                Either3::Third(_) => info!("[Central] GATT explorer cancelled"),
+            }
+            EXPLORE_WATCH.sender().send(None);
+            connection.disconnect();
+            sender.send(SourceState::Disconnected);
+            continue;
+        }
+
+        match select4(
             client.task(),
             subscription_task(&client),
-            event_task(&connection),
+            event_task(&connection, stack),
+            explore_receiver.changed_and(|address| address.is_some()),
         )
         .await
         {
-            Either3::First(result) => match result {
+            Either4::First(result) => match result {
                 Ok(_) => info!("[Central] Client runner has ended."),
-                Err(e) => error!("[Central] Client runner encountered an error: {:?}", e),
+                Err(e) => error!(
+                    "[Central] Client runner encountered an error: {:?}",
+                    Debug2Format(&e)
+                ),
             },
-            Either3::Second(result) => match result {
+            Either4::Second(result) => match result {
                 Ok(_) => info!("[Central] Subscription task has ended."),
-                Err(e) => error!("[Central] Subscription task encountered an error: {:?}", e),
+                Err(e) => {
+                    error!(
+                        "[Central] Subscription task encountered an error: {}",
+                        Display2Format(&e)
+                    );
+                    event_log::record(EventKind::CentralError, format_args!("{}", e));
+                }
             },
-            Either3::Third(_) => {
+            Either4::Third(_) => {
                 info!("[Central] Event task ended.")
             }
+            Either4::Fourth(_) => {
+                info!("[Central] Disconnecting source device for the GATT explorer");
+                connection.disconnect();
+            }
         };
 
         RADAR_DATA_WATCH.sender().send(None);
@@ -0,0 +1,190 @@
+use embassy_time::Timer;
+use heapless::Vec;
+use trouble_host::{
+    connection::Connection,
+    prelude::{DefaultPacketPool, L2capChannel, L2capChannelConfig},
+    BleHostError, Controller, Stack,
+};
+
+use magene_protocol::download::{
+    CaptureRecord, FRAME_CAPTURE, FRAME_END, FRAME_ERROR, FRAME_EVENT, FRAME_HEADER_SIZE,
+    FRAME_RIDE, REQUEST_CAPTURE, REQUEST_EVENT_LOG, REQUEST_RIDES,
+};
+
+use crate::{
+    capture,
+    config::{CAPTURE_MAX_NOTIFICATION_SIZE, L2CAP_DOWNLOAD_MTU, L2CAP_DOWNLOAD_PSM},
+    event_log::{self, EVENT_SIZE},
+    fmt::*,
+    rides,
+};
+
+// Serves the bulk download protocol of `magene_protocol::download`, frames are
+// packed into SDUs of up to L2CAP_DOWNLOAD_MTU bytes
+struct FrameWriter<'c, 'd> {
+    channel: &'c mut L2capChannel<'d, DefaultPacketPool>,
+    buffer: Vec<u8, L2CAP_DOWNLOAD_MTU>,
+    frames: u32,
+}
+
+impl<'c, 'd> FrameWriter<'c, 'd> {
+    fn new(channel: &'c mut L2capChannel<'d, DefaultPacketPool>) -> Self {
+        Self {
+            channel,
+            buffer: Vec::new(),
+            frames: 0,
+        }
+    }
+
+    async fn flush<C: Controller>(
+        &mut self,
+        stack: &Stack<'_, C, DefaultPacketPool>,
+    ) -> Result<(), BleHostError<C::Error>> {
+        if !self.buffer.is_empty() {
+            self.channel.send(stack, &self.buffer).await?;
+            self.buffer.clear();
+        }
+        Ok(())
+    }
+
+    async fn frame<C: Controller>(
+        &mut self,
+        stack: &Stack<'_, C, DefaultPacketPool>,
+        kind: u8,
+        payload: &[u8],
+    ) -> Result<(), BleHostError<C::Error>> {
+        if self.buffer.len() + FRAME_HEADER_SIZE + payload.len() > self.buffer.capacity() {
+            self.flush(stack).await?;
+        }
+        // Payloads are far smaller than an SDU, so the frame always fits an empty buffer
+        let _ = self.buffer.push(kind);
+        let _ = self
+            .buffer
+            .extend_from_slice(&(payload.len() as u16).to_le_bytes());
+        let _ = self.buffer.extend_from_slice(payload);
+        self.frames += 1;
+        Ok(())
+    }
+
+    async fn end<C: Controller>(
+        mut self,
+        stack: &Stack<'_, C, DefaultPacketPool>,
+    ) -> Result<(), BleHostError<C::Error>> {
+        let frames = self.frames;
+        self.frame(stack, FRAME_END, &frames.to_le_bytes()).await?;
+        self.flush(stack).await
+    }
+}
+
+async fn send_capture<C: Controller>(
+    stack: &Stack<'_, C, DefaultPacketPool>,
+    writer: &mut FrameWriter<'_, '_>,
+) -> Result<(), BleHostError<C::Error>> {
+    let mut index = 0;
+    while let Some(notification) = capture::get(index) {
+        let record = CaptureRecord {
+            uptime_ms: notification.uptime_ms,
+            data: &notification.data,
+        };
+        let mut payload = [0u8; 4 + CAPTURE_MAX_NOTIFICATION_SIZE];
+        let length = record.encode(&mut payload).unwrap_or(0);
+        writer
+            .frame(stack, FRAME_CAPTURE, &payload[..length])
+            .await?;
+        index += 1;
+    }
+    Ok(())
+}
+
+// Sends the stored events, oldest first
+async fn send_event_log<C: Controller>(
+    stack: &Stack<'_, C, DefaultPacketPool>,
+    writer: &mut FrameWriter<'_, '_>,
+) -> Result<(), BleHostError<C::Error>> {
+    // Include the events that are still held in RTC memory
+    event_log::flush();
+    let mut event = [0u8; EVENT_SIZE];
+    for age in (0..event_log::len()).rev() {
+        if event_log::read(age, &mut event) {
+            writer.frame(stack, FRAME_EVENT, &event).await?;
+        }
+    }
+    Ok(())
+}
+
+// Sends the stored rides, oldest first
+async fn send_rides<C: Controller>(
+    stack: &Stack<'_, C, DefaultPacketPool>,
+    writer: &mut FrameWriter<'_, '_>,
+) -> Result<(), BleHostError<C::Error>> {
+    for age in (0..rides::len()).rev() {
+        if let Some(ride) = rides::read(age) {
+            writer.frame(stack, FRAME_RIDE, &ride.encode()).await?;
+        }
+    }
+    Ok(())
+}
+
+async fn serve<C: Controller>(
+    stack: &Stack<'_, C, DefaultPacketPool>,
+    channel: &mut L2capChannel<'_, DefaultPacketPool>,
+    request: u8,
+) -> Result<(), BleHostError<C::Error>> {
+    let mut writer = FrameWriter::new(channel);
+    match request {
+        REQUEST_CAPTURE => send_capture(stack, &mut writer).await?,
+        REQUEST_EVENT_LOG => send_event_log(stack, &mut writer).await?,
+        REQUEST_RIDES => send_rides(stack, &mut writer).await?,
+        _ => {
+            warn!("[Download] Unknown request {:#x}", request);
+            writer.frame(stack, FRAME_ERROR, &[request]).await?;
+        }
+    }
+    writer.end(stack).await
+}
+
+// Accepts download channels of the connected client until it disconnects
+pub async fn download_task<C: Controller>(
+    stack: &Stack<'_, C, DefaultPacketPool>,
+    connection: &Connection<'_, DefaultPacketPool>,
+) {
+    let config = L2capChannelConfig {
+        mtu: Some(L2CAP_DOWNLOAD_MTU as u16),
+        ..Default::default()
+    };
+
+    loop {
+        let mut channel =
+            match L2capChannel::accept(stack, connection, &[L2CAP_DOWNLOAD_PSM], &config).await {
+                Ok(channel) => channel,
+                Err(e) => {
+                    warn!(
+                        "[Download] Could not accept L2CAP channel: {:?}",
+                        Debug2Format(&e)
+                    );
+                    // Do not spin while the connection is going down
+                    Timer::after_secs(1).await;
+                    continue;
+                }
+            };
+        info!("[Download] L2CAP channel opened");
+
+        let mut request = [0u8; L2CAP_DOWNLOAD_MTU];
+        loop {
+            match channel.receive(stack, &mut request).await {
+                Ok(0) => {}
+                Ok(_) => {
+                    debug!("[Download] Request {:#x}", request[0]);
+                    if let Err(e) = serve(stack, &mut channel, request[0]).await {
+                        warn!("[Download] Transfer failed: {:?}", Debug2Format(&e));
+                        break;
+                    }
+                }
+                Err(e) => {
+                    debug!("[Download] L2CAP channel closed: {:?}", Debug2Format(&e));
+                    break;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,221 @@
+use core::fmt::{self, Write as _};
+
+use embassy_futures::select::{select, Either};
+use embassy_time::{Instant, Timer};
+use esp_println::println;
+use heapless::{String, Vec};
+use trouble_host::gatt::GattClient;
+use trouble_host::prelude::{Characteristic, CharacteristicProp, Uuid};
+use trouble_host::{BleHostError, Controller, PacketPool};
+
+use crate::config::{
+    EXPLORER_CHARACTERISTICS_MAX, EXPLORER_DESCRIPTORS_MAX, EXPLORER_NOTIFY_DURATION,
+    EXPLORER_VALUE_SIZE,
+};
+use crate::fmt::*;
+
+// Every line of the dump starts with this prefix, so it can be cut out of a console log
+const PREFIX: &str = "GATT";
+
+const PROPERTIES: [(CharacteristicProp, &str); 6] = [
+    (CharacteristicProp::Broadcast, "broadcast"),
+    (CharacteristicProp::Read, "read"),
+    (
+        CharacteristicProp::WriteWithoutResponse,
+        "write_without_response",
+    ),
+    (CharacteristicProp::Write, "write"),
+    (CharacteristicProp::Notify, "notify"),
+    (CharacteristicProp::Indicate, "indicate"),
+];
+
+// UUIDs are printed most significant byte first, 16-bit ones as 4 hex digits
+struct DisplayUuid<'a>(&'a Uuid);
+
+impl fmt::Display for DisplayUuid<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in self.0.as_raw().iter().rev() {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+fn hex(data: &[u8]) -> String<{ 3 * EXPLORER_VALUE_SIZE }> {
+    let mut hex = String::new();
+    for byte in data {
+        let _ = write!(hex, "{:02x} ", byte);
+    }
+    let _ = hex.pop();
+    hex
+}
+
+fn properties(characteristic: &Characteristic<[u8]>) -> String<64> {
+    let mut names = String::new();
+    for (property, name) in PROPERTIES {
+        if characteristic.props.has(property) {
+            if !names.is_empty() {
+                let _ = names.push(',');
+            }
+            let _ = names.push_str(name);
+        }
+    }
+    names
+}
+
+// Prints every descriptor of a characteristic with its value, e.g. the CCCD,
+// user description and presentation format
+async fn dump_descriptors<C, P, const MAX_SERVICES: usize>(
+    client: &GattClient<'_, C, P, MAX_SERVICES>,
+    characteristic: &Characteristic<[u8]>,
+) -> Result<(), BleHostError<C::Error>>
+where
+    C: Controller,
+    P: PacketPool,
+{
+    let descriptors = client
+        .descriptors::<EXPLORER_DESCRIPTORS_MAX>(characteristic)
+        .await?;
+    for descriptor in descriptors.iter() {
+        let mut value = [0u8; EXPLORER_VALUE_SIZE];
+        match client.read_descriptor(descriptor, &mut value).await {
+            Ok(length) => println!(
+                "{} descriptor uuid={} handle={:#06x} {}",
+                PREFIX,
+                DisplayUuid(&descriptor.uuid),
+                descriptor.handle,
+                hex(&value[..length.min(EXPLORER_VALUE_SIZE)])
+            ),
+            Err(e) => println!(
+                "{} descriptor uuid={} handle={:#06x} error={:?}",
+                PREFIX,
+                DisplayUuid(&descriptor.uuid),
+                descriptor.handle,
+                e
+            ),
+        }
+    }
+    Ok(())
+}
+
+// Prints every notification of one characteristic for EXPLORER_NOTIFY_DURATION
+async fn listen<C, P, const MAX_SERVICES: usize>(
+    client: &GattClient<'_, C, P, MAX_SERVICES>,
+    characteristic: &Characteristic<[u8]>,
+) -> Result<u32, BleHostError<C::Error>>
+where
+    C: Controller,
+    P: PacketPool,
+{
+    let indicate = !characteristic.props.has(CharacteristicProp::Notify);
+    let mut listener = client.subscribe(characteristic, indicate).await?;
+    let start = Instant::now();
+    let mut count = 0;
+    loop {
+        match select(listener.next(), Timer::after(EXPLORER_NOTIFY_DURATION)).await {
+            Either::First(notification) => {
+                let data = notification.as_ref();
+                println!(
+                    "{} notification handle={:#06x} t={} {}",
+                    PREFIX,
+                    characteristic.handle,
+                    start.elapsed().as_millis(),
+                    hex(&data[..data.len().min(EXPLORER_VALUE_SIZE)])
+                );
+                count += 1;
+            }
+            Either::Second(_) => break,
+        }
+    }
+    drop(listener);
+    client.unsubscribe(characteristic).await?;
+    Ok(count)
+}
+
+// Dumps the GATT database of the connected device: every service and
+// characteristic with its properties, value and descriptors, followed by the notifications
+// received while subscribed to each notifiable characteristic in turn.
+pub async fn explore<C, P, const MAX_SERVICES: usize>(
+    client: &GattClient<'_, C, P, MAX_SERVICES>,
+) -> Result<(), BleHostError<C::Error>>
+where
+    C: Controller,
+    P: PacketPool,
+{
+    let mut notifiable: Vec<Characteristic<[u8]>, EXPLORER_CHARACTERISTICS_MAX> = Vec::new();
+    let services = client.services().await?;
+    println!("{} begin services={}", PREFIX, services.len());
+
+    for service in services.iter() {
+        println!("{} service uuid={}", PREFIX, DisplayUuid(service.uuid()));
+        let characteristics = client
+            .characteristics::<EXPLORER_CHARACTERISTICS_MAX>(service)
+            .await?;
+
+        for characteristic in characteristics {
+            println!(
+                "{} characteristic uuid={} handle={:#06x} props={}",
+                PREFIX,
+                DisplayUuid(&characteristic.uuid),
+                characteristic.handle,
+                properties(&characteristic)
+            );
+
+            if characteristic.props.has(CharacteristicProp::Read) {
+                let mut value = [0u8; EXPLORER_VALUE_SIZE];
+                match client
+                    .read_characteristic(&characteristic, &mut value)
+                    .await
+                {
+                    Ok(length) => println!(
+                        "{} value handle={:#06x} {}",
+                        PREFIX,
+                        characteristic.handle,
+                        hex(&value[..length.min(EXPLORER_VALUE_SIZE)])
+                    ),
+                    Err(e) => println!(
+                        "{} value handle={:#06x} error={:?}",
+                        PREFIX, characteristic.handle, e
+                    ),
+                }
+            }
+
+            if let Err(e) = dump_descriptors(client, &characteristic).await {
+                println!(
+                    "{} descriptors handle={:#06x} error={:?}",
+                    PREFIX, characteristic.handle, e
+                );
+            }
+
+            let subscribable = characteristic.props.has(CharacteristicProp::Notify)
+                || characteristic.props.has(CharacteristicProp::Indicate);
+            if subscribable
+                && characteristic.cccd_handle.is_some()
+                && notifiable.push(characteristic).is_err()
+            {
+                warn!("[Explorer] Too many notifiable characteristics, skipping the rest");
+            }
+        }
+    }
+
+    for characteristic in notifiable.iter() {
+        info!(
+            "[Explorer] Listening to handle {:#06x} for {} s",
+            characteristic.handle,
+            EXPLORER_NOTIFY_DURATION.as_secs()
+        );
+        match listen(client, characteristic).await {
+            Ok(count) => println!(
+                "{} subscription handle={:#06x} notifications={}",
+                PREFIX, characteristic.handle, count
+            ),
+            Err(e) => println!(
+                "{} subscription handle={:#06x} error={:?}",
+                PREFIX, characteristic.handle, e
+            ),
+        }
+    }
+
+    println!("{} end", PREFIX);
+    Ok(())
+}
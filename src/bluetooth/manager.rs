@@ -1,30 +1,33 @@
 use crate::bluetooth::central::ble_central_task;
 use crate::bluetooth::peripheral::ble_peripheral_task;
 use crate::config::Server;
+use crate::diagnostics::diagnostics_task;
+use crate::fmt::*;
 
 use bt_hci::cmd::le::LeSetScanParams;
+use bt_hci::cmd::status::ReadRssi;
 use bt_hci::controller::ControllerCmdSync;
-use embassy_futures::select::{select, Either};
-use log::*;
+use embassy_futures::select::{select3, Either3};
 use trouble_host::prelude::{Central, DefaultPacketPool, Peripheral};
-use trouble_host::{Controller, PacketPool, Stack};
+use trouble_host::{Controller, Stack};
 
-pub async fn ble_manager_task<'a, 'server, C, P>(
-    central: Central<'a, C, P>,
-    stack: &'a Stack<'a, C, P>,
+pub async fn ble_manager_task<'a, 'server, C>(
+    central: Central<'a, C, DefaultPacketPool>,
+    stack: &'a Stack<'a, C, DefaultPacketPool>,
     server: &'server Server<'a>,
     peripheral: &mut Peripheral<'a, C, DefaultPacketPool>,
 ) where
-    C: Controller + ControllerCmdSync<LeSetScanParams>,
-    P: PacketPool,
+    C: Controller + ControllerCmdSync<LeSetScanParams> + ControllerCmdSync<ReadRssi>,
 {
-    match select(
+    match select3(
         ble_central_task(central, stack),
-        ble_peripheral_task(server, peripheral),
+        ble_peripheral_task(server, peripheral, stack),
+        diagnostics_task(server),
     )
     .await
     {
-        Either::First(_) => info!("[Manager] BLE central task ended."),
-        Either::Second(_) => info!("[Manager] BLE peripheral task ended."),
+        Either3::First(_) => info!("[Manager] BLE central task ended."),
+        Either3::Second(_) => info!("[Manager] BLE peripheral task ended."),
+        Either3::Third(_) => info!("[Manager] Diagnostics task ended."),
     }
 }
@@ -1,8 +1,9 @@
 mod central;
+mod download;
+mod explorer;
 mod manager;
 mod peripheral;
 mod scan;
-mod utils;
 
 pub use manager::ble_manager_task;
-pub use scan::ScanEventHandler;
+pub use scan::{find_scan_result, scan_results, ScanEventHandler, ScanResult};
@@ -1,23 +1,45 @@
-use embassy_futures::select::{select3, Either3};
+use bt_hci::cmd::status::ReadRssi;
+use bt_hci::controller::ControllerCmdSync;
+use core::fmt::Write as _;
+use core::sync::atomic::Ordering;
+use embassy_futures::select::{select, select3, select4, Either, Either3, Either4};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::watch::Receiver;
+use embassy_time::Timer;
 use embedded_io::ErrorType;
-use log::*;
+use heapless::{String, Vec};
+use magene_protocol::bryton;
+use magene_protocol::ride::RIDE_STATS_SIZE;
 use trouble_host::{
-    gatt::{GattConnection, GattConnectionEvent},
+    gatt::{GattConnection, GattConnectionEvent, GattEvent},
     prelude::{
         AdStructure, Advertisement, DefaultPacketPool, Peripheral, BR_EDR_NOT_SUPPORTED,
         LE_GENERAL_DISCOVERABLE,
     },
-    Controller, PacketPool,
+    Controller, PacketPool, Stack,
 };
 
+use super::download::download_task;
 use crate::{
-    config::{Server, BATTERY_SERVICE},
+    command,
+    config::{
+        Server, SourceOfflinePolicy, BATTERY_SERVICE, CONFIG_RESPONSE_SIZE, RSSI_POLL_INTERVAL,
+    },
+    dfu,
+    diagnostics::{Diagnostics, DIAGNOSTICS, RSSI_UNAVAILABLE},
     errors::PeripheralError,
-    messages::{ClientState, BATTERY_DATA_WATCH, CLIENT_STATE_WATCH, RADAR_DATA_WATCH},
+    event_log::{self, EventKind, Truncate, EVENT_SIZE},
+    fmt::*,
+    messages::{
+        ClientState, SourceState, BATTERY_DATA_WATCH, CLIENT_STATE_WATCH, COMMAND_REQUEST_SIGNAL,
+        EVENT_REQUEST_SIGNAL, RADAR_DATA_WATCH, RIDE_REQUEST_SIGNAL, SOURCE_STATE_WATCH,
+    },
+    radar::RadarFrame,
+    rides, settings,
 };
 
 async fn advertise<'values, 'server, C>(
-    name: &'values str,
+    name: &str,
     peripheral: &mut Peripheral<'values, C, DefaultPacketPool>,
     server: &'server Server<'values>,
 ) -> Result<
@@ -62,29 +84,97 @@ where
 
     let sender = CLIENT_STATE_WATCH.sender();
     sender.send(ClientState::Connected);
+    Diagnostics::increment(&DIAGNOSTICS.client_connects);
 
     info!("[Peripheral] Client device connection established");
     Ok(gatt_connection)
 }
 
-async fn gatt_events_task<'a, 'server, P: PacketPool>(gatt_connection: &GattConnection<'_, '_, P>) {
+// Flash is not read or written in the GATT handler, commands and reads of the
+// event log and the rides are carried out by gatt_requests_task
+fn gatt_write_handler(server: &Server<'_>, handle: u16) {
+    let config_service = &server.config_service;
+    if handle == config_service.command.handle {
+        COMMAND_REQUEST_SIGNAL.signal(server.get(&config_service.command).unwrap_or_default());
+    }
+
+    let event_log_service = &server.event_log_service;
+    if handle == event_log_service.index.handle {
+        let index = server.get(&event_log_service.index).unwrap_or(0);
+        EVENT_REQUEST_SIGNAL.signal(index as u32);
+    }
+
+    let rides_service = &server.rides_service;
+    if handle == rides_service.index.handle {
+        let index = server.get(&rides_service.index).unwrap_or(0);
+        RIDE_REQUEST_SIGNAL.signal(index as u32);
+    }
+
+    let dfu_service = &server.dfu_service;
+    if handle == dfu_service.control.handle {
+        dfu::queue_control(&server.get(&dfu_service.control).unwrap_or_default());
+        update_dfu_status(server);
+    }
+    if handle == dfu_service.data.handle {
+        dfu::queue_data(&server.get(&dfu_service.data).unwrap_or_default());
+        update_dfu_status(server);
+    }
+}
+
+fn update_dfu_status(server: &Server<'_>) {
+    if let Err(e) = server.set(&server.dfu_service.status, &dfu::status()) {
+        warn!(
+            "[Peripheral] Could not update DFU status: {:?}",
+            Debug2Format(&e)
+        );
+    }
+}
+
+async fn gatt_events_task<'a, 'server, P: PacketPool>(
+    server: &Server<'_>,
+    gatt_connection: &GattConnection<'_, '_, P>,
+) {
     let reason = loop {
         match gatt_connection.next().await {
             GattConnectionEvent::Disconnected { reason } => {
                 let sender = CLIENT_STATE_WATCH.sender();
                 sender.send(ClientState::Disconnected);
+                DIAGNOSTICS
+                    .client_disconnect_reason
+                    .store(reason.into_inner(), Ordering::Relaxed);
+                DIAGNOSTICS
+                    .client_rssi
+                    .store(RSSI_UNAVAILABLE, Ordering::Relaxed);
                 break reason;
             }
             GattConnectionEvent::Gatt { event } => {
+                let written_handle = match &event {
+                    GattEvent::Write(write) => Some(write.handle()),
+                    // The DFU task updates the status in the background
+                    GattEvent::Read(read) if read.handle() == server.dfu_service.status.handle => {
+                        update_dfu_status(server);
+                        None
+                    }
+                    _ => None,
+                };
                 match event.accept() {
                     Ok(reply) => reply.send().await,
-                    Err(e) => warn!("[Peripheral] Error sending GATT response: {:?}", e),
+                    Err(e) => warn!(
+                        "[Peripheral] Error sending GATT response: {:?}",
+                        Debug2Format(&e)
+                    ),
                 };
+                if let Some(handle) = written_handle {
+                    gatt_write_handler(server, handle);
+                }
             }
             _ => {}
         }
     };
-    info!("[Peripheral] GATT connection disconnected: {:?}", reason);
+    info!(
+        "[Peripheral] GATT connection disconnected: {:?}",
+        Debug2Format(&reason)
+    );
 }
 
 async fn gatt_battery_task<P: PacketPool>(
@@ -106,7 +196,10 @@ async fn gatt_battery_task<P: PacketPool>(
             .notify(gatt_connection, &level)
             .await
         {
-            error!("[Peripheral] Could not send battery notification: {:?}", e);
+            error!(
+                "[Peripheral] Could not send battery notification: {:?}",
+                Debug2Format(&e)
+            );
         }
     }
 }
@@ -120,18 +213,134 @@ async fn gatt_radar_task<P: PacketPool>(
         .expect("[Peripheral] Watch receiver returned None - watch not initialized");
 
     loop {
+        // Frames with more targets than the Bryton format has room for are cut
+        // down to the most important ones by the encoder
         let data = match receiver.changed().await {
-            Some(data) => data,
-            None => [0u8; 16],
+            Some(frame) => bryton::encode(&frame),
+            None => bryton::encode(&RadarFrame::offline()),
         };
 
-        if let Err(e) = server
+        match server
             .radar_service
             .radar_data
             .notify(gatt_connection, &data)
             .await
         {
-            error!("[Peripheral] Could not send battery notification: {:?}", e);
+            Ok(()) => Diagnostics::increment(&DIAGNOSTICS.notifications_forwarded),
+            Err(e) => {
+                Diagnostics::increment(&DIAGNOSTICS.notify_failures);
+                error!(
+                    "[Peripheral] Could not send radar notification: {:?}",
+                    Debug2Format(&e)
+                );
+            }
+        }
+    }
+}
+
+// Runs a command line of the config service and sets its response
+fn command_request(server: &Server<'_>, line: &[u8]) {
+    let mut response = Truncate::<CONFIG_RESPONSE_SIZE>(String::new());
+    match core::str::from_utf8(line) {
+        Ok(line) => {
+            info!("[Peripheral] Config command: {}", line);
+            if let Err(e) = command::execute_gatt(line, &mut response) {
+                let _ = write!(response, "error: {}", e);
+            }
+        }
+        Err(_) => {
+            let _ = write!(response, "error: command is not valid UTF-8");
+        }
+    }
+    let response = Vec::from_slice(response.0.as_bytes()).unwrap_or_default();
+    if let Err(e) = server.set(&server.config_service.response, &response) {
+        warn!(
+            "[Peripheral] Could not update config response: {:?}",
+            Debug2Format(&e)
+        );
+    }
+}
+
+fn event_request(server: &Server<'_>, index: u32) {
+    // All zeros when there is no such event
+    let mut event = [0u8; EVENT_SIZE];
+    if !event_log::read(index, &mut event) {
+        event = [0u8; EVENT_SIZE];
+    }
+    if let Err(e) = server.set(&server.event_log_service.record, &event) {
+        warn!(
+            "[Peripheral] Could not update event log record: {:?}",
+            Debug2Format(&e)
+        );
+    }
+}
+
+fn ride_request(server: &Server<'_>, index: u32) {
+    // All zeros when there is no such ride
+    let record = rides::read(index).map_or([0u8; RIDE_STATS_SIZE], |ride| ride.encode());
+    if let Err(e) = server.set(&server.rides_service.record, &record) {
+        warn!(
+            "[Peripheral] Could not update ride record: {:?}",
+            Debug2Format(&e)
+        );
+    }
+}
+
+// Carries out the requests of the GATT handler that read or write flash
+async fn gatt_requests_task(server: &Server<'_>) {
+    // A request of an earlier connection is not answered on this one
+    COMMAND_REQUEST_SIGNAL.reset();
+    EVENT_REQUEST_SIGNAL.reset();
+    RIDE_REQUEST_SIGNAL.reset();
+    loop {
+        match select3(
+            COMMAND_REQUEST_SIGNAL.wait(),
+            EVENT_REQUEST_SIGNAL.wait(),
+            RIDE_REQUEST_SIGNAL.wait(),
+        )
+        .await
+        {
+            Either3::First(line) => command_request(server, &line),
+            Either3::Second(index) => event_request(server, index),
+            Either3::Third(index) => ride_request(server, index),
+        }
+    }
+}
+
+async fn source_lost<const N: usize>(
+    receiver: &mut Receiver<'_, CriticalSectionRawMutex, SourceState, N>,
+) {
+    receiver
+        .changed_and(|&state| state != SourceState::Connected)
+        .await;
+}
+
+async fn connection_monitor_task<'a, C, const N: usize>(
+    gatt_connection: &GattConnection<'_, '_, DefaultPacketPool>,
+    stack: &'a Stack<'a, C, DefaultPacketPool>,
+    receiver: &mut Receiver<'_, CriticalSectionRawMutex, SourceState, N>,
+) where
+    C: Controller + ControllerCmdSync<ReadRssi>,
+{
+    loop {
+        match select(source_lost(receiver), Timer::after(RSSI_POLL_INTERVAL)).await {
+            // The policy is read when the source is lost, so a change applies to this connection
+            Either::First(_)
+                if settings::get().offline_policy == SourceOfflinePolicy::DisconnectClient =>
+            {
+                info!("[Peripheral] Source device lost, disconnecting client");
+                // The disconnect event is picked up by gatt_events_task, which ends the connection
+                gatt_connection.raw().disconnect();
+                core::future::pending::<()>().await
+            }
+            Either::First(_) => {}
+            Either::Second(_) => match gatt_connection.raw().rssi(stack).await {
+                Ok(rssi) => DIAGNOSTICS.client_rssi.store(rssi, Ordering::Relaxed),
+                Err(e) => warn!(
+                    "[Peripheral] Could not read client RSSI: {:?}",
+                    Debug2Format(&e)
+                ),
+            },
         }
     }
 }
@@ -139,33 +348,79 @@ async fn gatt_radar_task<P: PacketPool>(
 pub async fn ble_peripheral_task<'a, 'server, C>(
     server: &'server Server<'a>,
     peripheral: &mut Peripheral<'a, C, DefaultPacketPool>,
+    stack: &'a Stack<'a, C, DefaultPacketPool>,
 ) where
-    C: Controller,
+    C: Controller + ControllerCmdSync<ReadRssi>,
 {
+    let mut source_receiver = SOURCE_STATE_WATCH
+        .receiver()
+        .expect("[Peripheral] Watch receiver returned None - watch not initialized");
+
     info!("[Peripheral] Starting advertising and GATT service");
     loop {
-        match advertise("RadarProxy", peripheral, &server).await {
+        // Read before advertising, the monitor reads the policy again when the source is lost
+        let settings = settings::get();
+        let name = settings.proxy_name;
+        let policy = settings.offline_policy;
+        let advertise_result = if policy == SourceOfflinePolicy::SignalOffline {
+            advertise(&name, peripheral, &server).await
+        } else {
+            info!("[Peripheral] Waiting for source device before advertising");
+            source_receiver
+                .get_and(|&state| state == SourceState::Connected)
+                .await;
+
+            match select(
+                advertise(&name, peripheral, &server),
+                source_lost(&mut source_receiver),
+            )
+            .await
+            {
+                Either::First(result) => result,
+                Either::Second(_) => {
+                    info!("[Peripheral] Source device lost, advertising stopped");
+                    continue;
+                }
+            }
+        };
+
+        match advertise_result {
             Ok(gatt_connection) => {
                 match select3(
-                    gatt_events_task(&gatt_connection),
-                    gatt_radar_task(&server, &gatt_connection),
-                    gatt_battery_task(&server, &gatt_connection),
+                    select4(
+                        gatt_events_task(&server, &gatt_connection),
+                        gatt_radar_task(&server, &gatt_connection),
+                        gatt_battery_task(&server, &gatt_connection),
+                        connection_monitor_task(&gatt_connection, stack, &mut source_receiver),
+                    ),
+                    download_task(stack, gatt_connection.raw()),
+                    gatt_requests_task(&server),
                 )
                 .await
                 {
-                    Either3::First(_) => {
+                    Either3::First(Either4::First(_)) => {
                         info!("[Peripheral] Gatt Event Task ended.")
                     }
-                    Either3::Second(_) => {
+                    Either3::First(Either4::Second(_)) => {
                         info!("[Peripheral] Gatt Radar Task ended.")
                     }
-                    Either3::Third(_) => {
+                    Either3::First(Either4::Third(_)) => {
                         info!("[Peripheral] Gatt battery Task ended.")
                     }
+                    Either3::First(Either4::Fourth(_)) => {
+                        info!("[Peripheral] Connection monitor Task ended.")
+                    }
+                    Either3::Second(_) => {
+                        info!("[Peripheral] Download Task ended.")
+                    }
+                    Either3::Third(_) => {
+                        info!("[Peripheral] Gatt requests Task ended.")
+                    }
                 }
             }
             Err(e) => {
-                error!("{:?}", e);
+                error!("{}", Display2Format(&e));
+                event_log::record(EventKind::PeripheralError, format_args!("{}", e));
                 continue;
             }
         }
@@ -1,52 +1,136 @@
-use crate::config::TARGET_NAME;
+use crate::config::{EXPLORER_SCAN_TIMEOUT, RADARLIGHT_SERVICE, SCAN_RESULTS_MAX};
 use crate::errors::CentralError;
-use crate::messages::{SourceState, SCAN_CHANNEL, SOURCE_STATE_WATCH};
+use crate::fmt::*;
+use crate::messages::{SourceState, EXPLORE_WATCH, SCAN_CHANNEL, SOURCE_STATE_WATCH};
+use crate::settings::{self, SETTINGS_NAME_SIZE};
 
 use bt_hci::cmd::le::LeSetScanParams;
 use bt_hci::controller::ControllerCmdSync;
+use bt_hci::param::BdAddr;
+use core::cell::RefCell;
+use core::future::pending;
 use core::{u8, usize};
-use embassy_time::Duration;
+use embassy_futures::select::{select3, Either3};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::watch::Receiver;
+use embassy_time::{Duration, Instant, Timer};
 use embedded_io::ErrorType;
-use log::*;
+use heapless::{String, Vec};
+use magene_protocol::advertising::{Advertisement, Uuid};
 use trouble_host::prelude::{Central, EventHandler, ScanConfig};
 use trouble_host::scan::{LeAdvReportsIter, Scanner};
 use trouble_host::{Address, Controller, PacketPool};
 
-fn parse_local_name(data: &[u8]) -> Option<&str> {
-    let mut i = 0;
-    while i < data.len() {
-        let length = data[i] as usize;
-        if length == 0 || i + length >= data.len() {
-            break;
+// Radar seen while scanning, listed by the console `scan` command
+#[derive(Debug, Clone)]
+pub struct ScanResult {
+    pub address: Address,
+    pub name: String<SETTINGS_NAME_SIZE>,
+    pub rssi: i8,
+    pub last_seen: Instant,
+}
+
+static SCAN_RESULTS: Mutex<CriticalSectionRawMutex, RefCell<Vec<ScanResult, SCAN_RESULTS_MAX>>> =
+    Mutex::new(RefCell::new(Vec::new()));
+
+// Radars are added when they match, later reports only refresh them. The name
+// often arrives in a scan response of its own, so it is filled in when missing.
+fn update_scan_results(address: Address, name: Option<&str>, rssi: i8, is_radar: bool) {
+    SCAN_RESULTS.lock(|results| {
+        let mut results = results.borrow_mut();
+        let now = Instant::now();
+        if let Some(result) = results
+            .iter_mut()
+            .find(|result| result.address.addr == address.addr)
+        {
+            result.rssi = rssi;
+            result.last_seen = now;
+            if let (true, Some(name)) = (result.name.is_empty(), name) {
+                result.name = String::try_from(name).unwrap_or_default();
+            }
+            return;
+        }
+        if !is_radar {
+            return;
         }
-        let ad_type = data[i + 1];
-        let ad_data = &data[i + 2..i + 1 + length];
 
-        if ad_type == 0x08 || ad_type == 0x09 {
-            if let Ok(name) = core::str::from_utf8(ad_data) {
-                return Some(name);
+        let result = ScanResult {
+            address,
+            name: name
+                .and_then(|name| String::try_from(name).ok())
+                .unwrap_or_default(),
+            rssi,
+            last_seen: now,
+        };
+        if results.is_full() {
+            // Replace the radar that has not been seen for the longest time
+            if let Some(oldest) = results.iter_mut().min_by_key(|result| result.last_seen) {
+                *oldest = result;
             }
+        } else {
+            let _ = results.push(result);
         }
-        i += length + 1;
-    }
-    None
+    });
+}
+
+// Radars seen within `max_age`, strongest signal first
+pub fn scan_results(max_age: Duration) -> Vec<ScanResult, SCAN_RESULTS_MAX> {
+    let now = Instant::now();
+    let mut results: Vec<ScanResult, SCAN_RESULTS_MAX> = SCAN_RESULTS.lock(|results| {
+        results
+            .borrow()
+            .iter()
+            .filter(|result| now - result.last_seen <= max_age)
+            .cloned()
+            .collect()
+    });
+    results.sort_unstable_by_key(|result| core::cmp::Reverse(result.rssi));
+    results
+}
+
+pub fn find_scan_result(addr: &BdAddr) -> Option<ScanResult> {
+    SCAN_RESULTS.lock(|results| {
+        results
+            .borrow()
+            .iter()
+            .find(|result| result.address.addr == *addr)
+            .cloned()
+    })
 }
 
 pub struct ScanEventHandler;
 
 impl EventHandler for ScanEventHandler {
     fn on_adv_reports(&self, mut it: LeAdvReportsIter<'_>) {
+        let settings = settings::get();
         while let Some(Ok(report)) = it.next() {
-            if let Some(local_name) = parse_local_name(report.data) {
-                if local_name == TARGET_NAME {
-                    match SCAN_CHANNEL.try_send(Address {
-                        kind: report.addr_kind,
-                        addr: report.addr,
-                    }) {
-                        Ok(_) => {}
-                        Err(e) => {
-                            error!("[Central] Could not send scan result to channel: {:?}", e)
-                        }
+            let address = Address {
+                kind: report.addr_kind,
+                addr: report.addr,
+            };
+            let advertisement = Advertisement::new(report.data);
+            let local_name = advertisement.local_name();
+            let name_matches = local_name == Some(settings.target_name.as_str());
+            let is_radar =
+                name_matches || advertisement.advertises_service(Uuid::Uuid128(RADARLIGHT_SERVICE));
+            update_scan_results(address, local_name, report.rssi, is_radar);
+
+            // A bound radar is only matched by its address, the name is ignored.
+            // While exploring, only the explored device is connected to.
+            let is_target = match (EXPLORE_WATCH.try_get().flatten(), settings.bound_address) {
+                (Some(explore), _) => explore == report.addr,
+                (None, Some(bound)) => bound.addr == report.addr,
+                (None, None) => name_matches,
+            };
+            if is_target {
+                match SCAN_CHANNEL.try_send(address) {
+                    Ok(_) => {}
+                    Err(e) => {
+                        error!(
+                            "[Central] Could not send scan result to channel: {:?}",
+                            Debug2Format(&e)
+                        )
                     }
                 }
             }
@@ -56,6 +140,7 @@ impl EventHandler for ScanEventHandler {
 
 pub async fn scan<'a, C, P>(
     mut central: Central<'a, C, P>,
+    explore_receiver: &mut Receiver<'static, CriticalSectionRawMutex, Option<BdAddr>, 1>,
 ) -> Result<(Address, Central<'a, C, P>), (CentralError<<C as ErrorType>::Error>, Central<'a, C, P>)>
 where
     C: Controller + ControllerCmdSync<LeSetScanParams>,
@@ -83,7 +168,33 @@ where
         return Err((CentralError::ScanInstantiationError(), central));
     }
 
-    let device = receiver.receive().await;
+    // A device to explore that does not show up within EXPLORER_SCAN_TIMEOUT is
+    // given up, the scan then goes on for the radar
+    let device = loop {
+        let exploring = EXPLORE_WATCH.try_get().flatten();
+        let timeout = async {
+            match exploring {
+                Some(_) => Timer::after(EXPLORER_SCAN_TIMEOUT).await,
+                None => pending::<()>().await,
+            }
+        };
+        match select3(receiver.receive(), explore_receiver.changed(), timeout).await {
+            Either3::First(device) => break device,
+            Either3::Second(_) => {
+                // Devices matched for the previous target are stale
+                SCAN_CHANNEL.clear();
+            }
+            Either3::Third(_) => {
+                if let Some(address) = exploring {
+                    warn!(
+                        "[Central] Device {:?} to explore not found, returning to the radar",
+                        address.into_inner()
+                    );
+                }
+                EXPLORE_WATCH.sender().send(None);
+            }
+        }
+    };
 
     info!("[Central] Device found: {:?}", device.addr.into_inner());
     target = device;
@@ -0,0 +1,50 @@
+use heapless::String;
+
+use crate::config::{Server, BUILD_INFO_SIZE};
+
+// Build information embedded by build.rs
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+pub const GIT_COMMIT: &str = env!("BUILD_GIT_COMMIT");
+pub const PROFILE: &str = env!("BUILD_PROFILE");
+pub const FEATURES: &str = env!("BUILD_FEATURES");
+
+// Device Information Service strings
+pub const FIRMWARE_REVISION: &str =
+    concat!(env!("CARGO_PKG_VERSION"), "+", env!("BUILD_GIT_COMMIT"));
+pub const SOFTWARE_REVISION: &str =
+    concat!(env!("BUILD_PROFILE"), " [", env!("BUILD_FEATURES"), "]");
+
+// e.g. "magene-proxy 0.1.0+1a2b3c4d release [alerts,led-strip]"
+pub const BUILD_INFO: &str = concat!(
+    env!("CARGO_PKG_NAME"),
+    " ",
+    env!("CARGO_PKG_VERSION"),
+    "+",
+    env!("BUILD_GIT_COMMIT"),
+    " ",
+    env!("BUILD_PROFILE"),
+    " [",
+    env!("BUILD_FEATURES"),
+    "]"
+);
+
+fn truncated(value: &str) -> String<BUILD_INFO_SIZE> {
+    String::try_from(&value[..value.len().min(BUILD_INFO_SIZE)]).unwrap_or_default()
+}
+
+// Fills the Device Information Service and the build info characteristic
+pub fn set_characteristics(server: &Server<'_>) -> Result<(), trouble_host::Error> {
+    let device_information = &server.device_information_service;
+    server.set(
+        &device_information.firmware_revision,
+        &truncated(FIRMWARE_REVISION),
+    )?;
+    server.set(
+        &device_information.software_revision,
+        &truncated(SOFTWARE_REVISION),
+    )?;
+    server.set(
+        &server.diagnostics_service.build_info,
+        &truncated(BUILD_INFO),
+    )
+}
@@ -0,0 +1,85 @@
+use core::cell::RefCell;
+use core::fmt::{self, Write};
+
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_time::Instant;
+use heapless::Vec;
+
+use crate::config::{CAPTURE_MAX_NOTIFICATION_SIZE, CAPTURE_SIZE};
+
+// Raw radar notification as received from the source device
+#[derive(Clone)]
+pub struct CapturedNotification {
+    pub uptime_ms: u32,
+    pub data: Vec<u8, CAPTURE_MAX_NOTIFICATION_SIZE>,
+}
+
+// Records raw source notifications for protocol analysis. Recording stops once
+// the buffer is full, so a capture always starts at the `capture start` command.
+struct Capture {
+    active: bool,
+    notifications: Vec<CapturedNotification, CAPTURE_SIZE>,
+}
+
+static CAPTURE: Mutex<CriticalSectionRawMutex, RefCell<Capture>> =
+    Mutex::new(RefCell::new(Capture {
+        active: false,
+        notifications: Vec::new(),
+    }));
+
+pub fn start() {
+    CAPTURE.lock(|capture| {
+        let mut capture = capture.borrow_mut();
+        capture.notifications.clear();
+        capture.active = true;
+    });
+}
+
+pub fn stop() {
+    CAPTURE.lock(|capture| capture.borrow_mut().active = false);
+}
+
+// Returns whether a capture is running and the number of captured notifications
+pub fn status() -> (bool, usize) {
+    CAPTURE.lock(|capture| {
+        let capture = capture.borrow();
+        (capture.active, capture.notifications.len())
+    })
+}
+
+pub fn record(data: &[u8]) {
+    CAPTURE.lock(|capture| {
+        let mut capture = capture.borrow_mut();
+        if !capture.active {
+            return;
+        }
+        let length = data.len().min(CAPTURE_MAX_NOTIFICATION_SIZE);
+        let notification = CapturedNotification {
+            uptime_ms: Instant::now().as_millis() as u32,
+            data: Vec::from_slice(&data[..length]).unwrap_or_default(),
+        };
+        if capture.notifications.push(notification).is_err() {
+            capture.active = false;
+        }
+    });
+}
+
+// Copies one notification at a time so callers do not send or print inside the lock
+pub fn get(index: usize) -> Option<CapturedNotification> {
+    CAPTURE.lock(|capture| capture.borrow().notifications.get(index).cloned())
+}
+
+// Writes one line per notification: uptime in ms followed by the payload as hex
+pub fn dump(out: &mut dyn Write) -> fmt::Result {
+    let mut index = 0;
+    while let Some(notification) = get(index) {
+        write!(out, "{:>10}", notification.uptime_ms)?;
+        for byte in notification.data.iter() {
+            write!(out, " {:02x}", byte)?;
+        }
+        writeln!(out)?;
+        index += 1;
+    }
+    Ok(())
+}
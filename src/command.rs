@@ -0,0 +1,480 @@
+use core::fmt::{self, Write};
+use core::str::SplitWhitespace;
+use core::sync::atomic::Ordering;
+
+use bt_hci::param::{AddrKind, BdAddr};
+use embassy_time::{Duration, Instant};
+use heapless::String;
+use log::LevelFilter;
+use trouble_host::Address;
+
+use crate::bluetooth::{find_scan_result, scan_results};
+use crate::build_info::BUILD_INFO;
+use crate::capture;
+use crate::config::{
+    SourceOfflinePolicy, FILTER_PROFILES, LOG_MODULE_FILTERS_MAX, RIDES_LIST_COUNT,
+    SCAN_RESULT_MAX_AGE,
+};
+use crate::diagnostics::{DIAGNOSTICS, RSSI_UNAVAILABLE};
+use crate::errors::CommandError;
+use crate::logger::ModuleName;
+use crate::messages::{
+    SourceState, SystemRequest, CLIENT_STATE_WATCH, EXPLORE_WATCH, SOURCE_STATE_WATCH,
+    SYSTEM_REQUEST_SIGNAL,
+};
+use crate::rides;
+use crate::settings::{self, Settings, SETTINGS_NAME_SIZE};
+
+// Command core shared by the serial console and the GATT config service. Commands
+// are single lines, the response is written to `out`.
+
+const HELP: &str = "\
+status                      show link state and counters
+scan                        list radars seen while scanning
+bind <address>|clear        only connect to the radar with this address
+config get [key]            show settings
+config set <key> <value>    change and persist a setting (target, name, bind, profile, offline_policy, log_level, log_rate_limit)
+config reset                restore the default settings
+log level [level]           show or set the log level (off, error, warn, info, debug, trace)
+log level <module> <level>  set the level of a [Module] tag, 'default' follows the log level again
+log ratelimit [on|off]      limit repeated warnings from the same place in the code
+capture start|stop|dump     record raw radar notifications
+rides [count]               show the ride in progress and the newest stored rides
+explore <address>|cancel    dump the GATT database of a device on the serial console
+reset                       restart the proxy
+sleep                       light sleep until the button is pressed
+";
+
+const SETTING_KEYS: [&str; 8] = [
+    "target",
+    "name",
+    "bind",
+    "profile",
+    "offline_policy",
+    "log_level",
+    "log_modules",
+    "log_rate_limit",
+];
+
+// Commands the GATT config service runs besides `config get`. Only `log` changes
+// anything, and log levels only change what the proxy prints.
+const GATT_COMMANDS: [&str; 4] = ["help", "status", "rides", "log"];
+
+// Bluetooth addresses are shown most significant byte first, as printed on devices and apps
+struct DisplayAddress<'a>(&'a BdAddr);
+
+impl fmt::Display for DisplayAddress<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bytes = self.0.into_inner();
+        for (i, byte) in bytes.iter().rev().enumerate() {
+            if i > 0 {
+                f.write_char(':')?;
+            }
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_address(value: &str) -> Option<BdAddr> {
+    let mut bytes = [0u8; 6];
+    let mut parts = value.split(':');
+    for byte in bytes.iter_mut().rev() {
+        *byte = u8::from_str_radix(parts.next()?, 16).ok()?;
+    }
+    match parts.next() {
+        Some(_) => None,
+        None => Some(BdAddr::new(bytes)),
+    }
+}
+
+fn argument<'a>(
+    args: &mut SplitWhitespace<'a>,
+    name: &'static str,
+) -> Result<&'a str, CommandError> {
+    args.next().ok_or(CommandError::MissingArgument(name))
+}
+
+fn write_rssi(out: &mut dyn Write, rssi: i8) -> fmt::Result {
+    match rssi {
+        RSSI_UNAVAILABLE => write!(out, "n/a"),
+        rssi => write!(out, "{} dBm", rssi),
+    }
+}
+
+fn status(out: &mut dyn Write) -> Result<(), CommandError> {
+    let settings = settings::get();
+    writeln!(out, "firmware: {}", BUILD_INFO)?;
+    writeln!(out, "uptime: {} s", Instant::now().as_secs())?;
+    write!(out, "source: {:?}, rssi ", SOURCE_STATE_WATCH.try_get())?;
+    write_rssi(out, DIAGNOSTICS.source_rssi.load(Ordering::Relaxed))?;
+    writeln!(out)?;
+    write!(out, "client: {:?}, rssi ", CLIENT_STATE_WATCH.try_get())?;
+    write_rssi(out, DIAGNOSTICS.client_rssi.load(Ordering::Relaxed))?;
+    writeln!(out)?;
+    match settings.bound_address {
+        Some(address) => writeln!(out, "target: bound to {}", DisplayAddress(&address.addr))?,
+        None => writeln!(out, "target: {}", settings.target_name)?,
+    }
+    writeln!(
+        out,
+        "notifications: {} received, {} decode errors, {} forwarded, {} notify failures, {} page timeouts",
+        DIAGNOSTICS.notifications_received.load(Ordering::Relaxed),
+        DIAGNOSTICS.decode_errors.load(Ordering::Relaxed),
+        DIAGNOSTICS.notifications_forwarded.load(Ordering::Relaxed),
+        DIAGNOSTICS.notify_failures.load(Ordering::Relaxed),
+        DIAGNOSTICS.page_timeouts.load(Ordering::Relaxed),
+    )?;
+    writeln!(
+        out,
+        "pages: {} mismatched, {} out of order",
+        DIAGNOSTICS.page_mismatches.load(Ordering::Relaxed),
+        DIAGNOSTICS.pages_out_of_order.load(Ordering::Relaxed),
+    )?;
+    let (capturing, captured) = capture::status();
+    writeln!(
+        out,
+        "capture: {}, {} notifications",
+        if capturing { "running" } else { "stopped" },
+        captured
+    )?;
+    Ok(())
+}
+
+fn scan(out: &mut dyn Write) -> Result<(), CommandError> {
+    // The central only scans while it has no radar, the results of the last scan
+    // are listed with their age until it scans again
+    let state = SOURCE_STATE_WATCH.try_get();
+    match state {
+        Some(SourceState::Scanning) => writeln!(out, "scanning")?,
+        Some(SourceState::Connecting) => writeln!(out, "not scanning (connecting)")?,
+        Some(SourceState::Connected) => writeln!(out, "not scanning (connected)")?,
+        _ => writeln!(out, "not scanning")?,
+    }
+    let scanning = state == Some(SourceState::Scanning);
+    let max_age = if scanning {
+        SCAN_RESULT_MAX_AGE
+    } else {
+        Duration::MAX
+    };
+    let results = scan_results(max_age);
+    if results.is_empty() && scanning {
+        writeln!(
+            out,
+            "no radars seen in the last {} s",
+            SCAN_RESULT_MAX_AGE.as_secs()
+        )?;
+    } else if results.is_empty() {
+        writeln!(out, "no radars seen since boot")?;
+    }
+    let now = Instant::now();
+    for result in results.iter() {
+        writeln!(
+            out,
+            "{} {:>4} dBm {:>3} s ago {}",
+            DisplayAddress(&result.address.addr),
+            result.rssi,
+            (now - result.last_seen).as_secs(),
+            result.name
+        )?;
+    }
+    Ok(())
+}
+
+fn bind_address(value: &str) -> Result<Option<Address>, CommandError> {
+    if value == "clear" {
+        return Ok(None);
+    }
+    let addr = parse_address(value).ok_or(CommandError::InvalidArgument("address"))?;
+    // Radars normally use a random static address, unless the scan says otherwise
+    let kind = find_scan_result(&addr).map_or(AddrKind::RANDOM, |result| result.address.kind);
+    Ok(Some(Address { kind, addr }))
+}
+
+fn show_setting(out: &mut dyn Write, settings: &Settings, key: &str) -> Result<(), CommandError> {
+    match key {
+        "target" => writeln!(out, "target = {}", settings.target_name)?,
+        "name" => writeln!(out, "name = {}", settings.proxy_name)?,
+        "bind" => match settings.bound_address {
+            Some(address) => writeln!(out, "bind = {}", DisplayAddress(&address.addr))?,
+            None => writeln!(out, "bind = clear")?,
+        },
+        "profile" => {
+            write!(out, "profile = {} (", settings.filter_profile().name)?;
+            for (index, profile) in FILTER_PROFILES.iter().enumerate() {
+                let separator = if index > 0 { ", " } else { "" };
+                write!(out, "{}{}", separator, profile.name)?;
+            }
+            writeln!(out, ")")?;
+        }
+        "offline_policy" => {
+            write!(out, "offline_policy = {} (", settings.offline_policy.name())?;
+            for (index, policy) in SourceOfflinePolicy::ALL.iter().enumerate() {
+                let separator = if index > 0 { ", " } else { "" };
+                write!(out, "{}{}", separator, policy.name())?;
+            }
+            writeln!(out, ")")?;
+        }
+        "log_level" => writeln!(out, "log_level = {}", settings.log_level)?,
+        "log_modules" => {
+            write!(out, "log_modules =")?;
+            for (module, level) in settings.log_modules.iter() {
+                write!(out, " {}={}", module, level)?;
+            }
+            writeln!(out)?;
+        }
+        "log_rate_limit" => writeln!(
+            out,
+            "log_rate_limit = {}",
+            if settings.log_rate_limit { "on" } else { "off" }
+        )?,
+        _ => return Err(CommandError::InvalidArgument("key")),
+    }
+    Ok(())
+}
+
+fn config_command(out: &mut dyn Write, args: &mut SplitWhitespace) -> Result<(), CommandError> {
+    match argument(args, "get, set or reset")? {
+        "get" => {
+            let settings = settings::get();
+            match args.next() {
+                Some(key) => show_setting(out, &settings, key)?,
+                None => {
+                    for key in SETTING_KEYS {
+                        show_setting(out, &settings, key)?;
+                    }
+                }
+            }
+        }
+        "set" => {
+            let key = argument(args, "key")?;
+            let value = argument(args, "value")?;
+            match key {
+                "target" | "name" => {
+                    let value: String<SETTINGS_NAME_SIZE> = String::try_from(value)
+                        .map_err(|_| CommandError::InvalidArgument("name, too long"))?;
+                    settings::update(|settings| match key {
+                        "target" => settings.target_name = value,
+                        _ => settings.proxy_name = value,
+                    })?;
+                }
+                "bind" => {
+                    let address = bind_address(value)?;
+                    settings::update(|settings| settings.bound_address = address)?;
+                }
+                "profile" => {
+                    let profile = FILTER_PROFILES
+                        .iter()
+                        .position(|profile| profile.name == value)
+                        .ok_or(CommandError::InvalidArgument("profile"))?;
+                    settings::update(|settings| settings.profile = profile)?;
+                }
+                "offline_policy" => {
+                    let policy = SourceOfflinePolicy::ALL
+                        .into_iter()
+                        .find(|policy| policy.name() == value)
+                        .ok_or(CommandError::InvalidArgument("offline policy"))?;
+                    settings::update(|settings| settings.offline_policy = policy)?;
+                }
+                "log_level" => {
+                    let level = parse_level(value)?;
+                    settings::update(|settings| settings.log_level = level)?;
+                }
+                "log_rate_limit" => {
+                    let enabled = parse_switch(value)?;
+                    settings::update(|settings| settings.log_rate_limit = enabled)?;
+                }
+                _ => return Err(CommandError::InvalidArgument("key")),
+            }
+            show_setting(out, &settings::get(), key)?;
+            if !key.starts_with("log_") && !matches!(key, "profile" | "offline_policy") {
+                writeln!(out, "takes effect on the next connection")?;
+            }
+        }
+        "reset" => {
+            settings::reset()?;
+            writeln!(out, "settings restored to defaults")?;
+        }
+        _ => return Err(CommandError::InvalidArgument("config command")),
+    }
+    Ok(())
+}
+
+fn parse_level(value: &str) -> Result<LevelFilter, CommandError> {
+    value
+        .parse()
+        .map_err(|_| CommandError::InvalidArgument("log level"))
+}
+
+fn parse_switch(value: &str) -> Result<bool, CommandError> {
+    match value {
+        "on" => Ok(true),
+        "off" => Ok(false),
+        _ => Err(CommandError::InvalidArgument("value, expected on or off")),
+    }
+}
+
+// Sets the level of a `[Module]` tag, `default` makes it follow the global level again
+fn set_module_level(module: &str, level: &str) -> Result<(), CommandError> {
+    let module: ModuleName =
+        String::try_from(module).map_err(|_| CommandError::InvalidArgument("module name"))?;
+    let level = match level {
+        "default" => None,
+        level => Some(parse_level(level)?),
+    };
+
+    // Changed on a copy, so a rejected change is neither applied nor written to flash
+    let mut modules = settings::get().log_modules;
+    let existing = modules.iter().position(|(name, _)| *name == module);
+    match (existing, level) {
+        (Some(index), Some(level)) => modules[index].1 = level,
+        (Some(index), None) => {
+            modules.remove(index);
+        }
+        (None, Some(level)) => modules
+            .push((module, level))
+            .map_err(|_| CommandError::TooManyModules(LOG_MODULE_FILTERS_MAX))?,
+        (None, None) => return Ok(()),
+    }
+    settings::update(|settings| settings.log_modules = modules)?;
+    Ok(())
+}
+
+fn log_command(out: &mut dyn Write, args: &mut SplitWhitespace) -> Result<(), CommandError> {
+    match argument(args, "level or ratelimit")? {
+        "level" => match (args.next(), args.next()) {
+            (Some(level), None) => {
+                let level = parse_level(level)?;
+                settings::update(|settings| settings.log_level = level)?;
+            }
+            (Some(module), Some(level)) => set_module_level(module, level)?,
+            (None, _) => {}
+        },
+        "ratelimit" => {
+            if let Some(value) = args.next() {
+                let enabled = parse_switch(value)?;
+                settings::update(|settings| settings.log_rate_limit = enabled)?;
+            }
+        }
+        _ => return Err(CommandError::InvalidArgument("log command")),
+    }
+
+    let settings = settings::get();
+    for key in ["log_level", "log_modules", "log_rate_limit"] {
+        show_setting(out, &settings, key)?;
+    }
+    Ok(())
+}
+
+fn capture_command(out: &mut dyn Write, args: &mut SplitWhitespace) -> Result<(), CommandError> {
+    match argument(args, "start, stop or dump")? {
+        "start" => {
+            capture::start();
+            writeln!(out, "capture started")?;
+        }
+        "stop" => {
+            capture::stop();
+            writeln!(
+                out,
+                "capture stopped, {} notifications",
+                capture::status().1
+            )?;
+        }
+        "dump" => capture::dump(out)?,
+        _ => return Err(CommandError::InvalidArgument("capture command")),
+    }
+    Ok(())
+}
+
+fn rides_command(out: &mut dyn Write, args: &mut SplitWhitespace) -> Result<(), CommandError> {
+    let count = match args.next() {
+        Some(count) => count
+            .parse()
+            .map_err(|_| CommandError::InvalidArgument("count"))?,
+        None => RIDES_LIST_COUNT,
+    };
+    match rides::current() {
+        Some(ride) => writeln!(out, "current: {}", ride)?,
+        None => writeln!(out, "current: no client connected")?,
+    }
+    writeln!(out, "{} rides stored", rides::len())?;
+    for age in 0..rides::len().min(count) {
+        if let Some(ride) = rides::read(age) {
+            writeln!(out, "#{} {}", age, ride)?;
+        }
+    }
+    Ok(())
+}
+
+// Runs a command from the GATT config service. Any device in range can write to
+// it, so besides the log levels only commands that read are allowed, other
+// changes need the serial console.
+pub fn execute_gatt(line: &str, out: &mut dyn Write) -> Result<(), CommandError> {
+    let mut args = line.split_whitespace();
+    let allowed = match args.next() {
+        None => true,
+        Some("config") => args.next() == Some("get"),
+        Some(command) => GATT_COMMANDS.contains(&command),
+    };
+    if !allowed {
+        return Err(CommandError::NotOverGatt);
+    }
+    execute(line, out)
+}
+
+pub fn execute(line: &str, out: &mut dyn Write) -> Result<(), CommandError> {
+    let mut args = line.split_whitespace();
+    let Some(command) = args.next() else {
+        return Ok(());
+    };
+
+    match command {
+        "help" => out.write_str(HELP)?,
+        "status" => status(out)?,
+        "scan" => scan(out)?,
+        "bind" => {
+            let address = bind_address(argument(&mut args, "address or clear")?)?;
+            settings::update(|settings| settings.bound_address = address)?;
+            show_setting(out, &settings::get(), "bind")?;
+        }
+        "config" => config_command(out, &mut args)?,
+        "log" => log_command(out, &mut args)?,
+        "capture" => capture_command(out, &mut args)?,
+        "rides" => rides_command(out, &mut args)?,
+        "explore" => {
+            let argument = argument(&mut args, "address or cancel")?;
+            if argument == "cancel" {
+                match EXPLORE_WATCH.try_get().flatten() {
+                    Some(address) => {
+                        EXPLORE_WATCH.sender().send(None);
+                        writeln!(
+                            out,
+                            "stopped exploring {}, returning to the radar",
+                            DisplayAddress(&address)
+                        )?;
+                    }
+                    None => writeln!(out, "not exploring")?,
+                }
+                return Ok(());
+            }
+            let address =
+                parse_address(argument).ok_or(CommandError::InvalidArgument("address"))?;
+            EXPLORE_WATCH.sender().send(Some(address));
+            writeln!(
+                out,
+                "exploring {}, the result is printed on the serial console",
+                DisplayAddress(&address)
+            )?;
+        }
+        "reset" => {
+            writeln!(out, "resetting")?;
+            SYSTEM_REQUEST_SIGNAL.signal(SystemRequest::Reset);
+        }
+        "sleep" => {
+            writeln!(out, "going to sleep, press the button to wake up")?;
+            SYSTEM_REQUEST_SIGNAL.signal(SystemRequest::Sleep);
+        }
+        _ => return Err(CommandError::UnknownCommand),
+    }
+    Ok(())
+}
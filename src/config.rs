@@ -1,11 +1,186 @@
 use embassy_time::Duration;
+use esp_hal::time::Rate;
+use heapless::{String, Vec};
+use magene_protocol::alert::AlertConfig;
+use magene_protocol::animation::RadarIndicationConfig;
+use magene_protocol::filter::FilterRules;
+use magene_protocol::ride::RIDE_STATS_SIZE;
 use trouble_host::prelude::*;
 
+use crate::alert::Beep;
+use crate::event_log::EVENT_SIZE;
+use crate::radar::ThreatLevel;
+
 // Configuration constants
 pub const LOG_LEVEL: log::LevelFilter = log::LevelFilter::Info;
+pub const LOG_RATE_LIMIT: bool = true;
+pub const LOG_RATE_LIMIT_BURST: u32 = 3;
+pub const LOG_RATE_LIMIT_INTERVAL: Duration = Duration::from_secs(10);
+pub const LOG_RATE_LIMIT_SITES: usize = 8;
+pub const LOG_MODULE_FILTERS_MAX: usize = 4;
+pub const LOG_MODULE_NAME_SIZE: usize = 12;
+pub const LOG_LINE_SIZE: usize = 256;
 pub const TARGET_NAME: &str = "34660-5";
+pub const PROXY_NAME: &str = "RadarProxy";
 pub const DISCOVERY_DELAY: Duration = Duration::from_millis(2000);
-pub const RADAR_DATA_PAGE_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Rules for which vehicles are shown, chosen with `config set profile <name>`.
+// The first profile is the default.
+pub struct FilterProfile {
+    pub name: &'static str,
+    pub rules: FilterRules,
+}
+
+pub const FILTER_PROFILES: [FilterProfile; 3] = [
+    FilterProfile {
+        name: "all",
+        rules: FilterRules::NONE,
+    },
+    // City traffic: leave out cyclists and cars crawling along behind, and only
+    // flag vehicles the radar rates medium or higher
+    FilterProfile {
+        name: "commute",
+        rules: FilterRules {
+            min_speed: 10,
+            max_range: 80,
+            speed_hysteresis: 3,
+            range_hysteresis: 10,
+            threat_map: [
+                ThreatLevel::None,
+                ThreatLevel::None,
+                ThreatLevel::Medium,
+                ThreatLevel::High,
+            ],
+        },
+    },
+    // Open roads: everything that approaches, flagged one level earlier
+    FilterProfile {
+        name: "training",
+        rules: FilterRules {
+            min_speed: 3,
+            max_range: u8::MAX,
+            speed_hysteresis: 2,
+            range_hysteresis: 0,
+            threat_map: [
+                ThreatLevel::None,
+                ThreatLevel::Medium,
+                ThreatLevel::High,
+                ThreatLevel::High,
+            ],
+        },
+    },
+];
+pub const DIAGNOSTICS_UPDATE_INTERVAL: Duration = Duration::from_secs(1);
+pub const RSSI_POLL_INTERVAL: Duration = Duration::from_secs(5);
+pub const EVENT_LOG_PARTITION: &str = "eventlog";
+pub const EVENT_LOG_FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+pub const SETTINGS_PARTITION: &str = "settings";
+pub const RIDES_PARTITION: &str = "rides";
+// Rides listed by the `rides` command without a count
+pub const RIDES_LIST_COUNT: u32 = 5;
+// Default of the `offline_policy` setting
+pub const SOURCE_OFFLINE_POLICY: SourceOfflinePolicy = SourceOfflinePolicy::DisconnectClient;
+
+// Serial console and config service
+pub const CONSOLE_LINE_SIZE: usize = 64;
+pub const CONFIG_RESPONSE_SIZE: usize = 256;
+pub const SCAN_RESULTS_MAX: usize = 8;
+pub const SCAN_RESULT_MAX_AGE: Duration = Duration::from_secs(30);
+pub const CAPTURE_SIZE: usize = 64;
+pub const CAPTURE_MAX_NOTIFICATION_SIZE: usize = 20;
+pub const BUILD_INFO_SIZE: usize = 96;
+
+// Firmware update over BLE
+// Ed25519 key that signs update images, set by DFU_PUBLIC_KEY at build time.
+// Updates are refused while it is all zeros.
+pub const DFU_PUBLIC_KEY: [u8; 32] = include!(concat!(env!("OUT_DIR"), "/dfu_public_key.rs"));
+// A new image is confirmed once a radar connection has held this long, until
+// then it is rolled back on the next reset
+pub const DFU_CONFIRM_DELAY: Duration = Duration::from_secs(10);
+// Largest control point write: opcode and signature
+pub const DFU_CONTROL_SIZE: usize = 65;
+// Largest data write: 4 byte offset and image data, fits an ATT MTU of 247
+pub const DFU_DATA_SIZE: usize = 244;
+// Writes waiting for the DFU task, enough for the chunks that arrive while a sector is erased
+pub const DFU_QUEUE_DEPTH: usize = 16;
+
+// GATT explorer, started by the console `explore` command
+pub const EXPLORER_CHARACTERISTICS_MAX: usize = 16;
+pub const EXPLORER_DESCRIPTORS_MAX: usize = 8;
+pub const EXPLORER_VALUE_SIZE: usize = 64;
+pub const EXPLORER_NOTIFY_DURATION: Duration = Duration::from_secs(10);
+// The proxy returns to the radar if the device to explore is not found this long
+pub const EXPLORER_SCAN_TIMEOUT: Duration = Duration::from_secs(30);
+
+// HCI trace, see the hci-trace feature. A packet holds the H4 indicator and at
+// most a full event or LE ACL packet.
+pub const HCI_TRACE_BUFFER_SIZE: usize = 4096;
+pub const HCI_TRACE_PACKET_SIZE: usize = 264;
+
+// Bulk download over an L2CAP channel, the PSM is in the dynamic LE range
+pub const L2CAP_DOWNLOAD_PSM: u16 = 0x0081;
+pub const L2CAP_DOWNLOAD_MTU: usize = 512;
+
+// Status LED
+pub const LED_BRIGHTNESS: u8 = 31;
+pub const LED_DIM_BRIGHTNESS: u8 = 4;
+pub const LED_RADAR: RadarIndicationConfig = RadarIndicationConfig {
+    brightness: LED_BRIGHTNESS,
+    dim_brightness: LED_DIM_BRIGHTNESS,
+    dim_delay: Duration::from_secs(30),
+    max_range: 140,
+    fast_approach_speed: 40,
+};
+
+// LED strip radar display (feature "led-strip")
+pub const LED_STRIP_LENGTH: usize = 16;
+pub const LED_STRIP_BRIGHTNESS: u8 = 31;
+pub const LED_STRIP_MAX_RANGE: u8 = 140;
+// Vehicles drawn at most, the most threatening and closest ones if there are more
+pub const LED_STRIP_MAX_TARGETS: usize = 6;
+
+// Buzzer / vibration alerts on LEDC (feature "alerts")
+pub const ALERT_FREQUENCY: Rate = Rate::from_hz(2700);
+pub const ALERT_DUTY: u8 = 50;
+pub const ALERT_DETECTOR: AlertConfig = AlertConfig {
+    min_interval: Duration::from_secs(3),
+    fast_approach_speed: 40,
+};
+pub const ALERT_NEW_VEHICLE_PATTERN: &[Beep] = &[Beep::new(80, 0)];
+pub const ALERT_FAST_APPROACH_PATTERN: &[Beep] =
+    &[Beep::new(60, 60), Beep::new(60, 60), Beep::new(60, 0)];
+pub const ALERT_VEHICLE_PASSED_PATTERN: &[Beep] = &[Beep::new(30, 80), Beep::new(30, 0)];
+pub const ALERT_HIGH_THREAT_PATTERN: &[Beep] = &[Beep::new(250, 0)];
+
+// Behaviour of the peripheral side while the source radar is unavailable, chosen
+// with `config set offline_policy <name>`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SourceOfflinePolicy {
+    // Always advertise, keep the client connected and notify an offline radar
+    // frame (cycle byte 0xFF). No head unit has been verified to show this as
+    // radar offline yet, it may just look like an empty road, so it is not the default.
+    SignalOffline = 0,
+    // Only advertise while the radar is connected and drop the client when it is lost
+    DisconnectClient = 1,
+    // Only advertise while the radar is connected, but keep an existing client connected
+    StopAdvertising = 2,
+}
+
+impl SourceOfflinePolicy {
+    pub const ALL: [Self; 3] = [
+        Self::SignalOffline,
+        Self::DisconnectClient,
+        Self::StopAdvertising,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::SignalOffline => "signal",
+            Self::DisconnectClient => "disconnect",
+            Self::StopAdvertising => "stop",
+        }
+    }
+}
 
 // Max connections and channels
 pub const CONNECTIONS_MAX: usize = 4;
@@ -21,11 +196,51 @@ pub const TARGET_BATTERY_LEVEL_CHARACTERISTIC: u16 = 0x2A19;
 // 16-bit UUIDs as u16
 pub const BATTERY_SERVICE: u16 = 0x180F;
 pub const BATTERY_LEVEL_CHARACTERISTIC: u16 = 0x2A19;
+pub const CHARACTERISTIC_USER_DESCRIPTION: u16 = 0x2901;
+pub const DEVICE_INFORMATION_SERVICE: u16 = 0x180A;
+pub const FIRMWARE_REVISION_CHARACTERISTIC: u16 = 0x2A26;
+pub const SOFTWARE_REVISION_CHARACTERISTIC: u16 = 0x2A28;
 
 // 128-bit UUIDs as u128
 pub const RADARLIGHT_SERVICE: u128 = 0x8ce5cc010a4d11e9ab14d663bd873d93;
 pub const RADARLIGHT_CHARACTERISTIC: u128 = 0x8ce5cc020a4d11e9ab14d663bd873d93;
 
+// Vendor specific proxy services
+pub const DIAGNOSTICS_SERVICE: u128 = 0x2b5e0100_8a4f_4e8e_9c43_6f0d1c7a1e5f;
+pub const DIAGNOSTICS_UPTIME_CHARACTERISTIC: u128 = 0x2b5e0101_8a4f_4e8e_9c43_6f0d1c7a1e5f;
+pub const DIAGNOSTICS_SOURCE_CONNECTS_CHARACTERISTIC: u128 = 0x2b5e0102_8a4f_4e8e_9c43_6f0d1c7a1e5f;
+pub const DIAGNOSTICS_CLIENT_CONNECTS_CHARACTERISTIC: u128 = 0x2b5e0103_8a4f_4e8e_9c43_6f0d1c7a1e5f;
+pub const DIAGNOSTICS_SOURCE_DISCONNECT_CHARACTERISTIC: u128 =
+    0x2b5e0104_8a4f_4e8e_9c43_6f0d1c7a1e5f;
+pub const DIAGNOSTICS_CLIENT_DISCONNECT_CHARACTERISTIC: u128 =
+    0x2b5e0105_8a4f_4e8e_9c43_6f0d1c7a1e5f;
+pub const DIAGNOSTICS_SOURCE_RSSI_CHARACTERISTIC: u128 = 0x2b5e0106_8a4f_4e8e_9c43_6f0d1c7a1e5f;
+pub const DIAGNOSTICS_CLIENT_RSSI_CHARACTERISTIC: u128 = 0x2b5e0107_8a4f_4e8e_9c43_6f0d1c7a1e5f;
+pub const DIAGNOSTICS_RECEIVED_CHARACTERISTIC: u128 = 0x2b5e0108_8a4f_4e8e_9c43_6f0d1c7a1e5f;
+pub const DIAGNOSTICS_FORWARDED_CHARACTERISTIC: u128 = 0x2b5e0109_8a4f_4e8e_9c43_6f0d1c7a1e5f;
+pub const DIAGNOSTICS_DECODE_ERRORS_CHARACTERISTIC: u128 = 0x2b5e010a_8a4f_4e8e_9c43_6f0d1c7a1e5f;
+pub const DIAGNOSTICS_PAGE_TIMEOUTS_CHARACTERISTIC: u128 = 0x2b5e010b_8a4f_4e8e_9c43_6f0d1c7a1e5f;
+pub const DIAGNOSTICS_HEAP_USED_CHARACTERISTIC: u128 = 0x2b5e010c_8a4f_4e8e_9c43_6f0d1c7a1e5f;
+pub const DIAGNOSTICS_HEAP_FREE_CHARACTERISTIC: u128 = 0x2b5e010d_8a4f_4e8e_9c43_6f0d1c7a1e5f;
+pub const DIAGNOSTICS_BUILD_INFO_CHARACTERISTIC: u128 = 0x2b5e010e_8a4f_4e8e_9c43_6f0d1c7a1e5f;
+pub const DIAGNOSTICS_PAGE_MISMATCHES_CHARACTERISTIC: u128 = 0x2b5e010f_8a4f_4e8e_9c43_6f0d1c7a1e5f;
+pub const DIAGNOSTICS_PAGES_OUT_OF_ORDER_CHARACTERISTIC: u128 =
+    0x2b5e0110_8a4f_4e8e_9c43_6f0d1c7a1e5f;
+pub const DIAGNOSTICS_NOTIFY_FAILURES_CHARACTERISTIC: u128 = 0x2b5e0111_8a4f_4e8e_9c43_6f0d1c7a1e5f;
+pub const EVENT_LOG_SERVICE: u128 = 0x2b5e0200_8a4f_4e8e_9c43_6f0d1c7a1e5f;
+pub const EVENT_LOG_INDEX_CHARACTERISTIC: u128 = 0x2b5e0201_8a4f_4e8e_9c43_6f0d1c7a1e5f;
+pub const EVENT_LOG_RECORD_CHARACTERISTIC: u128 = 0x2b5e0202_8a4f_4e8e_9c43_6f0d1c7a1e5f;
+pub const CONFIG_SERVICE: u128 = 0x2b5e0300_8a4f_4e8e_9c43_6f0d1c7a1e5f;
+pub const CONFIG_COMMAND_CHARACTERISTIC: u128 = 0x2b5e0301_8a4f_4e8e_9c43_6f0d1c7a1e5f;
+pub const CONFIG_RESPONSE_CHARACTERISTIC: u128 = 0x2b5e0302_8a4f_4e8e_9c43_6f0d1c7a1e5f;
+pub const DFU_SERVICE: u128 = 0x2b5e0400_8a4f_4e8e_9c43_6f0d1c7a1e5f;
+pub const DFU_CONTROL_CHARACTERISTIC: u128 = 0x2b5e0401_8a4f_4e8e_9c43_6f0d1c7a1e5f;
+pub const DFU_DATA_CHARACTERISTIC: u128 = 0x2b5e0402_8a4f_4e8e_9c43_6f0d1c7a1e5f;
+pub const DFU_STATUS_CHARACTERISTIC: u128 = 0x2b5e0403_8a4f_4e8e_9c43_6f0d1c7a1e5f;
+pub const RIDES_SERVICE: u128 = 0x2b5e0500_8a4f_4e8e_9c43_6f0d1c7a1e5f;
+pub const RIDES_INDEX_CHARACTERISTIC: u128 = 0x2b5e0501_8a4f_4e8e_9c43_6f0d1c7a1e5f;
+pub const RIDES_RECORD_CHARACTERISTIC: u128 = 0x2b5e0502_8a4f_4e8e_9c43_6f0d1c7a1e5f;
+
 // Magic bytes for radar activation
 pub const RADAR_ACTIVATION_BYTES: [u8; 3] = [0x57, 0x09, 0x01];
 
@@ -43,8 +258,122 @@ pub struct BatteryService {
     pub battery_level: [u8; 1],
 }
 
+#[gatt_service(uuid = DIAGNOSTICS_SERVICE.to_le_bytes())]
+pub struct DiagnosticsService {
+    #[descriptor(uuid = CHARACTERISTIC_USER_DESCRIPTION.to_le_bytes(), read, value = "Uptime (s)")]
+    #[characteristic(uuid = DIAGNOSTICS_UPTIME_CHARACTERISTIC.to_le_bytes(), read)]
+    pub uptime: u32,
+    #[descriptor(uuid = CHARACTERISTIC_USER_DESCRIPTION.to_le_bytes(), read, value = "Source connects")]
+    #[characteristic(uuid = DIAGNOSTICS_SOURCE_CONNECTS_CHARACTERISTIC.to_le_bytes(), read)]
+    pub source_connects: u32,
+    #[descriptor(uuid = CHARACTERISTIC_USER_DESCRIPTION.to_le_bytes(), read, value = "Client connects")]
+    #[characteristic(uuid = DIAGNOSTICS_CLIENT_CONNECTS_CHARACTERISTIC.to_le_bytes(), read)]
+    pub client_connects: u32,
+    #[descriptor(uuid = CHARACTERISTIC_USER_DESCRIPTION.to_le_bytes(), read, value = "Source disconnect reason")]
+    #[characteristic(uuid = DIAGNOSTICS_SOURCE_DISCONNECT_CHARACTERISTIC.to_le_bytes(), read)]
+    pub source_disconnect_reason: u8,
+    #[descriptor(uuid = CHARACTERISTIC_USER_DESCRIPTION.to_le_bytes(), read, value = "Client disconnect reason")]
+    #[characteristic(uuid = DIAGNOSTICS_CLIENT_DISCONNECT_CHARACTERISTIC.to_le_bytes(), read)]
+    pub client_disconnect_reason: u8,
+    #[descriptor(uuid = CHARACTERISTIC_USER_DESCRIPTION.to_le_bytes(), read, value = "Source RSSI (dBm)")]
+    #[characteristic(uuid = DIAGNOSTICS_SOURCE_RSSI_CHARACTERISTIC.to_le_bytes(), read)]
+    pub source_rssi: i8,
+    #[descriptor(uuid = CHARACTERISTIC_USER_DESCRIPTION.to_le_bytes(), read, value = "Client RSSI (dBm)")]
+    #[characteristic(uuid = DIAGNOSTICS_CLIENT_RSSI_CHARACTERISTIC.to_le_bytes(), read)]
+    pub client_rssi: i8,
+    #[descriptor(uuid = CHARACTERISTIC_USER_DESCRIPTION.to_le_bytes(), read, value = "Notifications received")]
+    #[characteristic(uuid = DIAGNOSTICS_RECEIVED_CHARACTERISTIC.to_le_bytes(), read)]
+    pub notifications_received: u32,
+    #[descriptor(uuid = CHARACTERISTIC_USER_DESCRIPTION.to_le_bytes(), read, value = "Notifications forwarded")]
+    #[characteristic(uuid = DIAGNOSTICS_FORWARDED_CHARACTERISTIC.to_le_bytes(), read)]
+    pub notifications_forwarded: u32,
+    #[descriptor(uuid = CHARACTERISTIC_USER_DESCRIPTION.to_le_bytes(), read, value = "Radar decode errors")]
+    #[characteristic(uuid = DIAGNOSTICS_DECODE_ERRORS_CHARACTERISTIC.to_le_bytes(), read)]
+    pub decode_errors: u32,
+    #[descriptor(uuid = CHARACTERISTIC_USER_DESCRIPTION.to_le_bytes(), read, value = "Page timeouts")]
+    #[characteristic(uuid = DIAGNOSTICS_PAGE_TIMEOUTS_CHARACTERISTIC.to_le_bytes(), read)]
+    pub page_timeouts: u32,
+    #[descriptor(uuid = CHARACTERISTIC_USER_DESCRIPTION.to_le_bytes(), read, value = "Heap used (bytes)")]
+    #[characteristic(uuid = DIAGNOSTICS_HEAP_USED_CHARACTERISTIC.to_le_bytes(), read)]
+    pub heap_used: u32,
+    #[descriptor(uuid = CHARACTERISTIC_USER_DESCRIPTION.to_le_bytes(), read, value = "Heap free (bytes)")]
+    #[characteristic(uuid = DIAGNOSTICS_HEAP_FREE_CHARACTERISTIC.to_le_bytes(), read)]
+    pub heap_free: u32,
+    #[descriptor(uuid = CHARACTERISTIC_USER_DESCRIPTION.to_le_bytes(), read, value = "Build info")]
+    #[characteristic(uuid = DIAGNOSTICS_BUILD_INFO_CHARACTERISTIC.to_le_bytes(), read)]
+    pub build_info: String<BUILD_INFO_SIZE>,
+    #[descriptor(uuid = CHARACTERISTIC_USER_DESCRIPTION.to_le_bytes(), read, value = "Page mismatches")]
+    #[characteristic(uuid = DIAGNOSTICS_PAGE_MISMATCHES_CHARACTERISTIC.to_le_bytes(), read)]
+    pub page_mismatches: u32,
+    #[descriptor(uuid = CHARACTERISTIC_USER_DESCRIPTION.to_le_bytes(), read, value = "Pages out of order")]
+    #[characteristic(uuid = DIAGNOSTICS_PAGES_OUT_OF_ORDER_CHARACTERISTIC.to_le_bytes(), read)]
+    pub pages_out_of_order: u32,
+    #[descriptor(uuid = CHARACTERISTIC_USER_DESCRIPTION.to_le_bytes(), read, value = "Client notify failures")]
+    #[characteristic(uuid = DIAGNOSTICS_NOTIFY_FAILURES_CHARACTERISTIC.to_le_bytes(), read)]
+    pub notify_failures: u32,
+}
+
+#[gatt_service(uuid = DEVICE_INFORMATION_SERVICE.to_le_bytes())]
+pub struct DeviceInformationService {
+    #[characteristic(uuid = FIRMWARE_REVISION_CHARACTERISTIC.to_le_bytes(), read)]
+    pub firmware_revision: String<BUILD_INFO_SIZE>,
+    #[characteristic(uuid = SOFTWARE_REVISION_CHARACTERISTIC.to_le_bytes(), read)]
+    pub software_revision: String<BUILD_INFO_SIZE>,
+}
+
+#[gatt_service(uuid = EVENT_LOG_SERVICE.to_le_bytes())]
+pub struct EventLogService {
+    #[descriptor(uuid = CHARACTERISTIC_USER_DESCRIPTION.to_le_bytes(), read, value = "Event index (0 = newest)")]
+    #[characteristic(uuid = EVENT_LOG_INDEX_CHARACTERISTIC.to_le_bytes(), read, write)]
+    pub index: u16,
+    #[descriptor(uuid = CHARACTERISTIC_USER_DESCRIPTION.to_le_bytes(), read, value = "Event record")]
+    #[characteristic(uuid = EVENT_LOG_RECORD_CHARACTERISTIC.to_le_bytes(), read)]
+    pub record: [u8; EVENT_SIZE],
+}
+
+// Runs the read-only console commands over BLE: write a command line, then read the response
+#[gatt_service(uuid = CONFIG_SERVICE.to_le_bytes())]
+pub struct ConfigService {
+    #[descriptor(uuid = CHARACTERISTIC_USER_DESCRIPTION.to_le_bytes(), read, value = "Command")]
+    #[characteristic(uuid = CONFIG_COMMAND_CHARACTERISTIC.to_le_bytes(), write)]
+    pub command: Vec<u8, CONSOLE_LINE_SIZE>,
+    #[descriptor(uuid = CHARACTERISTIC_USER_DESCRIPTION.to_le_bytes(), read, value = "Response")]
+    #[characteristic(uuid = CONFIG_RESPONSE_CHARACTERISTIC.to_le_bytes(), read)]
+    pub response: Vec<u8, CONFIG_RESPONSE_SIZE>,
+}
+
+// Firmware update: start with the control point, write the image in chunks, then finish
+#[gatt_service(uuid = DFU_SERVICE.to_le_bytes())]
+pub struct DfuService {
+    #[descriptor(uuid = CHARACTERISTIC_USER_DESCRIPTION.to_le_bytes(), read, value = "Control")]
+    #[characteristic(uuid = DFU_CONTROL_CHARACTERISTIC.to_le_bytes(), write)]
+    pub control: Vec<u8, DFU_CONTROL_SIZE>,
+    #[descriptor(uuid = CHARACTERISTIC_USER_DESCRIPTION.to_le_bytes(), read, value = "Data")]
+    #[characteristic(uuid = DFU_DATA_CHARACTERISTIC.to_le_bytes(), write, write_without_response)]
+    pub data: Vec<u8, DFU_DATA_SIZE>,
+    #[descriptor(uuid = CHARACTERISTIC_USER_DESCRIPTION.to_le_bytes(), read, value = "Status")]
+    #[characteristic(uuid = DFU_STATUS_CHARACTERISTIC.to_le_bytes(), read)]
+    pub status: [u8; 6],
+}
+
+#[gatt_service(uuid = RIDES_SERVICE.to_le_bytes())]
+pub struct RidesService {
+    #[descriptor(uuid = CHARACTERISTIC_USER_DESCRIPTION.to_le_bytes(), read, value = "Ride index (0 = newest)")]
+    #[characteristic(uuid = RIDES_INDEX_CHARACTERISTIC.to_le_bytes(), read, write)]
+    pub index: u16,
+    #[descriptor(uuid = CHARACTERISTIC_USER_DESCRIPTION.to_le_bytes(), read, value = "Ride statistics")]
+    #[characteristic(uuid = RIDES_RECORD_CHARACTERISTIC.to_le_bytes(), read)]
+    pub record: [u8; RIDE_STATS_SIZE],
+}
+
 #[gatt_server]
 pub struct Server {
     pub radar_service: RadarService,
     pub battery_service: BatteryService,
+    pub device_information_service: DeviceInformationService,
+    pub diagnostics_service: DiagnosticsService,
+    pub event_log_service: EventLogService,
+    pub config_service: ConfigService,
+    pub dfu_service: DfuService,
+    pub rides_service: RidesService,
 }
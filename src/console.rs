@@ -0,0 +1,78 @@
+use core::fmt;
+
+use embedded_io_async::Read;
+use esp_hal::usb_serial_jtag::UsbSerialJtagRx;
+use esp_hal::Async;
+use esp_println::{print, println};
+use heapless::String;
+
+use crate::command;
+use crate::config::CONSOLE_LINE_SIZE;
+use crate::fmt::*;
+
+const PROMPT: &str = "> ";
+
+// Console output goes through esp-println, so it does not fight the logger over the USB port
+struct ConsoleWriter;
+
+impl fmt::Write for ConsoleWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        print!("{}", s);
+        Ok(())
+    }
+}
+
+fn run(line: &str) {
+    if let Err(e) = command::execute(line, &mut ConsoleWriter) {
+        println!("error: {}", e);
+    }
+}
+
+// Line based command console on the USB-Serial-JTAG port
+pub async fn console_task(mut rx: UsbSerialJtagRx<'_, Async>) {
+    let mut line: String<CONSOLE_LINE_SIZE> = String::new();
+    let mut buffer = [0u8; 16];
+    let mut previous = 0u8;
+
+    info!("[Console] Ready, type 'help' for a list of commands");
+    loop {
+        let count = match rx.read(&mut buffer).await {
+            Ok(count) => count,
+            Err(e) => {
+                warn!(
+                    "[Console] Could not read from USB serial: {:?}",
+                    Debug2Format(&e)
+                );
+                continue;
+            }
+        };
+
+        for &byte in &buffer[..count] {
+            match byte {
+                // Terminals sending CR LF only end the line once
+                b'\n' if previous == b'\r' => {}
+                b'\r' | b'\n' => {
+                    println!();
+                    if !line.is_empty() {
+                        run(&line);
+                        line.clear();
+                    }
+                    print!("{}", PROMPT);
+                }
+                // Backspace and delete
+                0x08 | 0x7f => {
+                    if line.pop().is_some() {
+                        print!("\x08 \x08");
+                    }
+                }
+                byte if byte.is_ascii_graphic() || byte == b' ' => {
+                    if line.push(byte as char).is_ok() {
+                        print!("{}", byte as char);
+                    }
+                }
+                _ => {}
+            }
+            previous = byte;
+        }
+    }
+}
@@ -0,0 +1,322 @@
+use core::cell::RefCell;
+
+use embassy_futures::join::join;
+use embassy_futures::select::{select, Either};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_time::Timer;
+use esp_bootloader_esp_idf::ota::{Ota, OtaImageState, Slot};
+use esp_bootloader_esp_idf::partitions::{self, DataPartitionSubType, PartitionType};
+use esp_storage::FlashStorage;
+use heapless::Vec;
+use magene_protocol::dfu::{decode_chunk, Command, Update};
+
+use crate::config::{DFU_CONFIRM_DELAY, DFU_PUBLIC_KEY};
+use crate::errors::DfuError;
+use crate::event_log::{self, EventKind};
+use crate::fmt::*;
+use crate::messages::{
+    DfuRequest, SourceState, SystemRequest, DFU_REQUEST_CHANNEL, SOURCE_STATE_WATCH,
+    SYSTEM_REQUEST_SIGNAL,
+};
+use crate::storage;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DfuState {
+    Idle = 0,
+    Receiving = 1,
+    Complete = 2,
+    Failed = 3,
+}
+
+// Progress shown by the status characteristic. The update itself is owned by
+// dfu_task, so flash is never written from the GATT handler or under this lock.
+struct Dfu {
+    state: DfuState,
+    error: Option<DfuError>,
+    received: u32,
+}
+
+static DFU: Mutex<CriticalSectionRawMutex, RefCell<Dfu>> = Mutex::new(RefCell::new(Dfu {
+    state: DfuState::Idle,
+    error: None,
+    received: 0,
+}));
+
+// Runs `f` on the OTA data partition, which tells the bootloader which slot to boot
+fn with_ota<R>(
+    f: impl FnOnce(&mut Ota<'_, FlashStorage>) -> Result<R, partitions::Error>,
+) -> Result<R, DfuError> {
+    storage::with_partition_table(|table, flash| {
+        let ota_data = table
+            .find_partition(PartitionType::Data(DataPartitionSubType::Ota))
+            .map_err(|_| DfuError::OtaDataError)?
+            .ok_or(DfuError::PartitionNotFound("otadata"))?;
+        let mut ota_data = ota_data.as_embedded_storage(flash);
+        let mut ota = Ota::new(&mut ota_data).map_err(|_| DfuError::OtaDataError)?;
+        f(&mut ota).map_err(|_| DfuError::OtaDataError)
+    })
+    .ok_or(DfuError::OtaDataError)?
+}
+
+// Without a factory partition the bootloader runs ota_0 until a slot is selected
+fn running_slot() -> Result<Slot, DfuError> {
+    match with_ota(|ota| ota.current_slot())? {
+        Slot::Slot1 => Ok(Slot::Slot1),
+        _ => Ok(Slot::Slot0),
+    }
+}
+
+fn slot_label(slot: Slot) -> &'static str {
+    match slot {
+        Slot::Slot1 => "ota_1",
+        _ => "ota_0",
+    }
+}
+
+fn other_slot(slot: Slot) -> Slot {
+    match slot {
+        Slot::Slot1 => Slot::Slot0,
+        _ => Slot::Slot1,
+    }
+}
+
+// Begins an update into the slot that is not running, or finishes it and makes
+// the bootloader start the new image on the next reset. Returns the state the
+// update is in afterwards.
+fn control_command(
+    update: &mut Update,
+    slot: &mut Slot,
+    command: &[u8],
+) -> Result<DfuState, DfuError> {
+    match Command::decode(command).ok_or(DfuError::InvalidCommand)? {
+        Command::Start { size, digest } => {
+            *slot = other_slot(running_slot()?);
+            let partition = storage::find_partition(slot_label(*slot))?;
+            update.start(partition, size, digest)?;
+            info!("[DFU] Receiving {} byte image", size);
+            Ok(DfuState::Receiving)
+        }
+        Command::Signature(signature) => {
+            update.set_signature(signature)?;
+            Ok(DfuState::Receiving)
+        }
+        Command::Finish => {
+            update.finish(&mut FlashStorage::new())?;
+            let slot = *slot;
+            with_ota(|ota| {
+                ota.set_current_slot(slot)?;
+                ota.set_current_ota_state(OtaImageState::New)
+            })?;
+            info!(
+                "[DFU] Image verified, booting {} after reset",
+                slot_label(slot)
+            );
+            event_log::record(
+                EventKind::Firmware,
+                format_args!("update written to {}", slot_label(slot)),
+            );
+            SYSTEM_REQUEST_SIGNAL.signal(SystemRequest::Reset);
+            Ok(DfuState::Complete)
+        }
+        Command::Abort => {
+            update.abort();
+            info!("[DFU] Update aborted");
+            Ok(DfuState::Idle)
+        }
+    }
+}
+
+fn fail(dfu: &mut Dfu, error: DfuError) {
+    warn!("[DFU] Update failed: {}", Display2Format(&error));
+    event_log::record(
+        EventKind::Firmware,
+        format_args!("update failed: {}", error),
+    );
+    dfu.state = DfuState::Failed;
+    dfu.error = Some(error);
+}
+
+// Handles a control point write, in the order it was queued
+fn control(update: &mut Update, slot: &mut Slot, command: &[u8]) {
+    let result = control_command(update, slot, command);
+    DFU.lock(|dfu| {
+        let mut dfu = dfu.borrow_mut();
+        match result {
+            Ok(state) => {
+                dfu.error = None;
+                // A finished update keeps showing the size of the image
+                if state != DfuState::Complete {
+                    dfu.received = update.received();
+                }
+                dfu.state = state;
+            }
+            Err(e) => {
+                update.abort();
+                fail(&mut dfu, e);
+            }
+        }
+    });
+}
+
+// Handles a data write: [offset: u32 LE][image data]
+fn data(update: &mut Update, chunk: &[u8]) {
+    if !update.is_started() {
+        return;
+    }
+    let result = match decode_chunk(chunk) {
+        Some((offset, data)) => update
+            .append(&mut FlashStorage::new(), offset, data)
+            .map_err(DfuError::from),
+        None => Err(DfuError::InvalidCommand),
+    };
+    let received = update.received();
+    DFU.lock(|dfu| {
+        let mut dfu = dfu.borrow_mut();
+        match result {
+            Ok(()) => dfu.received = received,
+            // A lost chunk is reported and can be resent from the received offset
+            Err(e @ DfuError::UnexpectedOffset(..)) => {
+                debug!("[DFU] {}", Display2Format(&e));
+                dfu.error = Some(e);
+            }
+            Err(e) => {
+                update.abort();
+                fail(&mut dfu, e);
+            }
+        }
+    });
+}
+
+// Queues a write for dfu_task. A full queue drops it and reports Busy, the
+// next chunk then shows where to resume.
+fn queue(request: DfuRequest) {
+    if DFU_REQUEST_CHANNEL.try_send(request).is_err() {
+        DFU.lock(|dfu| dfu.borrow_mut().error = Some(DfuError::Busy));
+    }
+}
+
+// Handles a write to the control point characteristic
+pub fn queue_control(command: &[u8]) {
+    queue(DfuRequest::Control(
+        Vec::from_slice(command).unwrap_or_default(),
+    ));
+}
+
+// Handles a write to the data characteristic
+pub fn queue_data(chunk: &[u8]) {
+    queue(DfuRequest::Data(Vec::from_slice(chunk).unwrap_or_default()));
+}
+
+// Status characteristic value: [state][error code][bytes received: u32 LE]
+pub fn status() -> [u8; 6] {
+    DFU.lock(|dfu| {
+        let dfu = dfu.borrow();
+        let mut status = [0u8; 6];
+        status[0] = dfu.state as u8;
+        status[1] = dfu.error.as_ref().map_or(0, DfuError::code);
+        status[2..].copy_from_slice(&dfu.received.to_le_bytes());
+        status
+    })
+}
+
+// Called at boot. A new image gets one trial boot, if it resets again before it
+// confirmed itself the previous image is restored.
+pub fn init() {
+    if DFU_PUBLIC_KEY == [0u8; 32] {
+        warn!("[DFU] Built without DFU_PUBLIC_KEY, updates over BLE are refused");
+    }
+
+    let state = match with_ota(|ota| ota.current_ota_state()) {
+        Ok(state) => state,
+        Err(e) => {
+            warn!("[DFU] Could not read OTA state: {}", Display2Format(&e));
+            return;
+        }
+    };
+
+    match state {
+        OtaImageState::New => {
+            info!("[DFU] First boot of a new image, waiting for confirmation");
+            if let Err(e) = with_ota(|ota| ota.set_current_ota_state(OtaImageState::PendingVerify))
+            {
+                warn!(
+                    "[DFU] Could not mark image as pending: {}",
+                    Display2Format(&e)
+                );
+            }
+        }
+        OtaImageState::PendingVerify => {
+            error!("[DFU] New image was not confirmed, rolling back");
+            let result = running_slot().and_then(|slot| {
+                with_ota(|ota| {
+                    ota.set_current_ota_state(OtaImageState::Invalid)?;
+                    ota.set_current_slot(other_slot(slot))?;
+                    ota.set_current_ota_state(OtaImageState::Valid)
+                })
+            });
+            match result {
+                Ok(()) => {
+                    event_log::record(EventKind::Firmware, format_args!("rolled back"));
+                    event_log::flush();
+                    esp_hal::system::software_reset();
+                }
+                Err(e) => error!("[DFU] Rollback failed: {}", Display2Format(&e)),
+            }
+        }
+        _ => {}
+    }
+}
+
+// Carries out the queued writes. Erasing and writing the image happens here,
+// outside of any critical section.
+async fn transfer_task() {
+    let mut update = Update::new(DFU_PUBLIC_KEY);
+    let mut slot = Slot::Slot0;
+    loop {
+        match DFU_REQUEST_CHANNEL.receive().await {
+            DfuRequest::Control(command) => control(&mut update, &mut slot, &command),
+            DfuRequest::Data(chunk) => data(&mut update, &chunk),
+        }
+    }
+}
+
+// A new image is confirmed once it has held a radar connection for
+// DFU_CONFIRM_DELAY, which shows that it can do its job. Until then it is
+// rolled back on the next reset.
+async fn confirm_task() {
+    match with_ota(|ota| ota.current_ota_state()) {
+        Ok(OtaImageState::PendingVerify) => {}
+        Ok(_) => return,
+        Err(e) => {
+            warn!("[DFU] Could not read OTA state: {}", Display2Format(&e));
+            return;
+        }
+    }
+
+    let mut receiver = SOURCE_STATE_WATCH
+        .receiver()
+        .expect("[DFU] Source Watch receiver returned None - watch not initialized");
+    info!("[DFU] New image is confirmed once the radar connection holds");
+    loop {
+        receiver
+            .get_and(|&state| state == SourceState::Connected)
+            .await;
+        let lost = receiver.changed_and(|&state| state != SourceState::Connected);
+        if let Either::First(_) = select(Timer::after(DFU_CONFIRM_DELAY), lost).await {
+            break;
+        }
+    }
+
+    match with_ota(|ota| ota.set_current_ota_state(OtaImageState::Valid)) {
+        Ok(()) => {
+            info!("[DFU] New image confirmed");
+            event_log::record(EventKind::Firmware, format_args!("update confirmed"));
+        }
+        Err(e) => error!("[DFU] Could not confirm image: {}", Display2Format(&e)),
+    }
+}
+
+pub async fn dfu_task() {
+    join(transfer_task(), confirm_task()).await;
+}
@@ -0,0 +1,134 @@
+use core::sync::atomic::{AtomicI8, AtomicU32, AtomicU8, Ordering};
+
+use embassy_time::{Instant, Timer};
+
+use crate::config::{Server, DIAGNOSTICS_UPDATE_INTERVAL};
+use crate::fmt::*;
+
+// Link and pipeline counters, shared by the central and peripheral tasks
+pub struct Diagnostics {
+    pub source_connects: AtomicU32,
+    pub client_connects: AtomicU32,
+    pub source_disconnect_reason: AtomicU8,
+    pub client_disconnect_reason: AtomicU8,
+    pub source_rssi: AtomicI8,
+    pub client_rssi: AtomicI8,
+    pub notifications_received: AtomicU32,
+    pub notifications_forwarded: AtomicU32,
+    // Radar notifications the Magene decoder rejected
+    pub decode_errors: AtomicU32,
+    // Frames that could not be notified to the client
+    pub notify_failures: AtomicU32,
+    pub page_timeouts: AtomicU32,
+    pub page_mismatches: AtomicU32,
+    pub pages_out_of_order: AtomicU32,
+}
+
+pub static DIAGNOSTICS: Diagnostics = Diagnostics::new();
+
+// RSSI value reported while no link is established
+pub const RSSI_UNAVAILABLE: i8 = i8::MIN;
+
+impl Diagnostics {
+    const fn new() -> Self {
+        Self {
+            source_connects: AtomicU32::new(0),
+            client_connects: AtomicU32::new(0),
+            source_disconnect_reason: AtomicU8::new(0),
+            client_disconnect_reason: AtomicU8::new(0),
+            source_rssi: AtomicI8::new(RSSI_UNAVAILABLE),
+            client_rssi: AtomicI8::new(RSSI_UNAVAILABLE),
+            notifications_received: AtomicU32::new(0),
+            notifications_forwarded: AtomicU32::new(0),
+            decode_errors: AtomicU32::new(0),
+            notify_failures: AtomicU32::new(0),
+            page_timeouts: AtomicU32::new(0),
+            page_mismatches: AtomicU32::new(0),
+            pages_out_of_order: AtomicU32::new(0),
+        }
+    }
+
+    pub fn increment(counter: &AtomicU32) {
+        Self::add(counter, 1);
+    }
+
+    pub fn add(counter: &AtomicU32, amount: u32) {
+        counter.fetch_add(amount, Ordering::Relaxed);
+    }
+}
+
+pub async fn diagnostics_task(server: &Server<'_>) {
+    let diagnostics = &server.diagnostics_service;
+
+    loop {
+        let uptime = Instant::now().as_secs() as u32;
+        let results = [
+            server.set(&diagnostics.uptime, &uptime),
+            server.set(
+                &diagnostics.source_connects,
+                &DIAGNOSTICS.source_connects.load(Ordering::Relaxed),
+            ),
+            server.set(
+                &diagnostics.client_connects,
+                &DIAGNOSTICS.client_connects.load(Ordering::Relaxed),
+            ),
+            server.set(
+                &diagnostics.source_disconnect_reason,
+                &DIAGNOSTICS.source_disconnect_reason.load(Ordering::Relaxed),
+            ),
+            server.set(
+                &diagnostics.client_disconnect_reason,
+                &DIAGNOSTICS.client_disconnect_reason.load(Ordering::Relaxed),
+            ),
+            server.set(
+                &diagnostics.source_rssi,
+                &DIAGNOSTICS.source_rssi.load(Ordering::Relaxed),
+            ),
+            server.set(
+                &diagnostics.client_rssi,
+                &DIAGNOSTICS.client_rssi.load(Ordering::Relaxed),
+            ),
+            server.set(
+                &diagnostics.notifications_received,
+                &DIAGNOSTICS.notifications_received.load(Ordering::Relaxed),
+            ),
+            server.set(
+                &diagnostics.notifications_forwarded,
+                &DIAGNOSTICS.notifications_forwarded.load(Ordering::Relaxed),
+            ),
+            server.set(
+                &diagnostics.decode_errors,
+                &DIAGNOSTICS.decode_errors.load(Ordering::Relaxed),
+            ),
+            server.set(
+                &diagnostics.page_timeouts,
+                &DIAGNOSTICS.page_timeouts.load(Ordering::Relaxed),
+            ),
+            server.set(
+                &diagnostics.page_mismatches,
+                &DIAGNOSTICS.page_mismatches.load(Ordering::Relaxed),
+            ),
+            server.set(
+                &diagnostics.pages_out_of_order,
+                &DIAGNOSTICS.pages_out_of_order.load(Ordering::Relaxed),
+            ),
+            server.set(
+                &diagnostics.notify_failures,
+                &DIAGNOSTICS.notify_failures.load(Ordering::Relaxed),
+            ),
+            server.set(&diagnostics.heap_used, &(esp_alloc::HEAP.used() as u32)),
+            server.set(&diagnostics.heap_free, &(esp_alloc::HEAP.free() as u32)),
+        ];
+
+        for result in results {
+            if let Err(e) = result {
+                warn!(
+                    "[Diagnostics] Could not update characteristic: {:?}",
+                    Debug2Format(&e)
+                );
+            }
+        }
+
+        Timer::after(DIAGNOSTICS_UPDATE_INTERVAL).await;
+    }
+}
@@ -1,3 +1,6 @@
+use embedded_storage::nor_flash::{NorFlashError, NorFlashErrorKind};
+use esp_storage::FlashStorageError;
+use magene_protocol::dfu::UpdateError;
 use thiserror::Error;
 use trouble_host::{BleHostError, Error};
 
@@ -42,3 +45,135 @@ where
     #[error("Failed to create connection: {0:?}")]
     GattConnectionError(Error),
 }
+
+#[derive(Error, Debug)]
+pub enum StorageError {
+    #[error("Partition {0} not found")]
+    PartitionNotFound(&'static str),
+
+    #[error("Flash access failed: {0:?}")]
+    FlashError(FlashStorageError),
+}
+
+#[derive(Error, Debug)]
+pub enum CommandError {
+    #[error("Unknown command, try 'help'")]
+    UnknownCommand,
+
+    #[error(
+        "Only status, config get, rides and log are available over BLE, use the serial console"
+    )]
+    NotOverGatt,
+
+    #[error("Missing argument: {0}")]
+    MissingArgument(&'static str),
+
+    #[error("Invalid {0}")]
+    InvalidArgument(&'static str),
+
+    #[error("At most {0} modules can have their own log level")]
+    TooManyModules(usize),
+
+    #[error("{0}")]
+    StorageError(#[from] StorageError),
+
+    #[error("Could not write response")]
+    OutputError(#[from] core::fmt::Error),
+}
+
+#[derive(Error, Debug)]
+pub enum DfuError {
+    #[error("Invalid DFU command")]
+    InvalidCommand,
+
+    #[error("No update in progress")]
+    NotStarted,
+
+    #[error("No DFU public key configured")]
+    NoPublicKey,
+
+    #[error("Partition {0} not found")]
+    PartitionNotFound(&'static str),
+
+    #[error("Could not access OTA data")]
+    OtaDataError,
+
+    #[error("Image size {0} exceeds {1} bytes")]
+    ImageTooLarge(u32, u32),
+
+    #[error("Unexpected offset {0}, expected {1}")]
+    UnexpectedOffset(u32, u32),
+
+    #[error("Not an application image")]
+    InvalidImage,
+
+    #[error("Image incomplete, {0} of {1} bytes received")]
+    IncompleteImage(u32, u32),
+
+    #[error("Image digest does not match")]
+    DigestMismatch,
+
+    #[error("Image signature missing")]
+    MissingSignature,
+
+    #[error("Image signature invalid")]
+    InvalidSignature,
+
+    #[error("Could not write image to flash: {0:?}")]
+    FlashError(NorFlashErrorKind),
+
+    #[error("Update queue full, resend from the received offset")]
+    Busy,
+}
+
+impl From<StorageError> for DfuError {
+    fn from(error: StorageError) -> Self {
+        match error {
+            StorageError::PartitionNotFound(label) => DfuError::PartitionNotFound(label),
+            StorageError::FlashError(e) => DfuError::FlashError(e.kind()),
+        }
+    }
+}
+
+impl From<UpdateError> for DfuError {
+    fn from(error: UpdateError) -> Self {
+        match error {
+            UpdateError::NotStarted => DfuError::NotStarted,
+            UpdateError::NoPublicKey => DfuError::NoPublicKey,
+            UpdateError::ImageTooLarge(size, limit) => DfuError::ImageTooLarge(size, limit),
+            UpdateError::UnexpectedOffset(offset, expected) => {
+                DfuError::UnexpectedOffset(offset, expected)
+            }
+            UpdateError::InvalidImage => DfuError::InvalidImage,
+            UpdateError::IncompleteImage(received, size) => {
+                DfuError::IncompleteImage(received, size)
+            }
+            UpdateError::DigestMismatch => DfuError::DigestMismatch,
+            UpdateError::MissingSignature => DfuError::MissingSignature,
+            UpdateError::InvalidSignature => DfuError::InvalidSignature,
+            UpdateError::Flash(kind) => DfuError::FlashError(kind),
+        }
+    }
+}
+
+impl DfuError {
+    // Error code reported by the DFU status characteristic, 0 means no error
+    pub fn code(&self) -> u8 {
+        match self {
+            DfuError::InvalidCommand => 1,
+            DfuError::NotStarted => 2,
+            DfuError::NoPublicKey => 3,
+            DfuError::PartitionNotFound(_) => 4,
+            DfuError::OtaDataError => 5,
+            DfuError::ImageTooLarge(..) => 6,
+            DfuError::UnexpectedOffset(..) => 7,
+            DfuError::InvalidImage => 8,
+            DfuError::IncompleteImage(..) => 9,
+            DfuError::DigestMismatch => 10,
+            DfuError::MissingSignature => 11,
+            DfuError::InvalidSignature => 12,
+            DfuError::FlashError(_) => 13,
+            DfuError::Busy => 14,
+        }
+    }
+}
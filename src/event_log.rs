@@ -0,0 +1,258 @@
+use core::fmt::{self, Write as _};
+use core::ptr::addr_of_mut;
+
+use embassy_futures::select::{select3, Either3};
+use embassy_time::{Instant, Timer};
+use heapless::String;
+use log::*;
+
+use crate::config::{EVENT_LOG_FLUSH_INTERVAL, EVENT_LOG_PARTITION};
+use crate::messages::{CLIENT_STATE_WATCH, SOURCE_STATE_WATCH};
+use crate::storage::SharedRing;
+
+// Event layout: [uptime ms: u32][kind: u8][message length: u8][message]
+pub const EVENT_RECORD_SIZE: usize = 64;
+pub const EVENT_SIZE: usize = SharedRing::<EVENT_RECORD_SIZE>::PAYLOAD_SIZE;
+pub const EVENT_MESSAGE_SIZE: usize = EVENT_SIZE - 6;
+const RTC_EVENT_COUNT: usize = 32;
+const RTC_LOG_MAGIC: u32 = 0x4C4F_4731;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventKind {
+    Boot = 0,
+    Panic = 1,
+    SourceState = 2,
+    ClientState = 3,
+    CentralError = 4,
+    PeripheralError = 5,
+    Firmware = 6,
+    Unknown = 0xFF,
+}
+
+impl From<u8> for EventKind {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => EventKind::Boot,
+            1 => EventKind::Panic,
+            2 => EventKind::SourceState,
+            3 => EventKind::ClientState,
+            4 => EventKind::CentralError,
+            5 => EventKind::PeripheralError,
+            6 => EventKind::Firmware,
+            _ => EventKind::Unknown,
+        }
+    }
+}
+
+pub struct Event<'a> {
+    pub uptime_ms: u32,
+    pub kind: EventKind,
+    pub message: &'a str,
+}
+
+impl<'a> Event<'a> {
+    pub fn decode(data: &'a [u8; EVENT_SIZE]) -> Self {
+        let length = (data[5] as usize).min(EVENT_MESSAGE_SIZE);
+        Self {
+            uptime_ms: u32::from_le_bytes([data[0], data[1], data[2], data[3]]),
+            kind: EventKind::from(data[4]),
+            message: core::str::from_utf8(&data[6..6 + length]).unwrap_or("<invalid>"),
+        }
+    }
+}
+
+impl fmt::Display for Event<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}.{:03}s {:?}: {}",
+            self.uptime_ms / 1000,
+            self.uptime_ms % 1000,
+            self.kind,
+            self.message
+        )
+    }
+}
+
+// Most recent events in RTC fast memory. They survive software resets and panics
+// and are moved to flash by the event log task and on the next boot.
+#[esp_hal::ram(unstable(rtc_fast, persistent))]
+static mut RTC_LOG_MAGIC_VALUE: u32 = 0;
+#[esp_hal::ram(unstable(rtc_fast, persistent))]
+static mut RTC_LOG_HEAD: u32 = 0;
+#[esp_hal::ram(unstable(rtc_fast, persistent))]
+static mut RTC_LOG_FLUSHED: u32 = 0;
+#[esp_hal::ram(unstable(rtc_fast, persistent))]
+static mut RTC_LOG_EVENTS: [[u8; EVENT_SIZE]; RTC_EVENT_COUNT] = [[0; EVENT_SIZE]; RTC_EVENT_COUNT];
+
+static EVENT_RING: SharedRing<EVENT_RECORD_SIZE> = SharedRing::new(EVENT_LOG_PARTITION);
+
+// Cuts messages that do not fit into the buffer instead of dropping them
+pub(crate) struct Truncate<const N: usize>(pub String<N>);
+
+impl<const N: usize> fmt::Write for Truncate<N> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            if self.0.push(c).is_err() {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn encode(kind: EventKind, args: fmt::Arguments) -> [u8; EVENT_SIZE] {
+    let mut message = Truncate::<EVENT_MESSAGE_SIZE>(String::new());
+    let _ = message.write_fmt(args);
+    let message = message.0;
+
+    let mut event = [0u8; EVENT_SIZE];
+    let uptime_ms = Instant::now().as_millis() as u32;
+    event[..4].copy_from_slice(&uptime_ms.to_le_bytes());
+    event[4] = kind as u8;
+    event[5] = message.len() as u8;
+    event[6..6 + message.len()].copy_from_slice(message.as_bytes());
+    event
+}
+
+pub fn record(kind: EventKind, args: fmt::Arguments) {
+    let event = encode(kind, args);
+    critical_section::with(|_| unsafe {
+        let head = *addr_of_mut!(RTC_LOG_HEAD);
+        (*addr_of_mut!(RTC_LOG_EVENTS))[head as usize % RTC_EVENT_COUNT] = event;
+        *addr_of_mut!(RTC_LOG_HEAD) = head.wrapping_add(1);
+    });
+}
+
+// Takes the oldest event that is only held in RTC memory
+fn next_unflushed() -> Option<[u8; EVENT_SIZE]> {
+    critical_section::with(|_| unsafe {
+        let head = *addr_of_mut!(RTC_LOG_HEAD);
+        let mut flushed = *addr_of_mut!(RTC_LOG_FLUSHED);
+        if head.wrapping_sub(flushed) > RTC_EVENT_COUNT as u32 {
+            // Older events were overwritten before they could be flushed
+            flushed = head.wrapping_sub(RTC_EVENT_COUNT as u32);
+        }
+        if flushed == head {
+            return None;
+        }
+        *addr_of_mut!(RTC_LOG_FLUSHED) = flushed.wrapping_add(1);
+        Some((*addr_of_mut!(RTC_LOG_EVENTS))[flushed as usize % RTC_EVENT_COUNT])
+    })
+}
+
+// Moves events that are only held in RTC memory to the flash ring. Each event
+// is copied out of RTC memory first, the flash is written outside the lock.
+pub fn flush() {
+    // Without the flash log the events stay in RTC memory
+    if !EVENT_RING.is_open() {
+        return;
+    }
+    while let Some(event) = next_unflushed() {
+        if let Err(e) = EVENT_RING.append(&event) {
+            warn!("[EventLog] Could not write event to flash: {}", e);
+            break;
+        }
+    }
+}
+
+// Reads a stored event, `age` 0 being the newest one in flash
+pub fn read(age: u32, event: &mut [u8; EVENT_SIZE]) -> bool {
+    EVENT_RING.read(age, event).unwrap_or(false)
+}
+
+pub fn len() -> u32 {
+    EVENT_RING.len()
+}
+
+// Prints the newest `count` stored events, oldest first
+pub fn print_log(count: u32) {
+    let mut event = [0u8; EVENT_SIZE];
+    for age in (0..len().min(count)).rev() {
+        if read(age, &mut event) {
+            info!("[EventLog] #{} {}", age, Event::decode(&event));
+        }
+    }
+}
+
+// Opens the flash log, stores what the previous run left in RTC memory and records the reset reason
+pub fn init() {
+    let valid = critical_section::with(|_| unsafe {
+        let valid = *addr_of_mut!(RTC_LOG_MAGIC_VALUE) == RTC_LOG_MAGIC;
+        if !valid {
+            *addr_of_mut!(RTC_LOG_MAGIC_VALUE) = RTC_LOG_MAGIC;
+            *addr_of_mut!(RTC_LOG_HEAD) = 0;
+            *addr_of_mut!(RTC_LOG_FLUSHED) = 0;
+        }
+        valid
+    });
+    if !valid {
+        info!("[EventLog] RTC event log initialized after power on");
+    }
+
+    if let Err(e) = EVENT_RING.open() {
+        error!("[EventLog] {}, events are kept in RTC memory only", e);
+    }
+
+    flush();
+    print_log(RTC_EVENT_COUNT as u32);
+
+    record(
+        EventKind::Boot,
+        format_args!("reset reason {:?}", esp_hal::system::reset_reason()),
+    );
+}
+
+#[panic_handler]
+fn panic(info: &core::panic::PanicInfo) -> ! {
+    let backtrace = esp_backtrace::Backtrace::capture();
+    match info.location() {
+        Some(location) => record(
+            EventKind::Panic,
+            format_args!("{}:{} {}", location.file(), location.line(), info.message()),
+        ),
+        None => record(EventKind::Panic, format_args!("{}", info.message())),
+    }
+
+    // As many of the innermost frames as fit into one event, without cutting an address
+    let mut frames = String::<EVENT_MESSAGE_SIZE>::try_from("backtrace").unwrap_or_default();
+    for frame in backtrace.frames() {
+        let mut address = String::<12>::new();
+        let _ = write!(address, " {:#x}", frame.program_counter());
+        if frames.push_str(&address).is_err() {
+            break;
+        }
+    }
+    record(EventKind::Panic, format_args!("{}", frames));
+
+    // Printed like esp-backtrace does, so espflash monitor resolves the addresses
+    esp_println::println!("{}", info);
+    esp_println::println!("Backtrace:");
+    for frame in backtrace.frames() {
+        esp_println::println!("0x{:x}", frame.program_counter());
+    }
+    esp_hal::system::software_reset()
+}
+
+pub async fn event_log_task() {
+    let mut client_receiver = CLIENT_STATE_WATCH
+        .receiver()
+        .expect("[EventLog] Client Watch receiver returned None - watch not initialized");
+    let mut source_receiver = SOURCE_STATE_WATCH
+        .receiver()
+        .expect("[EventLog] Source Watch receiver returned None - watch not initialized");
+
+    loop {
+        match select3(
+            client_receiver.changed(),
+            source_receiver.changed(),
+            Timer::after(EVENT_LOG_FLUSH_INTERVAL),
+        )
+        .await
+        {
+            Either3::First(state) => record(EventKind::ClientState, format_args!("{:?}", state)),
+            Either3::Second(state) => record(EventKind::SourceState, format_args!("{:?}", state)),
+            Either3::Third(_) => flush(),
+        }
+    }
+}
@@ -0,0 +1,89 @@
+// Logging front end for the radar path. Without the `defmt` feature the macros
+// forward to `log`, with it they emit compact defmt frames that are formatted on
+// the host. Arguments must implement `defmt::Format`, anything else is wrapped in
+// `Debug2Format` or `Display2Format`.
+
+#[cfg(all(
+    feature = "defmt",
+    not(any(feature = "defmt-rtt", feature = "defmt-serial"))
+))]
+compile_error!("The defmt feature needs a transport, enable defmt-rtt or defmt-serial");
+
+#[macro_export]
+macro_rules! trace {
+    ($($arg:tt)*) => {{
+        #[cfg(feature = "defmt")]
+        ::defmt::trace!($($arg)*);
+        #[cfg(not(feature = "defmt"))]
+        ::log::trace!($($arg)*);
+    }};
+}
+
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)*) => {{
+        #[cfg(feature = "defmt")]
+        ::defmt::debug!($($arg)*);
+        #[cfg(not(feature = "defmt"))]
+        ::log::debug!($($arg)*);
+    }};
+}
+
+#[macro_export]
+macro_rules! info {
+    ($($arg:tt)*) => {{
+        #[cfg(feature = "defmt")]
+        ::defmt::info!($($arg)*);
+        #[cfg(not(feature = "defmt"))]
+        ::log::info!($($arg)*);
+    }};
+}
+
+#[macro_export]
+macro_rules! warn {
+    ($($arg:tt)*) => {{
+        #[cfg(feature = "defmt")]
+        ::defmt::warn!($($arg)*);
+        #[cfg(not(feature = "defmt"))]
+        ::log::warn!($($arg)*);
+    }};
+}
+
+#[macro_export]
+macro_rules! error {
+    ($($arg:tt)*) => {{
+        #[cfg(feature = "defmt")]
+        ::defmt::error!($($arg)*);
+        #[cfg(not(feature = "defmt"))]
+        ::log::error!($($arg)*);
+    }};
+}
+
+pub use crate::{debug, error, info, trace, warn};
+
+#[cfg(feature = "defmt")]
+pub use defmt::{Debug2Format, Display2Format};
+
+// Stand-ins for the defmt wrappers, so call sites look the same with both backends
+#[cfg(not(feature = "defmt"))]
+pub struct Debug2Format<'a, T: core::fmt::Debug + ?Sized>(pub &'a T);
+
+#[cfg(not(feature = "defmt"))]
+impl<T: core::fmt::Debug + ?Sized> core::fmt::Debug for Debug2Format<'_, T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[cfg(not(feature = "defmt"))]
+pub struct Display2Format<'a, T: core::fmt::Display + ?Sized>(pub &'a T);
+
+#[cfg(not(feature = "defmt"))]
+impl<T: core::fmt::Display + ?Sized> core::fmt::Display for Display2Format<'_, T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[cfg(feature = "defmt")]
+defmt::timestamp!("{=u64:ms}", embassy_time::Instant::now().as_millis());
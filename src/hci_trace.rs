@@ -0,0 +1,105 @@
+use core::fmt::Write as _;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use bt_hci::transport::Transport;
+use bt_hci::{ControllerToHostPacket, HostToControllerPacket, WriteHci};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::pipe::Pipe;
+use embassy_time::Instant;
+use esp_println::println;
+use heapless::String;
+use magene_protocol::btsnoop::{
+    self, RecordHeader, RECORD_HEADER_SIZE, TRACE_LINE_PREFIX, UNIX_EPOCH_US,
+};
+
+use crate::config::{HCI_TRACE_BUFFER_SIZE, HCI_TRACE_PACKET_SIZE};
+
+const RECORD_SIZE: usize = RECORD_HEADER_SIZE + HCI_TRACE_PACKET_SIZE;
+
+// Records waiting to be printed. When the console can not keep up, records are
+// dropped and counted in the header of the next one.
+static TRACE_PIPE: Pipe<CriticalSectionRawMutex, HCI_TRACE_BUFFER_SIZE> = Pipe::new();
+static TRACE_DROPS: AtomicU32 = AtomicU32::new(0);
+
+// Records the HCI traffic between the host stack and the controller in btsnoop
+// format. Packets longer than HCI_TRACE_PACKET_SIZE are truncated.
+pub struct TracingTransport<T> {
+    inner: T,
+}
+
+impl<T> TracingTransport<T> {
+    pub fn new(inner: T) -> Self {
+        Self { inner }
+    }
+}
+
+fn record(indicator: u8, received: bool, packet: &impl WriteHci) {
+    let mut record = [0u8; RECORD_SIZE];
+    let (header, body) = record.split_at_mut(RECORD_HEADER_SIZE);
+    body[0] = indicator;
+    // Writing stops at the end of the buffer, which truncates long packets
+    let _ = packet.write_hci(&mut &mut body[1..]);
+    let length = 1 + packet.size();
+    let included = length.min(HCI_TRACE_PACKET_SIZE);
+
+    let timestamp_us = UNIX_EPOCH_US + Instant::now().as_micros();
+    let mut record_header = RecordHeader::new(
+        length as u32,
+        btsnoop::flags(indicator, received),
+        timestamp_us,
+    );
+    record_header.included_length = included as u32;
+    record_header.drops = TRACE_DROPS.load(Ordering::Relaxed);
+    header.copy_from_slice(&record_header.encode());
+
+    // Only whole records go into the pipe, so the reader never sees a partial one
+    let record = &record[..RECORD_HEADER_SIZE + included];
+    if TRACE_PIPE.free_capacity() < record.len() || TRACE_PIPE.try_write(record).is_err() {
+        TRACE_DROPS.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl<T: Transport> embedded_io::ErrorType for TracingTransport<T> {
+    type Error = T::Error;
+}
+
+impl<T: Transport> Transport for TracingTransport<T> {
+    async fn read<'a>(&self, rx: &'a mut [u8]) -> Result<ControllerToHostPacket<'a>, Self::Error> {
+        let packet = self.inner.read(rx).await?;
+        record(packet.kind() as u8, true, &packet);
+        Ok(packet)
+    }
+
+    async fn write<P: HostToControllerPacket>(&self, packet: &P) -> Result<(), Self::Error> {
+        record(P::KIND as u8, false, packet);
+        self.inner.write(packet).await
+    }
+}
+
+async fn read_exact(buffer: &mut [u8]) {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        filled += TRACE_PIPE.read(&mut buffer[filled..]).await;
+    }
+}
+
+// Prints every record as a line of hex, which `magene-tool hci` turns into a
+// btsnoop file
+pub async fn hci_trace_task() {
+    let mut record = [0u8; RECORD_SIZE];
+    let mut line: String<{ 2 * RECORD_SIZE }> = String::new();
+    loop {
+        read_exact(&mut record[..RECORD_HEADER_SIZE]).await;
+        let mut header = [0u8; RECORD_HEADER_SIZE];
+        header.copy_from_slice(&record[..RECORD_HEADER_SIZE]);
+        let included = RecordHeader::decode(&header).included_length as usize;
+        let length = RECORD_HEADER_SIZE + included;
+        read_exact(&mut record[RECORD_HEADER_SIZE..length]).await;
+
+        line.clear();
+        for byte in &record[..length] {
+            let _ = write!(line, "{:02x}", byte);
+        }
+        println!("{}{}", TRACE_LINE_PREFIX, line);
+    }
+}
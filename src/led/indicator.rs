@@ -0,0 +1,83 @@
+use core::convert::Infallible;
+
+use esp_hal::gpio::Output;
+use esp_hal::rmt::{RawChannelAccess, TxChannelInternal};
+use esp_hal_smartled::{LedAdapterError, SmartLedsAdapter};
+use magene_protocol::indicator::Indicator;
+use smart_leds::{brightness, colors, SmartLedsWrite as _, RGB};
+
+use super::LedDropGuard;
+
+// Single addressable WS2812 pixel, switched off when dropped
+pub struct Ws2812Indicator<'a, TX, const BUFFER_SIZE: usize>
+where
+    TX: RawChannelAccess + TxChannelInternal + 'static,
+{
+    guard: LedDropGuard<'a, TX, BUFFER_SIZE>,
+}
+
+impl<'a, TX, const BUFFER_SIZE: usize> Ws2812Indicator<'a, TX, BUFFER_SIZE>
+where
+    TX: RawChannelAccess + TxChannelInternal + 'static,
+{
+    pub fn new(led: &'a mut SmartLedsAdapter<TX, BUFFER_SIZE>) -> Self {
+        Self {
+            guard: LedDropGuard::new(led, 1),
+        }
+    }
+}
+
+impl<TX, const BUFFER_SIZE: usize> Indicator for Ws2812Indicator<'_, TX, BUFFER_SIZE>
+where
+    TX: RawChannelAccess + TxChannelInternal + 'static,
+{
+    type Error = LedAdapterError;
+
+    fn set(&mut self, color: RGB<u8>, level: u8) -> Result<(), Self::Error> {
+        self.guard
+            .led()
+            .write(brightness([color].into_iter(), level))
+    }
+}
+
+// Plain single colour LED on a GPIO. Any colour above the threshold level turns it on.
+pub struct GpioIndicator<'d> {
+    pin: Output<'d>,
+    active_low: bool,
+    threshold: u8,
+}
+
+impl<'d> GpioIndicator<'d> {
+    pub fn new(pin: Output<'d>, active_low: bool) -> Self {
+        Self {
+            pin,
+            active_low,
+            threshold: 1,
+        }
+    }
+
+    pub fn with_threshold(mut self, threshold: u8) -> Self {
+        self.threshold = threshold;
+        self
+    }
+}
+
+impl Indicator for GpioIndicator<'_> {
+    type Error = Infallible;
+
+    fn set(&mut self, color: RGB<u8>, level: u8) -> Result<(), Self::Error> {
+        let on = level >= self.threshold && color != colors::BLACK;
+        if on != self.active_low {
+            self.pin.set_high();
+        } else {
+            self.pin.set_low();
+        }
+        Ok(())
+    }
+}
+
+impl Drop for GpioIndicator<'_> {
+    fn drop(&mut self) {
+        let _ = self.off();
+    }
+}
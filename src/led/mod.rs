@@ -0,0 +1,214 @@
+mod indicator;
+
+pub use indicator::{GpioIndicator, Ws2812Indicator};
+pub use magene_protocol::animation::{
+    Animation, AnimationEngine, Keyframe, Layer, RadarIndication,
+};
+pub use magene_protocol::indicator::{Indicator, MockIndicator};
+
+use embassy_futures::select::{select4, Either4};
+use embassy_time::{Duration, Instant, Timer};
+use esp_hal::rmt::{RawChannelAccess, TxChannelInternal};
+use esp_hal_smartled::SmartLedsAdapter;
+use smart_leds::{
+    brightness,
+    colors::{self},
+    SmartLedsWrite as _, RGB,
+};
+
+use crate::config::{LED_BRIGHTNESS, LED_DIM_BRIGHTNESS, LED_RADAR};
+use crate::fmt::*;
+use crate::messages::{
+    ClientState, SourceState, CLIENT_STATE_WATCH, RADAR_DATA_WATCH, SOURCE_STATE_WATCH,
+};
+use crate::radar::RadarFrame;
+
+pub(crate) struct LedDropGuard<'a, TX, const BUFFER_SIZE: usize>
+where
+    TX: RawChannelAccess + TxChannelInternal + 'static,
+{
+    led: &'a mut SmartLedsAdapter<TX, BUFFER_SIZE>,
+    pixels: usize,
+}
+
+impl<'a, TX, const BUFFER_SIZE: usize> LedDropGuard<'a, TX, BUFFER_SIZE>
+where
+    TX: RawChannelAccess + TxChannelInternal + 'static,
+{
+    pub(crate) fn new(led: &'a mut SmartLedsAdapter<TX, BUFFER_SIZE>, pixels: usize) -> Self {
+        Self { led, pixels }
+    }
+
+    pub(crate) fn led(&mut self) -> &mut SmartLedsAdapter<TX, BUFFER_SIZE> {
+        self.led
+    }
+}
+
+impl<'a, TX, const BUFFER_SIZE: usize> Drop for LedDropGuard<'a, TX, BUFFER_SIZE>
+where
+    TX: RawChannelAccess + TxChannelInternal + 'static,
+{
+    fn drop(&mut self) {
+        let _ = self.led.write(brightness(
+            core::iter::repeat(colors::BLACK).take(self.pixels),
+            0,
+        ));
+    }
+}
+
+#[derive(Debug, Clone)]
+struct LEDPattern {
+    client_state: ClientState,
+    source_state: SourceState,
+    radar: RadarIndication,
+}
+
+impl LEDPattern {
+    pub fn new() -> Self {
+        Self {
+            client_state: ClientState::Disconnected,
+            source_state: SourceState::Disconnected,
+            radar: RadarIndication::new(LED_RADAR),
+        }
+    }
+
+    pub fn set_client_state(&mut self, client_state: ClientState) {
+        self.client_state = client_state
+    }
+
+    pub fn set_source_state(&mut self, source_state: SourceState) {
+        self.source_state = source_state
+    }
+
+    pub fn set_radar_data(&mut self, frame: Option<RadarFrame>) {
+        self.radar.set_frame(frame);
+    }
+
+    fn client_color(&self) -> RGB<u8> {
+        match self.client_state {
+            ClientState::Connected => colors::AZURE,
+            ClientState::Disconnected => colors::YELLOW,
+        }
+    }
+
+    // Client connected but no radar to forward
+    pub fn error_animation(&self) -> Option<Animation> {
+        if self.client_state != ClientState::Connected
+            || self.source_state == SourceState::Connected
+        {
+            return None;
+        }
+        let on = Duration::from_millis(100);
+        Some(Animation::new(
+            &[
+                Keyframe::hold(colors::RED, LED_BRIGHTNESS, on),
+                Keyframe::hold(colors::BLACK, 0, on),
+                Keyframe::hold(colors::RED, LED_BRIGHTNESS, on),
+                Keyframe::hold(colors::BLACK, 0, Duration::from_millis(1700)),
+            ],
+            true,
+        ))
+    }
+
+    pub fn alert_animation(&self) -> Option<Animation> {
+        self.radar.alert_animation()
+    }
+
+    pub fn status_animation(&self) -> Option<Animation> {
+        let period = match self.source_state {
+            SourceState::Disconnected => return None,
+            SourceState::Scanning => Duration::from_millis(500),
+            SourceState::Connecting => Duration::from_millis(1000),
+            SourceState::Connected => Duration::from_millis(2500),
+        };
+        Some(Animation::blink(
+            self.client_color(),
+            LED_BRIGHTNESS,
+            Duration::from_millis(200),
+            period,
+        ))
+    }
+
+    pub fn idle_animation(&self) -> Animation {
+        Animation::breathe(
+            self.client_color(),
+            LED_DIM_BRIGHTNESS,
+            Duration::from_secs(4),
+        )
+    }
+
+    pub fn get_level_limit(&self, layer: Option<Layer>) -> u8 {
+        self.radar.level_limit(layer)
+    }
+
+    pub fn apply(&self, engine: &mut AnimationEngine) {
+        let now = Instant::now();
+        let layers = [
+            (Layer::Error, self.error_animation()),
+            (Layer::Alert, self.alert_animation()),
+            (Layer::Status, self.status_animation()),
+            (Layer::Idle, Some(self.idle_animation())),
+        ];
+        for (layer, animation) in layers {
+            match animation {
+                Some(animation) => engine.set(layer, animation, now),
+                None => engine.clear(layer),
+            }
+        }
+    }
+}
+
+pub async fn led_task<I: Indicator>(indicator: &mut I) {
+    let mut current_pattern = LEDPattern::new();
+    let mut engine = AnimationEngine::new();
+    current_pattern.apply(&mut engine);
+
+    let mut client_receiver = CLIENT_STATE_WATCH
+        .receiver()
+        .expect("[LED] Client Watch receiver returned None - watch not initialized");
+
+    let mut source_receiver = SOURCE_STATE_WATCH
+        .receiver()
+        .expect("[LED]Source Watch receiver returned None - watch not initialized");
+
+    let mut radar_receiver = RADAR_DATA_WATCH
+        .receiver()
+        .expect("[LED] Radar Watch receiver returned None - watch not initialized");
+
+    let mut last_output = None;
+
+    loop {
+        let now = Instant::now();
+        let (layer, color, level) = engine.sample(now);
+        let output = (color, level.min(current_pattern.get_level_limit(layer)));
+        if last_output != Some(output) {
+            match indicator.set(output.0, output.1) {
+                Ok(()) => last_output = Some(output),
+                Err(e) => warn!("[LED] Could not update indicator: {:?}", Debug2Format(&e)),
+            }
+        }
+
+        match select4(
+            client_receiver.changed(),
+            source_receiver.changed(),
+            radar_receiver.changed(),
+            Timer::at(engine.next_update(now)),
+        )
+        .await
+        {
+            Either4::First(state) => {
+                current_pattern.set_client_state(state);
+                current_pattern.apply(&mut engine);
+            }
+            Either4::Second(state) => {
+                current_pattern.set_source_state(state);
+                current_pattern.apply(&mut engine);
+            }
+            Either4::Third(frame) => {
+                current_pattern.set_radar_data(frame);
+                current_pattern.apply(&mut engine);
+            }
+            Either4::Fourth(_) => {}
+        }
+    }
+}
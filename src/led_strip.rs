@@ -0,0 +1,80 @@
+use esp_hal::rmt::{RawChannelAccess, TxChannelInternal};
+use esp_hal_smartled::SmartLedsAdapter;
+use magene_protocol::strip::{self, Pixel};
+use smart_leds::{brightness, colors, SmartLedsWrite as _, RGB};
+
+use crate::config::{
+    LED_STRIP_BRIGHTNESS, LED_STRIP_LENGTH, LED_STRIP_MAX_RANGE, LED_STRIP_MAX_TARGETS,
+};
+use crate::fmt::*;
+use crate::led::LedDropGuard;
+use crate::messages::RADAR_DATA_WATCH;
+use crate::radar::{RadarFrame, ThreatLevel};
+
+// Colours of the strip, the layout is drawn by `magene_protocol::strip`
+struct StripDisplay {
+    pixels: [Pixel; LED_STRIP_LENGTH],
+}
+
+impl StripDisplay {
+    pub fn new() -> Self {
+        Self {
+            pixels: [Pixel::Off; LED_STRIP_LENGTH],
+        }
+    }
+
+    fn color(pixel: Pixel) -> RGB<u8> {
+        match pixel {
+            Pixel::Off => colors::BLACK,
+            Pixel::RadarOffline => colors::RED,
+            Pixel::RoadClear => colors::GREEN,
+            Pixel::Vehicle(ThreatLevel::None) => colors::GREEN,
+            Pixel::Vehicle(ThreatLevel::Low) => colors::YELLOW,
+            Pixel::Vehicle(ThreatLevel::Medium) => colors::ORANGE,
+            Pixel::Vehicle(ThreatLevel::High) => colors::RED,
+        }
+    }
+
+    pub fn render(&mut self, frame: &RadarFrame) {
+        strip::render(
+            frame,
+            &mut self.pixels,
+            LED_STRIP_MAX_RANGE,
+            LED_STRIP_MAX_TARGETS,
+        );
+    }
+
+    pub fn pixels(&self) -> impl Iterator<Item = RGB<u8>> + '_ {
+        self.pixels.iter().copied().map(Self::color)
+    }
+}
+
+pub async fn led_strip_task<TX, const BUFFER_SIZE: usize>(
+    strip: &mut SmartLedsAdapter<TX, BUFFER_SIZE>,
+) where
+    TX: RawChannelAccess + TxChannelInternal + 'static,
+{
+    let mut strip_guard = LedDropGuard::new(strip, LED_STRIP_LENGTH);
+    let mut display = StripDisplay::new();
+
+    let mut radar_receiver = RADAR_DATA_WATCH
+        .receiver()
+        .expect("[LED Strip] Radar Watch receiver returned None - watch not initialized");
+
+    display.render(&RadarFrame::offline());
+
+    loop {
+        if let Err(e) = strip_guard
+            .led()
+            .write(brightness(display.pixels(), LED_STRIP_BRIGHTNESS))
+        {
+            warn!("[LED Strip] Could not write strip: {:?}", Debug2Format(&e));
+        }
+
+        let frame = radar_receiver
+            .changed()
+            .await
+            .unwrap_or_else(RadarFrame::offline);
+        display.render(&frame);
+    }
+}
@@ -1,6 +1,24 @@
 #![no_std]
+pub mod alert;
 pub mod bluetooth;
+pub mod build_info;
+pub mod capture;
+pub mod command;
 pub mod config;
+pub mod console;
+pub mod dfu;
+pub mod diagnostics;
 pub mod errors;
+pub mod event_log;
+pub mod fmt;
+#[cfg(feature = "hci-trace")]
+pub mod hci_trace;
 pub mod led;
+#[cfg(feature = "led-strip")]
+pub mod led_strip;
+pub mod logger;
 pub mod messages;
+pub mod radar;
+pub mod rides;
+pub mod settings;
+pub mod storage;
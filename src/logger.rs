@@ -0,0 +1,199 @@
+use core::cell::RefCell;
+use core::fmt::Write as _;
+
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_time::Instant;
+use esp_println::println;
+use heapless::{String, Vec};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+use crate::config::{
+    LOG_LINE_SIZE, LOG_MODULE_FILTERS_MAX, LOG_MODULE_NAME_SIZE, LOG_RATE_LIMIT_BURST,
+    LOG_RATE_LIMIT_INTERVAL, LOG_RATE_LIMIT_SITES,
+};
+use crate::event_log::Truncate;
+use crate::settings::Settings;
+
+pub type ModuleName = String<LOG_MODULE_NAME_SIZE>;
+pub type ModuleFilters = Vec<(ModuleName, LevelFilter), LOG_MODULE_FILTERS_MAX>;
+
+// Warnings and errors logged from one place in the code
+struct CallSite {
+    file: &'static str,
+    line: u32,
+    window_start: Instant,
+    count: u32,
+}
+
+struct LoggerState {
+    level: LevelFilter,
+    modules: ModuleFilters,
+    rate_limit: bool,
+    call_sites: Vec<CallSite, LOG_RATE_LIMIT_SITES>,
+}
+
+// Runtime configurable replacement for the esp-println logger. Messages are
+// filtered by their `[Module]` tag, falling back to the crate name for log
+// messages of dependencies.
+struct Logger {
+    state: Mutex<CriticalSectionRawMutex, RefCell<LoggerState>>,
+}
+
+static LOGGER: Logger = Logger {
+    state: Mutex::new(RefCell::new(LoggerState {
+        level: LevelFilter::Info,
+        modules: Vec::new(),
+        rate_limit: false,
+        call_sites: Vec::new(),
+    })),
+};
+
+// Module a message belongs to: its `[Module]` tag, or the crate that logged it
+fn module<'a>(message: &'a str, target: &'a str) -> &'a str {
+    message
+        .strip_prefix('[')
+        .and_then(|rest| rest.split_once(']'))
+        .map(|(tag, _)| tag)
+        .unwrap_or_else(|| target.split("::").next().unwrap_or(target))
+}
+
+fn color(level: Level) -> &'static str {
+    match level {
+        Level::Error => "\x1b[31m",
+        Level::Warn => "\x1b[33m",
+        Level::Info => "\x1b[32m",
+        Level::Debug => "\x1b[34m",
+        Level::Trace => "\x1b[35m",
+    }
+}
+
+impl LoggerState {
+    fn level_of(&self, module: &str) -> LevelFilter {
+        self.modules
+            .iter()
+            .find(|(name, _)| name.as_str() == module)
+            .map_or(self.level, |(_, level)| *level)
+    }
+
+    // Returns whether the message may be printed and how many earlier ones were suppressed
+    fn rate_limit(&mut self, record: &Record) -> (bool, u32) {
+        if !self.rate_limit || record.level() > Level::Warn {
+            return (true, 0);
+        }
+        let (Some(file), Some(line)) = (record.file_static(), record.line()) else {
+            return (true, 0);
+        };
+
+        let now = Instant::now();
+        let site = match self
+            .call_sites
+            .iter()
+            .position(|site| site.line == line && site.file == file)
+        {
+            Some(index) => &mut self.call_sites[index],
+            None => {
+                let site = CallSite {
+                    file,
+                    line,
+                    window_start: now,
+                    count: 0,
+                };
+                if self.call_sites.is_full() {
+                    // Forget the call site that has been quiet for the longest time
+                    let oldest = self
+                        .call_sites
+                        .iter()
+                        .enumerate()
+                        .min_by_key(|(_, site)| site.window_start)
+                        .map_or(0, |(index, _)| index);
+                    self.call_sites[oldest] = site;
+                    &mut self.call_sites[oldest]
+                } else {
+                    let _ = self.call_sites.push(site);
+                    let last = self.call_sites.len() - 1;
+                    &mut self.call_sites[last]
+                }
+            }
+        };
+
+        if now - site.window_start > LOG_RATE_LIMIT_INTERVAL {
+            let suppressed = site.count.saturating_sub(LOG_RATE_LIMIT_BURST);
+            site.window_start = now;
+            site.count = 1;
+            return (true, suppressed);
+        }
+        site.count += 1;
+        (site.count <= LOG_RATE_LIMIT_BURST, 0)
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let mut message = Truncate::<LOG_LINE_SIZE>(String::new());
+        let _ = write!(message, "{}", record.args());
+        let message = message.0;
+
+        let (print, suppressed) = self.state.lock(|state| {
+            let mut state = state.borrow_mut();
+            if record.level() > state.level_of(module(&message, record.target())) {
+                return (false, 0);
+            }
+            state.rate_limit(record)
+        });
+
+        if suppressed > 0 {
+            println!(
+                "{}WARN - [Logger] {} similar messages from {}:{} suppressed\x1b[0m",
+                color(Level::Warn),
+                suppressed,
+                record.file().unwrap_or("?"),
+                record.line().unwrap_or(0)
+            );
+        }
+        if print {
+            println!(
+                "{}{} - {}\x1b[0m",
+                color(record.level()),
+                record.level(),
+                message
+            );
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+// Must be called once, before anything is logged
+pub fn init(level: LevelFilter) {
+    LOGGER.state.lock(|state| state.borrow_mut().level = level);
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(level);
+    }
+}
+
+// Applies the log settings. The global max level is the most verbose of all
+// levels so the log macros let through messages of verbose modules.
+pub fn apply(settings: &Settings) {
+    let max_level = settings
+        .log_modules
+        .iter()
+        .map(|(_, level)| *level)
+        .fold(settings.log_level, LevelFilter::max);
+
+    LOGGER.state.lock(|state| {
+        let mut state = state.borrow_mut();
+        state.level = settings.log_level;
+        state.modules = settings.log_modules.clone();
+        state.rate_limit = settings.log_rate_limit;
+    });
+    log::set_max_level(max_level);
+}
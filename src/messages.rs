@@ -1,15 +1,24 @@
+use bt_hci::param::BdAddr;
 use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
 use embassy_sync::channel::Channel;
+use embassy_sync::signal::Signal;
 use embassy_sync::watch::Watch;
+use heapless::Vec;
+use magene_protocol::tracker::TrackEvent;
 use trouble_host::prelude::*;
 
+use crate::config::{CONSOLE_LINE_SIZE, DFU_CONTROL_SIZE, DFU_DATA_SIZE, DFU_QUEUE_DEPTH};
+use crate::radar::RadarFrame;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum ClientState {
     Connected,
     Disconnected,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum SourceState {
     Disconnected,
     Scanning,
@@ -17,9 +26,43 @@ pub enum SourceState {
     Connected,
 }
 
+// Requested by the console, carried out by main
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SystemRequest {
+    Reset,
+    Sleep,
+}
+
+// Writes to the DFU service, carried out in order by the DFU task
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DfuRequest {
+    Control(Vec<u8, DFU_CONTROL_SIZE>),
+    Data(Vec<u8, DFU_DATA_SIZE>),
+}
+
 // Channel declarations
 pub static SCAN_CHANNEL: Channel<CriticalSectionRawMutex, Address, 32> = Channel::new();
-pub static RADAR_DATA_WATCH: Watch<CriticalSectionRawMutex, Option<[u8; 16]>, 2> = Watch::new();
+// Tracked radar frames, None while no radar data is available
+pub static RADAR_DATA_WATCH: Watch<CriticalSectionRawMutex, Option<RadarFrame>, 4> = Watch::new();
+// Vehicles appearing and passing, as seen by the tracker
+pub static TRACK_EVENT_CHANNEL: Channel<CriticalSectionRawMutex, TrackEvent, 8> = Channel::new();
 pub static BATTERY_DATA_WATCH: Watch<CriticalSectionRawMutex, Option<[u8; 1]>, 2> = Watch::new();
-pub static CLIENT_STATE_WATCH: Watch<CriticalSectionRawMutex, ClientState, 4> = Watch::new();
-pub static SOURCE_STATE_WATCH: Watch<CriticalSectionRawMutex, SourceState, 4> = Watch::new();
+pub static CLIENT_STATE_WATCH: Watch<CriticalSectionRawMutex, ClientState, 6> = Watch::new();
+pub static SOURCE_STATE_WATCH: Watch<CriticalSectionRawMutex, SourceState, 5> = Watch::new();
+// Device to connect to for the GATT explorer instead of the radar, None when idle
+pub static EXPLORE_WATCH: Watch<CriticalSectionRawMutex, Option<BdAddr>, 1> = Watch::new();
+pub static DFU_REQUEST_CHANNEL: Channel<CriticalSectionRawMutex, DfuRequest, DFU_QUEUE_DEPTH> =
+    Channel::new();
+pub static SYSTEM_REQUEST_SIGNAL: Signal<CriticalSectionRawMutex, SystemRequest> = Signal::new();
+// Requests of the BLE client that read or write flash, carried out outside the
+// GATT handler. A request that is not carried out yet is replaced by the next one.
+// Command line written to the config service
+pub static COMMAND_REQUEST_SIGNAL: Signal<CriticalSectionRawMutex, Vec<u8, CONSOLE_LINE_SIZE>> =
+    Signal::new();
+// Index of the event the client asked for
+pub static EVENT_REQUEST_SIGNAL: Signal<CriticalSectionRawMutex, u32> = Signal::new();
+// Index of the stored ride the client asked for
+pub static RIDE_REQUEST_SIGNAL: Signal<CriticalSectionRawMutex, u32> = Signal::new();
+// Settings were changed, e.g. from the console
+pub static SETTINGS_CHANGED_SIGNAL: Signal<CriticalSectionRawMutex, ()> = Signal::new();
@@ -0,0 +1 @@
+pub use magene_protocol::radar::*;
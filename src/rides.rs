@@ -0,0 +1,105 @@
+use core::cell::RefCell;
+
+use embassy_futures::select::{select, Either};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_time::Instant;
+use log::*;
+use magene_protocol::ride::{RideRecorder, RideStats, RIDE_STATS_SIZE};
+use magene_protocol::tracker::Tracked;
+
+use crate::config::RIDES_PARTITION;
+use crate::messages::{ClientState, SourceState, CLIENT_STATE_WATCH, SOURCE_STATE_WATCH};
+use crate::storage::SharedRing;
+
+// Ride layout: see magene_protocol::ride::RideStats
+const RIDE_RECORD_SIZE: usize = 32;
+
+// A ride lasts from the client connecting to it disconnecting. Its statistics
+// are collected from the frames the proxy forwards and appended to the rides
+// partition when it ends.
+static RIDE: Mutex<CriticalSectionRawMutex, RefCell<Option<RideRecorder>>> =
+    Mutex::new(RefCell::new(None));
+static RIDE_RING: SharedRing<RIDE_RECORD_SIZE> = SharedRing::new(RIDES_PARTITION);
+
+pub fn init() {
+    match RIDE_RING.open() {
+        Ok(()) => info!("[Rides] {} rides stored", RIDE_RING.len()),
+        Err(e) => error!("[Rides] {}, rides will not be stored", e),
+    }
+}
+
+// Adds a forwarded frame and its vehicle events to the ride in progress
+pub fn record(tracked: &Tracked) {
+    RIDE.lock(|ride| {
+        if let Some(ride) = ride.borrow_mut().as_mut() {
+            ride.update(Instant::now(), tracked);
+        }
+    });
+}
+
+pub fn radar_lost() {
+    RIDE.lock(|ride| {
+        if let Some(ride) = ride.borrow_mut().as_mut() {
+            ride.radar_lost(Instant::now());
+        }
+    });
+}
+
+// Statistics of the ride in progress, None while no client is connected
+pub fn current() -> Option<RideStats> {
+    RIDE.lock(|ride| {
+        ride.borrow()
+            .as_ref()
+            .map(|ride| ride.stats(Instant::now()))
+    })
+}
+
+fn start() {
+    info!("[Rides] Ride started");
+    RIDE.lock(|ride| *ride.borrow_mut() = Some(RideRecorder::start(Instant::now())));
+}
+
+fn finish() {
+    let Some(ride) = RIDE.lock(|ride| ride.borrow_mut().take()) else {
+        return;
+    };
+    let stats = ride.stats(Instant::now());
+    info!("[Rides] Ride finished: {}", stats);
+
+    if let Err(e) = RIDE_RING.append(&stats.encode()) {
+        warn!("[Rides] Could not write ride to flash: {}", e);
+    }
+}
+
+// Reads a stored ride, `age` 0 being the newest one
+pub fn read(age: u32) -> Option<RideStats> {
+    let mut record = [0u8; RIDE_STATS_SIZE];
+    match RIDE_RING.read(age, &mut record) {
+        Ok(true) => RideStats::decode(&record),
+        _ => None,
+    }
+}
+
+pub fn len() -> u32 {
+    RIDE_RING.len()
+}
+
+pub async fn ride_task() {
+    let mut client_receiver = CLIENT_STATE_WATCH
+        .receiver()
+        .expect("[Rides] Client Watch receiver returned None - watch not initialized");
+    let mut source_receiver = SOURCE_STATE_WATCH
+        .receiver()
+        .expect("[Rides] Source Watch receiver returned None - watch not initialized");
+
+    loop {
+        match select(client_receiver.changed(), source_receiver.changed()).await {
+            Either::First(ClientState::Connected) => start(),
+            Either::First(ClientState::Disconnected) => finish(),
+            Either::Second(SourceState::Connected) => {}
+            // Frames stop without a timeout when the radar disconnects
+            Either::Second(_) => radar_lost(),
+        }
+    }
+}
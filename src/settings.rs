@@ -0,0 +1,199 @@
+use core::cell::RefCell;
+
+use bt_hci::param::{AddrKind, BdAddr};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use heapless::String;
+use log::*;
+use trouble_host::Address;
+
+use crate::config::{
+    FilterProfile, SourceOfflinePolicy, FILTER_PROFILES, LOG_LEVEL, LOG_MODULE_FILTERS_MAX,
+    LOG_MODULE_NAME_SIZE, LOG_RATE_LIMIT, PROXY_NAME, SETTINGS_PARTITION, SOURCE_OFFLINE_POLICY,
+    TARGET_NAME,
+};
+use crate::errors::StorageError;
+use crate::logger::{self, ModuleFilters};
+use crate::messages::SETTINGS_CHANGED_SIGNAL;
+use crate::storage::SharedRing;
+
+// Settings layout: [version][log level][bound][address kind][address: 6][target length][target: 20]
+// [name length][name: 20][log rate limit][module count][modules: [level][name length][name: 12] * 4]
+// [filter profile][offline policy]
+const SETTINGS_RECORD_SIZE: usize = 128;
+const SETTINGS_VERSION: u8 = 4;
+// Version 2 records end before the filter profile, the zero there picks the
+// default. Versions 2 and 3 use the default offline policy.
+const SETTINGS_VERSION_MIN: u8 = 2;
+const SETTINGS_MODULES_OFFSET: usize = 54;
+const SETTINGS_MODULE_SIZE: usize = 2 + LOG_MODULE_NAME_SIZE;
+const SETTINGS_PROFILE_OFFSET: usize =
+    SETTINGS_MODULES_OFFSET + LOG_MODULE_FILTERS_MAX * SETTINGS_MODULE_SIZE;
+const SETTINGS_POLICY_OFFSET: usize = SETTINGS_PROFILE_OFFSET + 1;
+pub const SETTINGS_NAME_SIZE: usize = 20;
+
+// Runtime configuration, persisted in the settings partition. Every change
+// appends a new record, the newest record wins on boot.
+#[derive(Debug, Clone)]
+pub struct Settings {
+    pub target_name: String<SETTINGS_NAME_SIZE>,
+    pub proxy_name: String<SETTINGS_NAME_SIZE>,
+    pub bound_address: Option<Address>,
+    pub log_level: log::LevelFilter,
+    pub log_modules: ModuleFilters,
+    pub log_rate_limit: bool,
+    // Index into FILTER_PROFILES
+    pub profile: usize,
+    pub offline_policy: SourceOfflinePolicy,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            target_name: String::try_from(TARGET_NAME).unwrap_or_default(),
+            proxy_name: String::try_from(PROXY_NAME).unwrap_or_default(),
+            bound_address: None,
+            log_level: LOG_LEVEL,
+            log_modules: ModuleFilters::new(),
+            log_rate_limit: LOG_RATE_LIMIT,
+            profile: 0,
+            offline_policy: SOURCE_OFFLINE_POLICY,
+        }
+    }
+}
+
+fn level_from_u8(value: u8) -> Option<log::LevelFilter> {
+    log::LevelFilter::iter().find(|level| *level as u8 == value)
+}
+
+fn encode_name(name: &str, buffer: &mut [u8]) {
+    buffer[0] = name.len() as u8;
+    buffer[1..1 + name.len()].copy_from_slice(name.as_bytes());
+}
+
+fn decode_name<const N: usize>(buffer: &[u8]) -> Option<String<N>> {
+    let length = buffer[0] as usize;
+    if length > N {
+        return None;
+    }
+    let name = core::str::from_utf8(&buffer[1..1 + length]).ok()?;
+    String::try_from(name).ok()
+}
+
+impl Settings {
+    pub fn filter_profile(&self) -> &'static FilterProfile {
+        &FILTER_PROFILES[self.profile]
+    }
+
+    fn encode(&self) -> [u8; SharedRing::<SETTINGS_RECORD_SIZE>::PAYLOAD_SIZE] {
+        let mut record = [0u8; SharedRing::<SETTINGS_RECORD_SIZE>::PAYLOAD_SIZE];
+        record[0] = SETTINGS_VERSION;
+        record[1] = self.log_level as u8;
+        if let Some(address) = self.bound_address {
+            record[2] = 1;
+            record[3] = address.kind.into_inner();
+            record[4..10].copy_from_slice(&address.addr.into_inner());
+        }
+        encode_name(&self.target_name, &mut record[10..31]);
+        encode_name(&self.proxy_name, &mut record[31..52]);
+        record[52] = self.log_rate_limit as u8;
+        record[53] = self.log_modules.len() as u8;
+        let modules = record[SETTINGS_MODULES_OFFSET..].chunks_exact_mut(SETTINGS_MODULE_SIZE);
+        for ((name, level), buffer) in self.log_modules.iter().zip(modules) {
+            buffer[0] = *level as u8;
+            encode_name(name, &mut buffer[1..]);
+        }
+        record[SETTINGS_PROFILE_OFFSET] = self.profile as u8;
+        record[SETTINGS_POLICY_OFFSET] = self.offline_policy as u8;
+        record
+    }
+
+    fn decode(record: &[u8]) -> Option<Self> {
+        if !(SETTINGS_VERSION_MIN..=SETTINGS_VERSION).contains(&record[0]) {
+            return None;
+        }
+        let profile = record[SETTINGS_PROFILE_OFFSET] as usize;
+        if profile >= FILTER_PROFILES.len() {
+            return None;
+        }
+        let offline_policy = match record[0] {
+            4.. => *SourceOfflinePolicy::ALL.get(record[SETTINGS_POLICY_OFFSET] as usize)?,
+            _ => SOURCE_OFFLINE_POLICY,
+        };
+        let bound_address = match record[2] {
+            0 => None,
+            _ => {
+                let mut addr = [0u8; 6];
+                addr.copy_from_slice(&record[4..10]);
+                Some(Address {
+                    kind: AddrKind::new(record[3]),
+                    addr: BdAddr::new(addr),
+                })
+            }
+        };
+        let mut log_modules = ModuleFilters::new();
+        let modules = record[SETTINGS_MODULES_OFFSET..].chunks_exact(SETTINGS_MODULE_SIZE);
+        for buffer in modules.take(record[53] as usize) {
+            let filter = (decode_name(&buffer[1..])?, level_from_u8(buffer[0])?);
+            log_modules.push(filter).ok()?;
+        }
+        Some(Self {
+            target_name: decode_name(&record[10..31])?,
+            proxy_name: decode_name(&record[31..52])?,
+            bound_address,
+            log_level: level_from_u8(record[1])?,
+            log_modules,
+            log_rate_limit: record[52] != 0,
+            profile,
+            offline_policy,
+        })
+    }
+}
+
+static SETTINGS: Mutex<CriticalSectionRawMutex, RefCell<Option<Settings>>> =
+    Mutex::new(RefCell::new(None));
+static SETTINGS_RING: SharedRing<SETTINGS_RECORD_SIZE> = SharedRing::new(SETTINGS_PARTITION);
+
+// Loads the newest settings record and applies the log settings
+pub fn init() {
+    let mut settings = None;
+
+    match SETTINGS_RING.open() {
+        Ok(()) => {
+            let mut record = [0u8; SharedRing::<SETTINGS_RECORD_SIZE>::PAYLOAD_SIZE];
+            if let Ok(true) = SETTINGS_RING.read(0, &mut record) {
+                settings = Settings::decode(&record);
+                if settings.is_none() {
+                    warn!("[Settings] Stored settings are invalid, using defaults");
+                }
+            }
+        }
+        Err(e) => error!("[Settings] {}, settings will not be persisted", e),
+    }
+
+    let settings = settings.unwrap_or_default();
+    logger::apply(&settings);
+    info!("[Settings] {:?}", settings);
+    SETTINGS.lock(|cell| *cell.borrow_mut() = Some(settings));
+}
+
+pub fn get() -> Settings {
+    SETTINGS.lock(|cell| cell.borrow().clone().unwrap_or_default())
+}
+
+// Applies a change, takes effect immediately and is written to flash
+pub fn update(change: impl FnOnce(&mut Settings)) -> Result<(), StorageError> {
+    let mut settings = get();
+    change(&mut settings);
+    logger::apply(&settings);
+    let record = settings.encode();
+    SETTINGS.lock(|cell| *cell.borrow_mut() = Some(settings));
+    SETTINGS_CHANGED_SIGNAL.signal(());
+
+    SETTINGS_RING.append(&record)
+}
+
+// Drops all changes by storing the defaults
+pub fn reset() -> Result<(), StorageError> {
+    update(|settings| *settings = Settings::default())
+}
@@ -0,0 +1,116 @@
+use core::cell::RefCell;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use esp_bootloader_esp_idf::partitions::{self, PartitionTable};
+use esp_storage::FlashStorage;
+pub use magene_protocol::flash_ring::{FlashRing, Partition};
+
+use crate::errors::StorageError;
+
+// Runs `f` on the partition table and the flash it was read from, None if the
+// table could not be read
+pub fn with_partition_table<R>(
+    f: impl FnOnce(&PartitionTable<'_>, &mut FlashStorage) -> R,
+) -> Option<R> {
+    let mut flash = FlashStorage::new();
+    let mut table_buffer = [0u8; partitions::PARTITION_TABLE_MAX_LEN];
+    let table = partitions::read_partition_table(&mut flash, &mut table_buffer).ok()?;
+    Some(f(&table, &mut flash))
+}
+
+// Looks up a data partition by its label in the partition table
+pub fn find_partition(label: &'static str) -> Result<Partition, StorageError> {
+    with_partition_table(|table, _| {
+        table
+            .iter()
+            .find(|entry| entry.label_as_str() == label)
+            .map(|entry| Partition {
+                offset: entry.offset(),
+                size: entry.len() as u32,
+            })
+    })
+    .flatten()
+    .ok_or(StorageError::PartitionNotFound(label))
+}
+
+// FlashRing in a partition, shared between tasks. The lock only guards a copy
+// of the ring position, erasing and writing the flash never runs inside it.
+//
+// An append writes with a copy of the position and stores it back afterwards, so
+// two appends at once would lose a record. Appends never await and every writer
+// is a task of the one embassy executor, so one always finishes before the next
+// starts. `appending` checks that this stays so.
+pub struct SharedRing<const RECORD_SIZE: usize> {
+    label: &'static str,
+    ring: Mutex<CriticalSectionRawMutex, RefCell<Option<FlashRing<RECORD_SIZE>>>>,
+    appending: AtomicBool,
+}
+
+impl<const RECORD_SIZE: usize> SharedRing<RECORD_SIZE> {
+    pub const PAYLOAD_SIZE: usize = FlashRing::<RECORD_SIZE>::PAYLOAD_SIZE;
+
+    pub const fn new(label: &'static str) -> Self {
+        Self {
+            label,
+            ring: Mutex::new(RefCell::new(None)),
+            appending: AtomicBool::new(false),
+        }
+    }
+
+    // Finds the newest record of the partition, until then the ring stays unavailable
+    pub fn open(&self) -> Result<(), StorageError> {
+        let partition = find_partition(self.label)?;
+        let ring = FlashRing::open(&mut FlashStorage::new(), partition)
+            .map_err(StorageError::FlashError)?;
+        self.ring.lock(|cell| *cell.borrow_mut() = Some(ring));
+        Ok(())
+    }
+
+    fn ring(&self) -> Result<FlashRing<RECORD_SIZE>, StorageError> {
+        self.ring
+            .lock(|cell| cell.borrow().clone())
+            .ok_or(StorageError::PartitionNotFound(self.label))
+    }
+
+    pub fn append(&self, payload: &[u8]) -> Result<(), StorageError> {
+        let concurrent = self.appending.swap(true, Ordering::Acquire);
+        assert!(
+            !concurrent,
+            "concurrent append to the {} partition",
+            self.label
+        );
+        let result = self.write(payload);
+        self.appending.store(false, Ordering::Release);
+        result
+    }
+
+    fn write(&self, payload: &[u8]) -> Result<(), StorageError> {
+        let mut ring = self.ring()?;
+        ring.append(&mut FlashStorage::new(), payload)
+            .map_err(StorageError::FlashError)?;
+        self.ring.lock(|cell| *cell.borrow_mut() = Some(ring));
+        Ok(())
+    }
+
+    // Reads the payload of a record, `age` 0 being the newest. Returns false if there is no such record.
+    pub fn read(&self, age: u32, payload: &mut [u8]) -> Result<bool, StorageError> {
+        self.ring()?
+            .read(&mut FlashStorage::new(), age, payload)
+            .map_err(StorageError::FlashError)
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.ring.lock(|cell| cell.borrow().is_some())
+    }
+
+    pub fn len(&self) -> u32 {
+        self.ring
+            .lock(|cell| cell.borrow().as_ref().map_or(0, |ring| ring.len()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
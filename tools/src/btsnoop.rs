@@ -0,0 +1,84 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use magene_protocol::btsnoop::{
+    self, RecordHeader, FILE_HEADER, H4_ACL, RECORD_HEADER_SIZE, TRACE_LINE_PREFIX, UNIX_EPOCH_US,
+};
+
+use crate::capture::Notification;
+
+// The capture does not record handles, these only have to be consistent
+const CONNECTION_HANDLE: u16 = 0x0001;
+const ATTRIBUTE_HANDLE: u16 = 0x0010;
+// First automatically flushable packet of an L2CAP PDU
+const ACL_PACKET_START: u16 = 0x2000;
+const L2CAP_ATT_CHANNEL: u16 = 0x0004;
+const ATT_HANDLE_VALUE_NOTIFICATION: u8 = 0x1B;
+
+// H4 framed ACL packet carrying the notification as the radar sent it
+fn acl_notification(value: &[u8]) -> Vec<u8> {
+    let att_length = 3 + value.len();
+    let l2cap_length = 4 + att_length;
+
+    let mut packet = vec![H4_ACL];
+    packet.extend_from_slice(&(CONNECTION_HANDLE | ACL_PACKET_START).to_le_bytes());
+    packet.extend_from_slice(&(l2cap_length as u16).to_le_bytes());
+    packet.extend_from_slice(&(att_length as u16).to_le_bytes());
+    packet.extend_from_slice(&L2CAP_ATT_CHANNEL.to_le_bytes());
+    packet.push(ATT_HANDLE_VALUE_NOTIFICATION);
+    packet.extend_from_slice(&ATTRIBUTE_HANDLE.to_le_bytes());
+    packet.extend_from_slice(value);
+    packet
+}
+
+// Writes the notifications as received ATT notifications, timestamped with the
+// uptime of the proxy counted from 1970-01-01
+pub fn write(path: &Path, notifications: &[Notification]) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    out.write_all(&FILE_HEADER)?;
+    for notification in notifications {
+        let packet = acl_notification(&notification.data);
+        let timestamp_us = UNIX_EPOCH_US + notification.uptime_ms as u64 * 1000;
+        let flags = btsnoop::flags(H4_ACL, true);
+        out.write_all(&RecordHeader::new(packet.len() as u32, flags, timestamp_us).encode())?;
+        out.write_all(&packet)?;
+    }
+    out.flush()
+}
+
+// Record of an HCI trace line: header and packet as hex
+fn parse_trace_line(line: &str) -> Option<Vec<u8>> {
+    let (_, hex) = line.split_once(TRACE_LINE_PREFIX)?;
+    let hex = hex.trim();
+    let record = (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+
+    let header: &[u8; RECORD_HEADER_SIZE] = record.get(..RECORD_HEADER_SIZE)?.try_into().ok()?;
+    let included = RecordHeader::decode(header).included_length as usize;
+    (record.len() == RECORD_HEADER_SIZE + included).then_some(record)
+}
+
+// Collects the HCI trace records from a serial console log into a btsnoop file.
+// Returns the number of records and of broken lines that were skipped.
+pub fn write_trace(log: &Path, path: &Path) -> io::Result<(usize, usize)> {
+    let log = fs::read(log)?;
+    let log = String::from_utf8_lossy(&log);
+    let mut out = BufWriter::new(File::create(path)?);
+    out.write_all(&FILE_HEADER)?;
+
+    let (mut records, mut broken) = (0, 0);
+    for line in log.lines().filter(|line| line.contains(TRACE_LINE_PREFIX)) {
+        match parse_trace_line(line) {
+            Some(record) => {
+                out.write_all(&record)?;
+                records += 1;
+            }
+            None => broken += 1,
+        }
+    }
+    out.flush()?;
+    Ok((records, broken))
+}
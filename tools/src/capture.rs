@@ -0,0 +1,70 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use magene_protocol::download::{CaptureRecord, Frames, FRAME_CAPTURE, FRAME_END};
+
+// Raw radar notification as recorded by the proxy
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notification {
+    pub uptime_ms: u32,
+    pub data: Vec<u8>,
+}
+
+// Reads the binary answer to a capture request on the download channel, or a
+// serial console log containing the output of `capture dump`
+pub fn read(path: &Path) -> io::Result<Vec<Notification>> {
+    let bytes = fs::read(path)?;
+    if let Some(notifications) = parse_download(&bytes) {
+        return Ok(notifications);
+    }
+
+    let notifications = parse_console(&String::from_utf8_lossy(&bytes));
+    if notifications.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{}: no captured notifications found", path.display()),
+        ));
+    }
+    Ok(notifications)
+}
+
+// Capture frames followed by an end frame
+fn parse_download(bytes: &[u8]) -> Option<Vec<Notification>> {
+    let mut notifications = Vec::new();
+    for frame in Frames::new(bytes) {
+        match frame.kind {
+            FRAME_CAPTURE => {
+                let record = CaptureRecord::decode(frame.payload)?;
+                notifications.push(Notification {
+                    uptime_ms: record.uptime_ms,
+                    data: record.data.to_vec(),
+                });
+            }
+            FRAME_END => return Some(notifications),
+            _ => return None,
+        }
+    }
+    None
+}
+
+// Every line of `capture dump` is the uptime in ms followed by the payload as
+// hex bytes. Other lines, such as log messages, are skipped.
+fn parse_console(text: &str) -> Vec<Notification> {
+    text.lines().filter_map(parse_line).collect()
+}
+
+fn parse_line(line: &str) -> Option<Notification> {
+    let mut fields = line.split_whitespace();
+    let uptime_ms = fields.next()?.parse().ok()?;
+    let data = fields
+        .map(|byte| match byte.len() {
+            2 => u8::from_str_radix(byte, 16).ok(),
+            _ => None,
+        })
+        .collect::<Option<Vec<u8>>>()?;
+    if data.is_empty() {
+        return None;
+    }
+    Some(Notification { uptime_ms, data })
+}
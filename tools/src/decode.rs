@@ -0,0 +1,146 @@
+use std::cell::Cell;
+
+use embassy_time::Instant;
+use magene_protocol::bryton::{self, FRAME_SIZE};
+use magene_protocol::clock::Clock;
+use magene_protocol::escalation::EscalationRules;
+use magene_protocol::filter::FilterRules;
+use magene_protocol::magene::{self, DecodeError};
+use magene_protocol::page_buffer::{PageBuffer, Update, DATA_PAGE_TIMEOUT, PAGE_HOLD};
+use magene_protocol::pipeline::Pipeline;
+use magene_protocol::radar::{RadarFrame, RadarPage, RadarTarget};
+use magene_protocol::tracker::TrackerConfig;
+
+use crate::capture::Notification;
+
+// A notification with its decoded page and the frame the proxy forwards after it,
+// as tracked and escalated and as encoded for the client
+pub struct Decoded<'a> {
+    pub notification: &'a Notification,
+    pub page: Result<RadarPage, DecodeError>,
+    pub frame: Option<([u8; FRAME_SIZE], RadarFrame)>,
+}
+
+// Capture time of the notification being replayed
+struct ReplayClock(Cell<Instant>);
+
+impl Clock for ReplayClock {
+    fn now(&self) -> Instant {
+        self.0.get()
+    }
+}
+
+// Lets the timer fire up to `until`, as it would have between two notifications,
+// and attributes a partial frame to the row of the page that waited
+fn run_timer(
+    buffer: &mut PageBuffer<&ReplayClock>,
+    pipeline: &mut Pipeline,
+    clock: &ReplayClock,
+    pending: &mut Option<usize>,
+    frames: &mut [Option<RadarFrame>],
+    until: Option<Instant>,
+) {
+    while let Some(expiry) = buffer.next_expiry() {
+        if until.is_some_and(|until| expiry > until) {
+            break;
+        }
+        clock.0.set(clock.now().max(expiry));
+        match buffer.poll() {
+            Some(Update::Frame(frame)) => {
+                let frame = pipeline.process(&frame).frame;
+                if let Some(row) = pending.take() {
+                    frames[row] = Some(frame);
+                }
+            }
+            Some(Update::Expired) => pipeline.reset(),
+            None => {}
+        }
+    }
+}
+
+// Replays the notifications like the proxy does and attributes every forwarded
+// frame to the notification whose page completed or extended it, or whose page
+// went out without the rest of its cycle.
+pub fn decode(notifications: &[Notification]) -> Vec<Decoded<'_>> {
+    let clock = ReplayClock(Cell::new(Instant::from_millis(0)));
+    let mut buffer = PageBuffer::with_clock(PAGE_HOLD, DATA_PAGE_TIMEOUT, &clock);
+    // The default profile filters nothing
+    let mut pipeline = Pipeline::new(
+        TrackerConfig::DEFAULT,
+        FilterRules::NONE,
+        EscalationRules::DEFAULT,
+    );
+    let mut frames: Vec<Option<RadarFrame>> = vec![None; notifications.len()];
+    // Row of the page whose cycle waits for its other pages
+    let mut pending: Option<usize> = None;
+
+    for (row, notification) in notifications.iter().enumerate() {
+        let now = Instant::from_millis(notification.uptime_ms as u64);
+        run_timer(
+            &mut buffer,
+            &mut pipeline,
+            &clock,
+            &mut pending,
+            &mut frames,
+            Some(now),
+        );
+        clock.0.set(clock.now().max(now));
+
+        let Ok(page) = magene::decode_notification(&notification.data) else {
+            continue;
+        };
+        let complete = buffer.counters().complete;
+        let frame = buffer
+            .push(page)
+            .map(|frame| pipeline.process(&frame).frame);
+        if buffer.counters().complete > complete {
+            pending = None;
+            frames[row] = frame;
+            continue;
+        }
+        // Anything else is the previous cycle going out incomplete, or this page
+        // extending a frame that already went out or going out with a zero hold
+        if let Some(row) = pending.take() {
+            frames[row] = frame;
+        } else if frame.is_some() {
+            frames[row] = frame;
+            continue;
+        }
+        pending = Some(row);
+    }
+    run_timer(
+        &mut buffer,
+        &mut pipeline,
+        &clock,
+        &mut pending,
+        &mut frames,
+        None,
+    );
+
+    notifications
+        .iter()
+        .zip(frames)
+        .map(|(notification, frame)| Decoded {
+            notification,
+            page: magene::decode_notification(&notification.data)
+                .map(|page| RadarPage::decode(page.data())),
+            frame: frame.map(|frame| (bryton::encode(&frame), frame)),
+        })
+        .collect()
+}
+
+pub fn hex(data: &[u8]) -> String {
+    data.iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// Tracked targets start with their vehicle id
+pub fn target(target: &RadarTarget) -> String {
+    let id = target.id.map(|id| format!("[{}] ", id)).unwrap_or_default();
+    format!(
+        "{}{}m {}km/h {:?}",
+        id, target.range, target.speed, target.threat
+    )
+}
@@ -0,0 +1,117 @@
+use std::fs;
+use std::path::Path;
+
+use ed25519_compact::KeyPair;
+use magene_protocol::dfu::{Command, OFFSET_SIZE};
+use sha2::{Digest, Sha256};
+
+use crate::decode::hex;
+
+// Image data in a data write, DFU_DATA_SIZE of the firmware less the offset
+const CHUNK_SIZE: usize = 240;
+
+// Write to one of the characteristics of the DFU service
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Write {
+    Control(Vec<u8>),
+    Data(Vec<u8>),
+}
+
+// Reads an Ed25519 private key in the PEM format of `openssl genpkey`
+pub fn read_key(path: &Path) -> Result<KeyPair, String> {
+    let pem = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    KeyPair::from_pem(&pem).map_err(|_| format!("{}: not an Ed25519 private key", path.display()))
+}
+
+// Public half of the key as DFU_PUBLIC_KEY takes it at build time: 64 hex digits
+pub fn public_key(key: &KeyPair) -> String {
+    key.pk.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+// Signs the image and returns the writes that upload it: start with the size
+// and digest, the signature, the image in chunks from offset 0 and finish
+pub fn sign(key: &KeyPair, image: &[u8]) -> Vec<Write> {
+    let digest: [u8; 32] = Sha256::digest(image).into();
+    let signature = key.sk.sign(digest, None);
+
+    let mut writes = vec![
+        Write::Control(
+            Command::Start {
+                size: image.len() as u32,
+                digest,
+            }
+            .encode()
+            .to_vec(),
+        ),
+        Write::Control(Command::Signature(*signature).encode().to_vec()),
+    ];
+    for (index, data) in image.chunks(CHUNK_SIZE).enumerate() {
+        let mut chunk = Vec::with_capacity(OFFSET_SIZE + data.len());
+        chunk.extend_from_slice(&((index * CHUNK_SIZE) as u32).to_le_bytes());
+        chunk.extend_from_slice(data);
+        writes.push(Write::Data(chunk));
+    }
+    writes.push(Write::Control(Command::Finish.encode().to_vec()));
+    writes
+}
+
+// One write per line: the characteristic, `control` or `data`, and the value in hex
+pub fn render(writes: &[Write]) -> String {
+    writes
+        .iter()
+        .map(|write| match write {
+            Write::Control(value) => format!("control {}\n", hex(value)),
+            Write::Data(value) => format!("data {}\n", hex(value)),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use ed25519_compact::{PublicKey, Seed, Signature};
+    use magene_protocol::dfu::decode_chunk;
+
+    use super::*;
+
+    #[test]
+    fn writes_carry_the_signed_image_in_order() {
+        let key = KeyPair::from_seed(Seed::new([7; 32]));
+        let image: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+        let writes = sign(&key, &image);
+
+        let Some((Write::Control(start), rest)) = writes.split_first() else {
+            panic!("no start command");
+        };
+        let Some(Command::Start { size, digest }) = Command::decode(start) else {
+            panic!("first write is not a start command");
+        };
+        assert_eq!(size, 1000);
+        assert_eq!(digest, <[u8; 32]>::from(Sha256::digest(&image)));
+
+        let Some(Command::Signature(signature)) = (match &rest[0] {
+            Write::Control(command) => Command::decode(command),
+            Write::Data(_) => None,
+        }) else {
+            panic!("second write is not the signature");
+        };
+        PublicKey::new(*key.pk)
+            .verify(digest, &Signature::new(signature))
+            .unwrap();
+
+        let mut received = Vec::new();
+        for write in &rest[1..rest.len() - 1] {
+            let Write::Data(chunk) = write else {
+                panic!("control write between the chunks");
+            };
+            let (offset, data) = decode_chunk(chunk).unwrap();
+            assert_eq!(offset as usize, received.len());
+            assert!(data.len() <= CHUNK_SIZE);
+            received.extend_from_slice(data);
+        }
+        assert_eq!(received, image);
+        assert_eq!(
+            rest.last(),
+            Some(&Write::Control(Command::Finish.encode().to_vec()))
+        );
+    }
+}
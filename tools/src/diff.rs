@@ -0,0 +1,52 @@
+use std::fmt::Write as _;
+
+use crate::capture::Notification;
+use crate::decode::hex;
+
+// Compares the payloads of two captures notification by notification, ignoring
+// the timestamps. Returns the report and whether the captures differ.
+pub fn diff(left: &[Notification], right: &[Notification]) -> (String, bool) {
+    let mut out = String::new();
+    let mut differences = 0;
+
+    for index in 0..left.len().max(right.len()) {
+        let (left, right) = (left.get(index), right.get(index));
+        if left.map(|n| &n.data) == right.map(|n| &n.data) {
+            continue;
+        }
+        differences += 1;
+
+        let line = |notification: Option<&Notification>| match notification {
+            Some(notification) => format!(
+                "{:>10}  {}",
+                notification.uptime_ms,
+                hex(&notification.data)
+            ),
+            None => format!("{:>10}", "-"),
+        };
+        let _ = writeln!(out, "#{}", index);
+        let _ = writeln!(out, "< {}", line(left));
+        let _ = writeln!(out, "> {}", line(right));
+        if let (Some(left), Some(right)) = (left, right) {
+            // Mark the bytes that differ below the hex dump
+            let marker = (0..left.data.len().max(right.data.len()))
+                .map(|i| match left.data.get(i) == right.data.get(i) {
+                    true => "  ",
+                    false => "^^",
+                })
+                .collect::<Vec<_>>()
+                .join(" ");
+            let _ = writeln!(out, "  {:>10}  {}", "", marker.trim_end());
+        }
+    }
+
+    let _ = writeln!(
+        out,
+        "{} of {} notifications differ ({} left, {} right)",
+        differences,
+        left.len().max(right.len()),
+        left.len(),
+        right.len()
+    );
+    (out, differences > 0)
+}
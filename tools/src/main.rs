@@ -0,0 +1,131 @@
+mod btsnoop;
+mod capture;
+mod decode;
+mod dfu;
+mod diff;
+mod output;
+
+use std::fs;
+use std::path::Path;
+use std::process::ExitCode;
+
+use output::Format;
+
+const USAGE: &str = "\
+Usage: magene-tool <command> [arguments]
+
+Commands:
+  decode [--format table|csv|json] <capture>  print the decoded Magene pages and Bryton frames
+  btsnoop <capture> <output>                  convert a capture to a btsnoop file for Wireshark
+  diff <capture> <capture>                    compare the notifications of two captures
+  hci <log> <output>                          extract the HCI trace of a serial console log
+                                              into a btsnoop file
+  dfu-key <key>                               print the public key to build the firmware with
+                                              as DFU_PUBLIC_KEY
+  sign <key> <image> <output>                 sign a firmware image and write the writes that
+                                              upload it over the DFU service
+
+A capture is a serial console log with the output of `capture dump`, or the
+binary answer to a capture request on the L2CAP download channel. A key is an
+Ed25519 private key in PEM format, e.g. from `openssl genpkey -algorithm ed25519`.";
+
+fn read(path: &str) -> Result<Vec<capture::Notification>, String> {
+    capture::read(Path::new(path)).map_err(|e| format!("{}: {}", path, e))
+}
+
+fn decode_command(args: &[String]) -> Result<ExitCode, String> {
+    let (format, path) = match args {
+        [path] => (Format::Table, path),
+        [option, format, path] if option == "--format" => (format.parse()?, path),
+        _ => return Err(USAGE.to_string()),
+    };
+    let notifications = read(path)?;
+    print!(
+        "{}",
+        output::render(&decode::decode(&notifications), format)
+    );
+    Ok(ExitCode::SUCCESS)
+}
+
+fn btsnoop_command(args: &[String]) -> Result<ExitCode, String> {
+    let [path, output] = args else {
+        return Err(USAGE.to_string());
+    };
+    let notifications = read(path)?;
+    btsnoop::write(Path::new(output), &notifications).map_err(|e| format!("{}: {}", output, e))?;
+    println!(
+        "{} notifications written to {}",
+        notifications.len(),
+        output
+    );
+    Ok(ExitCode::SUCCESS)
+}
+
+fn hci_command(args: &[String]) -> Result<ExitCode, String> {
+    let [log, output] = args else {
+        return Err(USAGE.to_string());
+    };
+    let (records, broken) = btsnoop::write_trace(Path::new(log), Path::new(output))
+        .map_err(|e| format!("{}: {}", log, e))?;
+    println!("{} HCI packets written to {}", records, output);
+    if broken > 0 {
+        eprintln!("{} broken trace lines skipped", broken);
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn diff_command(args: &[String]) -> Result<ExitCode, String> {
+    let [left, right] = args else {
+        return Err(USAGE.to_string());
+    };
+    let (report, differ) = diff::diff(&read(left)?, &read(right)?);
+    print!("{}", report);
+    // Like diff(1), exit with 1 when the captures differ
+    Ok(ExitCode::from(differ as u8))
+}
+
+fn dfu_key_command(args: &[String]) -> Result<ExitCode, String> {
+    let [key] = args else {
+        return Err(USAGE.to_string());
+    };
+    println!("{}", dfu::public_key(&dfu::read_key(Path::new(key))?));
+    Ok(ExitCode::SUCCESS)
+}
+
+fn sign_command(args: &[String]) -> Result<ExitCode, String> {
+    let [key, image, output] = args else {
+        return Err(USAGE.to_string());
+    };
+    let key = dfu::read_key(Path::new(key))?;
+    let image = fs::read(image).map_err(|e| format!("{}: {}", image, e))?;
+    let writes = dfu::sign(&key, &image);
+    fs::write(output, dfu::render(&writes)).map_err(|e| format!("{}: {}", output, e))?;
+    println!(
+        "{} writes uploading a {} byte image written to {}",
+        writes.len(),
+        image.len(),
+        output
+    );
+    Ok(ExitCode::SUCCESS)
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = match args.split_first() {
+        Some((command, args)) if command == "decode" => decode_command(args),
+        Some((command, args)) if command == "btsnoop" => btsnoop_command(args),
+        Some((command, args)) if command == "diff" => diff_command(args),
+        Some((command, args)) if command == "hci" => hci_command(args),
+        Some((command, args)) if command == "dfu-key" => dfu_key_command(args),
+        Some((command, args)) if command == "sign" => sign_command(args),
+        _ => Err(USAGE.to_string()),
+    };
+
+    match result {
+        Ok(code) => code,
+        Err(message) => {
+            eprintln!("{}", message);
+            ExitCode::from(2)
+        }
+    }
+}
@@ -0,0 +1,274 @@
+use std::fmt::Write as _;
+
+use magene_protocol::radar::{RadarPage, RadarTarget};
+
+use crate::decode::{hex, target, Decoded};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Table,
+    Csv,
+    Json,
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "table" => Ok(Format::Table),
+            "csv" => Ok(Format::Csv),
+            "json" => Ok(Format::Json),
+            _ => Err(format!(
+                "unknown format '{}', expected table, csv or json",
+                s
+            )),
+        }
+    }
+}
+
+pub fn render(rows: &[Decoded], format: Format) -> String {
+    match format {
+        Format::Table => table(rows),
+        Format::Csv => csv(rows),
+        Format::Json => json(rows),
+    }
+}
+
+fn targets(targets: &[RadarTarget], separator: &str) -> String {
+    targets
+        .iter()
+        .map(target)
+        .collect::<Vec<_>>()
+        .join(separator)
+}
+
+fn page_summary(page: &RadarPage) -> String {
+    match page.online {
+        true => format!(
+            "{:#04x} #{:<3} {}",
+            page.kind,
+            page.cycle,
+            targets(&page.targets, ", ")
+        ),
+        false => format!("{:#04x} offline", page.kind),
+    }
+}
+
+fn table(rows: &[Decoded]) -> String {
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "{:>10}  {:<32}  {:<40}  bryton frame targets",
+        "uptime ms", "notification", "magene page"
+    );
+    for row in rows {
+        let page = match &row.page {
+            Ok(page) => page_summary(page),
+            Err(e) => format!("error: {}", e),
+        };
+        let frame = match &row.frame {
+            Some((_, frame)) if !frame.online => "offline".to_string(),
+            Some((_, frame)) => targets(&frame.targets, ", "),
+            None => String::new(),
+        };
+        let _ = writeln!(
+            out,
+            "{:>10}  {:<32}  {:<40}  {}",
+            row.notification.uptime_ms,
+            hex(&row.notification.data),
+            page,
+            frame
+        );
+    }
+    out
+}
+
+// Error messages contain commas, so such fields are quoted
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn csv(rows: &[Decoded]) -> String {
+    let mut out = String::from(
+        "uptime_ms,notification,page_type,cycle,page_online,page_targets,error,frame,frame_online,frame_targets\n",
+    );
+    for row in rows {
+        let (page, error) = match &row.page {
+            Ok(page) => (
+                format!(
+                    "{:#04x},{},{},{}",
+                    page.kind,
+                    page.cycle,
+                    page.online,
+                    targets(&page.targets, ";")
+                ),
+                String::new(),
+            ),
+            Err(e) => (",,,".to_string(), csv_field(&e.to_string())),
+        };
+        let frame = match &row.frame {
+            Some((data, frame)) => format!(
+                "{},{},{}",
+                hex(data),
+                frame.online,
+                targets(&frame.targets, ";")
+            ),
+            None => ",,".to_string(),
+        };
+        let _ = writeln!(
+            out,
+            "{},{},{},{},{}",
+            row.notification.uptime_ms,
+            hex(&row.notification.data),
+            page,
+            error,
+            frame
+        );
+    }
+    out
+}
+
+fn json_targets(targets: &[RadarTarget]) -> String {
+    let targets = targets
+        .iter()
+        .map(|target| {
+            format!(
+                r#"{{"id":{},"range":{},"speed":{},"threat":"{:?}"}}"#,
+                target.id.map_or("null".to_string(), |id| id.to_string()),
+                target.range,
+                target.speed,
+                target.threat
+            )
+        })
+        .collect::<Vec<_>>();
+    format!("[{}]", targets.join(","))
+}
+
+// Strings only ever hold hex digits and error messages without quotes, so
+// nothing needs to be escaped
+fn json(rows: &[Decoded]) -> String {
+    let rows = rows
+        .iter()
+        .map(|row| {
+            let mut object = format!(
+                r#"{{"uptime_ms":{},"notification":"{}""#,
+                row.notification.uptime_ms,
+                hex(&row.notification.data)
+            );
+            match &row.page {
+                Ok(page) => {
+                    let _ = write!(
+                        object,
+                        r#","page":{{"type":{},"cycle":{},"online":{},"targets":{}}}"#,
+                        page.kind,
+                        page.cycle,
+                        page.online,
+                        json_targets(&page.targets)
+                    );
+                }
+                Err(e) => {
+                    let _ = write!(object, r#","error":"{}""#, e);
+                }
+            }
+            if let Some((data, frame)) = &row.frame {
+                let _ = write!(
+                    object,
+                    r#","frame":{{"data":"{}","online":{},"targets":{}}}"#,
+                    hex(data),
+                    frame.online,
+                    json_targets(&frame.targets)
+                );
+            }
+            object.push('}');
+            object
+        })
+        .collect::<Vec<_>>();
+    format!("[\n  {}\n]\n", rows.join(",\n  "))
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::capture::Notification;
+    use crate::decode::decode;
+
+    const CSV_COLUMNS: usize = 10;
+
+    // Mostly well-formed radar notifications, with some of any length and content
+    fn notification() -> impl Strategy<Value = Notification> {
+        let data = prop_oneof![
+            3 => (any::<[u8; 3]>(), 0x30u8..=0x33, any::<[u8; 7]>()).prop_map(|(header, kind, page)| {
+                let mut data = header.to_vec();
+                data.push(kind);
+                data.extend_from_slice(&page);
+                data
+            }),
+            1 => prop::collection::vec(any::<u8>(), 0..16),
+        ];
+        (any::<u32>(), data).prop_map(|(uptime_ms, data)| Notification { uptime_ms, data })
+    }
+
+    fn csv_fields(line: &str) -> usize {
+        let mut fields = 1;
+        let mut quoted = false;
+        for c in line.chars() {
+            match c {
+                '"' => quoted = !quoted,
+                ',' if !quoted => fields += 1,
+                _ => {}
+            }
+        }
+        fields
+    }
+
+    // Depth of brackets outside of strings, None if it ever drops below zero
+    fn json_depth(json: &str) -> Option<i32> {
+        let mut depth = 0;
+        let mut quoted = false;
+        for c in json.chars() {
+            match c {
+                '"' => quoted = !quoted,
+                '{' | '[' if !quoted => depth += 1,
+                '}' | ']' if !quoted => {
+                    depth -= 1;
+                    if depth < 0 {
+                        return None;
+                    }
+                }
+                _ => {}
+            }
+        }
+        (!quoted).then_some(depth)
+    }
+
+    proptest! {
+        #[test]
+        fn table_has_a_line_per_notification(notifications in prop::collection::vec(notification(), 0..16)) {
+            let table = render(&decode(&notifications), Format::Table);
+            prop_assert_eq!(table.lines().count(), notifications.len() + 1);
+        }
+
+        #[test]
+        fn csv_rows_have_every_column(notifications in prop::collection::vec(notification(), 0..16)) {
+            let csv = render(&decode(&notifications), Format::Csv);
+            prop_assert_eq!(csv.lines().count(), notifications.len() + 1);
+            for line in csv.lines() {
+                prop_assert_eq!(csv_fields(line), CSV_COLUMNS, "{}", line);
+            }
+        }
+
+        #[test]
+        fn json_is_balanced(notifications in prop::collection::vec(notification(), 0..16)) {
+            let json = render(&decode(&notifications), Format::Json);
+            prop_assert_eq!(json_depth(&json), Some(0));
+            prop_assert_eq!(json.matches("\"uptime_ms\"").count(), notifications.len());
+        }
+    }
+}